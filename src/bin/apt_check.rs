@@ -11,12 +11,18 @@ fn main() -> Result<(), RaptoboError> {
 
     let spec = RepositorySpec::parse();
 
-    let mut repo = spec.to_repo();
+    let mut repo = spec.to_repo()?;
 
     repo.load_metadata()?;
     repo.process_files()?;
+    repo.load_packages()?;
 
     log::info!("[apt_check] found {} index files", repo.data.files.len());
+    log::info!("[apt_check] found {} packages", repo.data.packages.len());
+    log::info!(
+        "[apt_check] found {} source packages",
+        repo.data.sources.len()
+    );
 
     let meta = &repo.metadata.unwrap();
 
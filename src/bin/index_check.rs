@@ -1,9 +1,11 @@
 use raptobo::error::RaptoboError;
 use raptobo::logger::init_logger;
 use raptobo::package::PackageMetadata;
-use raptobo::utils::{download, download_gz, download_xz};
+use raptobo::transport::transport_for;
+use raptobo::utils::decompress_reader;
 use clap::{arg, command};
 use std::cmp::min;
+use std::io::BufReader;
 
 fn main() -> Result<(), RaptoboError> {
     init_logger();
@@ -13,15 +15,9 @@ fn main() -> Result<(), RaptoboError> {
         .get_matches();
 
     if let Some(url) = matches.get_one::<String>("url") {
-        let content = if url.to_lowercase().ends_with("xz") {
-            download_xz(url)?
-        } else if url.to_lowercase().ends_with("gz") {
-            download_gz(url)?
-        } else {
-            download(url)?
-        };
-
-        let packages = PackageMetadata::parse(content)?;
+        let reader = BufReader::new(transport_for(url)?.open(url)?);
+        let packages =
+            PackageMetadata::read(decompress_reader(reader)?).collect::<Result<Vec<_>, _>>()?;
 
         log::info!("Found {} packages.", packages.len());
 
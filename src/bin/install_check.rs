@@ -0,0 +1,93 @@
+use clap::{Parser, ValueEnum};
+use raptobo::checker::check;
+use raptobo::deb822::{to_stanza, write_stanzas};
+use raptobo::error::RaptoboError;
+use raptobo::logger::init_logger;
+use raptobo::repository::RepositorySpec;
+use std::io::stdout;
+use std::process::exit;
+
+/// Output format of the report.
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+enum Format {
+    /// One line per uninstallable package
+    Text,
+    /// One deb822 stanza per uninstallable package
+    Deb822,
+}
+
+#[derive(Debug, Parser)]
+struct Args {
+    #[command(flatten)]
+    spec: RepositorySpec,
+    /// Architecture to check, "all" for the binary-all index, all loaded architectures if not given
+    #[arg(short, long)]
+    architecture: Option<String>,
+    /// Component to check, all loaded components if not given; dependencies are
+    /// resolved from all loaded components
+    #[arg(long)]
+    component: Option<String>,
+    /// Output format of the report
+    #[arg(long, value_enum, default_value_t = Format::Text)]
+    format: Format,
+}
+
+/// CLI tool install_check
+///
+/// This tool reports the packages of an APT repository which are not installable,
+/// and exits with an error if there are any.
+fn main() -> Result<(), RaptoboError> {
+    init_logger();
+
+    let args = Args::parse();
+
+    let mut repo = args.spec.to_repo()?;
+
+    repo.load_metadata()?;
+    repo.process_files()?;
+
+    if let Some(component) = &args.component {
+        if !repo.selected_components()?.contains(component) {
+            return Err(RaptoboError::new(&format!(
+                "[install_check] component {} is not loaded!",
+                component
+            )));
+        }
+    }
+
+    repo.load_packages()?;
+
+    let report = check(
+        &repo.data,
+        args.component.as_deref(),
+        args.architecture.as_deref(),
+    );
+
+    match args.format {
+        Format::Text => {
+            for package in &report.uninstallable {
+                println!("{}", package);
+            }
+        }
+        Format::Deb822 => {
+            let stanzas = report
+                .uninstallable
+                .iter()
+                .map(to_stanza)
+                .collect::<Result<Vec<_>, _>>()?;
+            write_stanzas(stdout(), &stanzas)?;
+        }
+    }
+
+    log::info!(
+        "[install_check] {} of {} packages are not installable",
+        report.uninstallable.len(),
+        report.checked
+    );
+
+    if !report.uninstallable.is_empty() {
+        exit(1);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,65 @@
+use clap::Parser;
+use raptobo::error::RaptoboError;
+use raptobo::logger::init_logger;
+use raptobo::package::RelationField;
+use raptobo::rdepends::ReverseIndex;
+use raptobo::repository::RepositorySpec;
+
+#[derive(Debug, Parser)]
+struct Args {
+    #[command(flatten)]
+    spec: RepositorySpec,
+    /// Name of the package
+    package: String,
+    /// Maximum depth of the recursion, 1 lists the direct reverse dependencies only
+    #[arg(long, default_value_t = 1)]
+    depth: usize,
+    /// List the reverse dependencies recursively without depth limit
+    #[arg(long, default_value_t = false, conflicts_with = "depth")]
+    recursive: bool,
+    /// Relation fields to follow, all fields if not given
+    #[arg(long, value_enum, value_delimiter = ',')]
+    fields: Option<Vec<RelationField>>,
+}
+
+/// CLI tool rdepends
+///
+/// This tool lists the packages of an APT repository which depend on,
+/// recommend, break, etc. the given package or a package it provides.
+fn main() -> Result<(), RaptoboError> {
+    init_logger();
+
+    let args = Args::parse();
+
+    let mut repo = args.spec.to_repo()?;
+
+    repo.load_metadata()?;
+    repo.process_files()?;
+    repo.load_packages()?;
+
+    let packages = repo.data.packages.get(&args.package).ok_or_else(|| {
+        RaptoboError::new(&format!("[rdepends] package {} not found!", args.package))
+    })?;
+
+    let index = ReverseIndex::from_repository(&repo.data);
+    let fields = args.fields.unwrap_or(RelationField::ALL.to_vec());
+    let max_depth = if args.recursive {
+        None
+    } else {
+        Some(args.depth)
+    };
+
+    for package in packages {
+        println!("{} {}", package.package, package.version);
+        for dependency in index.recursive(package, &fields, max_depth) {
+            println!(
+                "{}{} (via {})",
+                "  ".repeat(dependency.depth),
+                dependency,
+                dependency.target.package
+            );
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,392 @@
+use crate::package::{PackageMetadata, PackageVersion};
+use crate::repository::RepositoryData;
+use crate::resolver::{Problem, Resolver, Unsatisfiable};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashSet};
+use std::fmt;
+
+/// Reason why a package is not installable.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Reason {
+    /// A dependency names a package which doesn't exist.
+    MissingDependency,
+    /// The dependency exists, but no version satisfies the relation.
+    UnsatisfiableVersion,
+    /// All candidates of a dependency conflict with other needed packages.
+    UnavoidableConflict,
+    /// The resolution was aborted, the package may be installable.
+    Aborted,
+}
+
+impl From<&Problem> for Reason {
+    fn from(problem: &Problem) -> Reason {
+        match problem {
+            Problem::Missing | Problem::Architecture => Reason::MissingDependency,
+            Problem::Version => Reason::UnsatisfiableVersion,
+            Problem::Conflict { .. } => Reason::UnavoidableConflict,
+            Problem::Aborted => Reason::Aborted,
+        }
+    }
+}
+
+impl fmt::Display for Reason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let reason = match self {
+            Reason::MissingDependency => "missing dependency",
+            Reason::UnsatisfiableVersion => "unsatisfiable version",
+            Reason::UnavoidableConflict => "unavoidable conflict",
+            Reason::Aborted => "aborted",
+        };
+        write!(f, "{}", reason)
+    }
+}
+
+/// Package which can't be installed from the repository.
+///
+/// The fields are named like deb822 fields, so a report can be written as stanzas.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Uninstallable {
+    #[serde(rename = "Package")]
+    pub package: String,
+    #[serde(rename = "Version")]
+    pub version: PackageVersion,
+    /// architecture of the package index
+    #[serde(rename = "Architecture")]
+    pub architecture: String,
+    #[serde(rename = "Component")]
+    pub component: Option<String>,
+    #[serde(rename = "Reason")]
+    pub reason: Reason,
+    /// the relation which can't be satisfied
+    #[serde(rename = "Relation")]
+    pub relation: String,
+    /// relations from the package to the unsatisfiable relation, e.g. "foo 1.0 Depends: bar"
+    #[serde(rename = "Chain", default)]
+    pub chain: Vec<String>,
+    /// description of the problem, e.g. the conflicting package
+    #[serde(rename = "Explanation")]
+    pub explanation: String,
+}
+
+impl Uninstallable {
+    fn new(package: &PackageMetadata, architecture: &str, error: &Unsatisfiable) -> Uninstallable {
+        Uninstallable {
+            package: package.package.clone(),
+            version: package.version.clone(),
+            architecture: architecture.to_string(),
+            component: package.component.clone(),
+            reason: Reason::from(&error.problem),
+            relation: error.relation().to_string(),
+            chain: error.chain.iter().map(|d| d.to_string()).collect(),
+            explanation: error.problem.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for Uninstallable {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} {} [{}]: {}",
+            self.package, self.version, self.architecture, self.reason
+        )?;
+        for dependency in &self.chain {
+            write!(f, " -> {}", dependency)?;
+        }
+        write!(f, ": {}", self.explanation)
+    }
+}
+
+/// Result of an installability check.
+#[derive(Debug)]
+pub struct CheckReport {
+    /// Number of checked packages.
+    pub checked: usize,
+    /// Uninstallable packages, sorted by name, version and architecture.
+    pub uninstallable: Vec<Uninstallable>,
+}
+
+/// Check which packages of the repository are installable, like dose-debcheck.
+///
+/// A package is installable if an install set for exactly its version exists,
+/// using all loaded packages of the same architecture. The packages of a
+/// binary-all index are installable on every architecture, so they are added to
+/// and checked with each of the other architectures. The checked packages can
+/// be limited to a component and an architecture, "all" for the binary-all index.
+pub fn check(
+    data: &RepositoryData,
+    component: Option<&str>,
+    architecture: Option<&str>,
+) -> CheckReport {
+    let packages: Vec<&PackageMetadata> = data
+        .packages
+        .values()
+        .flatten()
+        .map(|p| p.as_ref())
+        .collect();
+
+    let mut architectures: BTreeSet<&str> = packages
+        .iter()
+        .map(|p| index_architecture(p))
+        .filter(|a| *a != "all")
+        .collect();
+    if architectures.is_empty() {
+        // only architecture independent packages
+        architectures.insert("all");
+    }
+
+    let mut report = CheckReport {
+        checked: 0,
+        uninstallable: Vec::new(),
+    };
+
+    for arch in architectures {
+        if architecture.is_some_and(|a| a != arch && a != "all") {
+            continue;
+        }
+
+        let mut arch_packages: Vec<&PackageMetadata> = packages
+            .iter()
+            .filter(|p| index_architecture(p) == arch)
+            .copied()
+            .collect();
+        // architecture independent packages which are not also in the index of this architecture
+        let known: HashSet<(&str, &PackageVersion)> = arch_packages
+            .iter()
+            .map(|p| (p.package.as_str(), &p.version))
+            .collect();
+        let independent: Vec<&PackageMetadata> = packages
+            .iter()
+            .filter(|p| index_architecture(p) == "all" && arch != "all")
+            .filter(|p| !known.contains(&(p.package.as_str(), &p.version)))
+            .copied()
+            .collect();
+        arch_packages.extend(independent);
+        let resolver = Resolver::new(arch_packages.iter().copied()).with_architecture(arch);
+
+        for package in arch_packages {
+            if component.is_some() && package.component.as_deref() != component {
+                continue;
+            }
+            if architecture == Some("all") && index_architecture(package) != "all" {
+                continue;
+            }
+
+            report.checked += 1;
+            if let Err(error) = resolver.resolve_package(package) {
+                report
+                    .uninstallable
+                    .push(Uninstallable::new(package, arch, &error));
+            }
+        }
+    }
+
+    report.uninstallable.sort_by(|a, b| {
+        a.package
+            .cmp(&b.package)
+            .then_with(|| a.version.cmp(&b.version))
+            .then_with(|| a.architecture.cmp(&b.architecture))
+    });
+
+    report
+}
+
+/// Architecture of the index of the package, or of the package if unknown.
+fn index_architecture(package: &PackageMetadata) -> &str {
+    package
+        .index_architecture
+        .as_deref()
+        .unwrap_or(package.architecture.as_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check, Reason, Uninstallable};
+    use crate::deb822::{from_stanza, to_string, StanzaReader};
+    use crate::package::PackageMetadata;
+    use crate::repository::RepositoryData;
+
+    const PACKAGES: &str = "\
+Package: app
+Version: 1.0
+Architecture: amd64
+Depends: libfoo (>= 2.0)
+
+Package: libfoo
+Version: 1.0
+Architecture: amd64
+
+Package: tool
+Version: 1.0
+Architecture: all
+Depends: libbar
+
+Package: editor
+Version: 1.0
+Architecture: amd64
+Depends: libfoo, ui
+Conflicts: libfoo
+
+Package: ui
+Version: 1.0
+Architecture: amd64
+Depends: app | libfoo
+";
+
+    fn repository() -> RepositoryData {
+        let mut data = RepositoryData::new();
+        for architecture in ["amd64", "arm64"] {
+            for mut package in PackageMetadata::read(PACKAGES.as_bytes()).map(|p| p.unwrap()) {
+                if package.architecture != "all" && package.architecture != architecture {
+                    continue;
+                }
+                package.component = Some(String::from(if package.package == "tool" {
+                    "universe"
+                } else {
+                    "main"
+                }));
+                package.index_architecture = Some(architecture.to_string());
+                data.packages
+                    .entry(package.package.clone())
+                    .or_default()
+                    .push(Box::new(package));
+            }
+        }
+        data
+    }
+
+    #[test]
+    fn check_repository() {
+        let data = repository();
+
+        let report = check(&data, None, Some("amd64"));
+        assert_eq!(report.checked, 5);
+        let lines: Vec<String> = report.uninstallable.iter().map(|u| u.to_string()).collect();
+        assert_eq!(
+            lines,
+            [
+                "app 1.0 [amd64]: unsatisfiable version -> app 1.0 Depends: libfoo (>= 2.0): no matching version",
+                "editor 1.0 [amd64]: unavoidable conflict -> editor 1.0 Depends: libfoo: conflicts with editor 1.0",
+                "tool 1.0 [amd64]: missing dependency -> tool 1.0 Depends: libbar: missing package",
+            ]
+        );
+        assert_eq!(report.uninstallable[1].relation, "libfoo");
+
+        let report = check(&data, Some("universe"), None);
+        assert_eq!(report.checked, 2);
+        let tool: Vec<_> = report
+            .uninstallable
+            .iter()
+            .map(|u| (u.package.as_str(), u.architecture.as_str(), &u.reason))
+            .collect();
+        assert_eq!(
+            tool,
+            [
+                ("tool", "amd64", &Reason::MissingDependency),
+                ("tool", "arm64", &Reason::MissingDependency)
+            ]
+        );
+
+        assert_eq!(check(&data, Some("main"), None).checked, 4);
+        assert!(check(&data, None, Some("i386")).uninstallable.is_empty());
+    }
+
+    #[test]
+    fn check_ignores_providers_of_the_package() {
+        let mut data = repository();
+        let content = "\
+Package: libfoo-compat
+Version: 1.0
+Architecture: amd64
+Provides: app (= 1.0)
+";
+        for mut package in PackageMetadata::read(content.as_bytes()).map(|p| p.unwrap()) {
+            package.index_architecture = Some(String::from("amd64"));
+            data.packages
+                .entry(package.package.clone())
+                .or_default()
+                .push(Box::new(package));
+        }
+
+        let report = check(&data, None, Some("amd64"));
+        assert_eq!(report.uninstallable[0].package, "app");
+        assert_eq!(report.uninstallable[0].reason, Reason::UnsatisfiableVersion);
+    }
+
+    #[test]
+    fn check_binary_all_index() {
+        let content = "\
+Package: libc6
+Version: 2.36
+Architecture: amd64
+
+Package: libc6
+Version: 2.36
+Architecture: arm64
+
+Package: perl-doc
+Version: 5.36
+Architecture: all
+Depends: libc6
+
+Package: old-doc
+Version: 1.0
+Architecture: all
+Depends: libc5
+";
+        let mut data = RepositoryData::new();
+        for mut package in PackageMetadata::read(content.as_bytes()).map(|p| p.unwrap()) {
+            package.component = Some(String::from("main"));
+            package.index_architecture = Some(package.architecture.clone());
+            data.packages
+                .entry(package.package.clone())
+                .or_default()
+                .push(Box::new(package));
+        }
+
+        let report = check(&data, None, None);
+        assert_eq!(report.checked, 6);
+        let uninstallable: Vec<_> = report
+            .uninstallable
+            .iter()
+            .map(|u| (u.package.as_str(), u.architecture.as_str()))
+            .collect();
+        assert_eq!(uninstallable, [("old-doc", "amd64"), ("old-doc", "arm64")]);
+
+        assert_eq!(check(&data, None, Some("amd64")).checked, 3);
+        let report = check(&data, None, Some("all"));
+        assert_eq!(report.checked, 4);
+        assert_eq!(report.uninstallable.len(), 2);
+
+        // without architecture dependent packages, the binary-all index is checked alone
+        data.packages.remove("libc6");
+        let report = check(&data, None, None);
+        assert_eq!(report.checked, 2);
+        assert_eq!(report.uninstallable.len(), 2);
+        assert_eq!(report.uninstallable[0].architecture, "all");
+    }
+
+    #[test]
+    fn write_report() {
+        let report = check(&repository(), Some("main"), Some("amd64"));
+        let text = to_string(&report.uninstallable[0]).unwrap();
+        assert_eq!(
+            text,
+            "Package: app\n\
+             Version: 1.0\n\
+             Architecture: amd64\n\
+             Component: main\n\
+             Reason: unsatisfiable-version\n\
+             Relation: libfoo (>= 2.0)\n\
+             Chain:\n \
+             app 1.0 Depends: libfoo (>= 2.0)\n\
+             Explanation: no matching version\n"
+        );
+
+        let stanza = StanzaReader::new(text.as_bytes()).next().unwrap().unwrap();
+        let read: Uninstallable = from_stanza(&stanza).unwrap();
+        assert_eq!(read.reason, Reason::UnsatisfiableVersion);
+        assert_eq!(read.chain, ["app 1.0 Depends: libfoo (>= 2.0)"]);
+    }
+}
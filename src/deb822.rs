@@ -0,0 +1,787 @@
+mod de;
+mod ser;
+
+pub use de::{
+    field_names, from_reader, from_stanza, from_stanza_extra, from_stanza_lenient, parse_stanzas,
+    StanzaDeserializer, ValueDeserializer,
+};
+pub use ser::{to_stanza, to_string, StanzaSerializer, ValueSerializer};
+
+use crate::error::RaptoboError;
+use std::fmt;
+use std::io::{self, BufRead, Lines, Write};
+use std::ops::Index;
+
+/// Field order of Packages indices, as written by dpkg-scanpackages and apt-ftparchive.
+pub const PACKAGES_FIELD_ORDER: &[&str] = &[
+    "Package",
+    "Package-Type",
+    "Architecture",
+    "Subarchitecture",
+    "Version",
+    "Kernel-Version",
+    "Built-Using",
+    "Static-Built-Using",
+    "Built-For-Profiles",
+    "Auto-Built-Package",
+    "Multi-Arch",
+    "Status",
+    "Priority",
+    "Build-Essential",
+    "Protected",
+    "Essential",
+    "Important",
+    "Installer-Menu-Item",
+    "Section",
+    "Source",
+    "Origin",
+    "Maintainer",
+    "Original-Maintainer",
+    "Bugs",
+    "Config-Version",
+    "Conffiles",
+    "Triggers-Awaited",
+    "Triggers-Pending",
+    "Installed-Size",
+    "Provides",
+    "Pre-Depends",
+    "Depends",
+    "Recommends",
+    "Suggests",
+    "Conflicts",
+    "Breaks",
+    "Replaces",
+    "Enhances",
+    "Filename",
+    "Size",
+    "MD5sum",
+    "SHA1",
+    "SHA256",
+    "SHA512",
+    "Homepage",
+    "Description",
+    "Tag",
+    "Task",
+];
+
+/// Field order of Sources indices, as written by dpkg-scansources and apt-ftparchive.
+pub const SOURCES_FIELD_ORDER: &[&str] = &[
+    "Package",
+    "Source",
+    "Format",
+    "Binary",
+    "Architecture",
+    "Version",
+    "Priority",
+    "Section",
+    "Origin",
+    "Maintainer",
+    "Original-Maintainer",
+    "Uploaders",
+    "Dm-Upload-Allowed",
+    "Standards-Version",
+    "Build-Depends",
+    "Build-Depends-Arch",
+    "Build-Depends-Indep",
+    "Build-Conflicts",
+    "Build-Conflicts-Arch",
+    "Build-Conflicts-Indep",
+    "Testsuite",
+    "Testsuite-Triggers",
+    "Homepage",
+    "Description",
+    "Vcs-Browser",
+    "Vcs-Arch",
+    "Vcs-Bzr",
+    "Vcs-Cvs",
+    "Vcs-Darcs",
+    "Vcs-Git",
+    "Vcs-Hg",
+    "Vcs-Mtn",
+    "Vcs-Svn",
+    "Directory",
+    "Package-List",
+    "Files",
+    "Checksums-Md5",
+    "Checksums-Sha1",
+    "Checksums-Sha256",
+    "Checksums-Sha512",
+];
+
+/// Field order of Release files, as written by apt-ftparchive.
+pub const RELEASE_FIELD_ORDER: &[&str] = &[
+    "Origin",
+    "Label",
+    "Suite",
+    "Version",
+    "Codename",
+    "Changelogs",
+    "Date",
+    "Valid-Until",
+    "NotAutomatic",
+    "ButAutomaticUpgrades",
+    "Acquire-By-Hash",
+    "Signed-By",
+    "Architectures",
+    "Components",
+    "Description",
+    "MD5Sum",
+    "SHA1",
+    "SHA256",
+    "SHA512",
+];
+
+/// A field of a deb822 stanza.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Field {
+    /// Field name, in its original casing.
+    pub name: String,
+    /// Value lines: the text after the colon, followed by the continuation
+    /// lines including their leading whitespace.
+    pub value: Vec<String>,
+}
+
+/// A deb822 stanza, i.e. a paragraph of a control file.
+///
+/// The fields keep their order and original casing, lookup of field names
+/// is case-insensitive as required by Debian Policy 5.1.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Stanza {
+    fields: Vec<Field>,
+}
+
+impl Stanza {
+    pub fn new() -> Stanza {
+        Stanza { fields: Vec::new() }
+    }
+
+    /// Fields of the stanza, in the original order.
+    pub fn fields(&self) -> &[Field] {
+        &self.fields
+    }
+
+    /// Names of the fields, in the original order.
+    pub fn keys(&self) -> impl Iterator<Item = &str> {
+        self.fields.iter().map(|f| f.name.as_str())
+    }
+
+    pub fn len(&self) -> usize {
+        self.fields.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+
+    /// Get the field with the given name, ignoring the case.
+    pub fn field(&self, name: &str) -> Option<&Field> {
+        self.fields
+            .iter()
+            .find(|f| f.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Get the value lines of the field with the given name, ignoring the case.
+    pub fn get(&self, name: &str) -> Option<&Vec<String>> {
+        self.field(name).map(|f| &f.value)
+    }
+
+    pub fn contains_key(&self, name: &str) -> bool {
+        self.field(name).is_some()
+    }
+
+    /// Set the value of a field, an existing field keeps its position and casing.
+    pub fn insert(&mut self, name: &str, value: Vec<String>) {
+        match self
+            .fields
+            .iter_mut()
+            .find(|f| f.name.eq_ignore_ascii_case(name))
+        {
+            Some(field) => field.value = value,
+            None => self.push(name, value),
+        }
+    }
+
+    /// Append a field, without checking for an existing field of the same name.
+    pub fn push(&mut self, name: &str, value: Vec<String>) {
+        self.fields.push(Field {
+            name: name.to_string(),
+            value,
+        });
+    }
+
+    /// Set a single line value.
+    pub fn set_value(&mut self, name: &str, value: &str) {
+        let value = if value.is_empty() {
+            String::new()
+        } else {
+            format!(" {}", value)
+        };
+        self.insert(name, vec![value]);
+    }
+
+    /// Set a multi-line text, e.g. a description.
+    ///
+    /// The first line stays on the field line, empty lines are folded to " .".
+    pub fn set_text(&mut self, name: &str, text: &str) {
+        self.insert(name, text_lines(text));
+    }
+
+    /// Set a value consisting of lines only, e.g. a file list; the field line stays empty.
+    pub fn set_lines<I, S>(&mut self, name: &str, lines: I)
+    where
+        I: IntoIterator<Item = S>,
+        S: fmt::Display,
+    {
+        let mut value = vec![String::new()];
+        value.extend(lines.into_iter().map(|l| format!(" {}", l)));
+        self.insert(name, value);
+    }
+
+    /// Sort the fields by the given order, e.g. PACKAGES_FIELD_ORDER.
+    ///
+    /// Unknown fields are moved to the end and keep their relative order.
+    pub fn sort_fields(&mut self, order: &[&str]) {
+        self.fields.sort_by_key(|f| {
+            order
+                .iter()
+                .position(|o| o.eq_ignore_ascii_case(&f.name))
+                .unwrap_or(order.len())
+        });
+    }
+
+    /// Remove the field with the given name, ignoring the case.
+    pub fn remove(&mut self, name: &str) -> Option<Field> {
+        let index = self
+            .fields
+            .iter()
+            .position(|f| f.name.eq_ignore_ascii_case(name))?;
+        Some(self.fields.remove(index))
+    }
+}
+
+/// Value lines of a multi-line text, empty lines are folded to " .".
+///
+/// Deserializing a text field unfolds the " ." lines, so the text survives a round trip.
+pub(crate) fn text_lines(text: &str) -> Vec<String> {
+    let mut lines = text.split('\n');
+    let mut value = match lines.next() {
+        Some(first) if !first.is_empty() => vec![format!(" {}", first)],
+        _ => vec![String::new()],
+    };
+    value.extend(lines.map(|l| {
+        if l.trim().is_empty() {
+            String::from(" .")
+        } else {
+            format!(" {}", l)
+        }
+    }));
+    value
+}
+
+impl Index<&str> for Stanza {
+    type Output = Vec<String>;
+
+    /// Get the value lines of the field with the given name, ignoring the case.
+    ///
+    /// Panics if the field doesn't exist.
+    fn index(&self, name: &str) -> &Vec<String> {
+        self.get(name)
+            .unwrap_or_else(|| panic!("[Stanza] field {} not found!", name))
+    }
+}
+
+impl fmt::Display for Stanza {
+    /// Write the stanza as deb822 text, without a terminating empty line.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for field in &self.fields {
+            let mut lines = field.value.iter();
+            writeln!(
+                f,
+                "{}:{}",
+                field.name,
+                lines.next().map(|l| l.as_str()).unwrap_or("")
+            )?;
+            for line in lines {
+                writeln!(f, "{}", line)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Write the stanzas as deb822 text, each stanza terminated by an empty line
+/// like in Packages and Sources indices.
+pub fn write_stanzas<'a, W, I>(mut writer: W, stanzas: I) -> Result<(), RaptoboError>
+where
+    W: Write,
+    I: IntoIterator<Item = &'a Stanza>,
+{
+    for stanza in stanzas {
+        writeln!(writer, "{}", stanza)
+            .map_err(|e| RaptoboError::new(&format!("[write_stanzas] {}", e)))?;
+    }
+    Ok(())
+}
+
+/// Streaming parser for deb822 control files, e.g. Release, Packages and Sources indices.
+///
+/// The stanzas are read one by one, so a whole index is never held in memory.
+/// The first line of a field value is the text after the colon, continuation
+/// lines are kept including the leading whitespace.
+#[derive(Debug)]
+pub struct StanzaReader<I> {
+    lines: I,
+    done: bool,
+}
+
+impl<R: BufRead> StanzaReader<Lines<R>> {
+    /// Read the stanzas from the given reader.
+    pub fn new(reader: R) -> StanzaReader<Lines<R>> {
+        StanzaReader::from_lines(reader.lines())
+    }
+}
+
+impl<I: Iterator<Item = io::Result<String>>> StanzaReader<I> {
+    /// Read the stanzas from the given lines, without line terminators.
+    pub fn from_lines(lines: I) -> StanzaReader<I> {
+        StanzaReader { lines, done: false }
+    }
+}
+
+impl<I: Iterator<Item = io::Result<String>>> Iterator for StanzaReader<I> {
+    type Item = Result<Stanza, RaptoboError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        let mut stanza = Stanza::new();
+        let mut field: Option<(String, Vec<String>)> = None;
+
+        loop {
+            let line = match self.lines.next() {
+                None => {
+                    self.done = true;
+                    break;
+                }
+                Some(Err(e)) => {
+                    self.done = true;
+                    return Some(Err(RaptoboError::from_io(e, "[StanzaReader]")));
+                }
+                Some(Ok(line)) => line,
+            };
+
+            if line.trim().is_empty() {
+                if field.is_some() || !stanza.is_empty() {
+                    // end of stanza
+                    break;
+                }
+                // empty lines before the stanza
+                continue;
+            }
+
+            if line.starts_with(' ') || line.starts_with('\t') {
+                // follow up line
+                match &mut field {
+                    Some((_, value)) => value.push(line),
+                    None => log::debug!("[StanzaReader] follow up line without key: {}", line),
+                }
+                continue;
+            }
+
+            if let Some((key, value)) = field.take() {
+                stanza.push(&key, value);
+            }
+
+            match line.split_once(':') {
+                Some((key, value)) => field = Some((key.to_string(), vec![value.to_string()])),
+                None => log::debug!("[StanzaReader] invalid line, missing key: {}", line),
+            }
+        }
+
+        if let Some((key, value)) = field {
+            stanza.push(&key, value);
+        }
+
+        if stanza.is_empty() {
+            None
+        } else {
+            Some(Ok(stanza))
+        }
+    }
+}
+
+/// Serde helper for comma separated list fields, e.g. Binary or Testsuite.
+///
+/// Use as `#[serde(default, with = "comma_separated")]` on an `Option<Vec<T>>`,
+/// the items are parsed with FromStr and written with Display.
+pub mod comma_separated {
+    use serde::{de, Deserialize, Deserializer, Serializer};
+    use std::fmt::Display;
+    use std::str::FromStr;
+
+    pub fn serialize<S, T>(value: &Option<Vec<T>>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+        T: Display,
+    {
+        match value {
+            Some(items) if !items.is_empty() => {
+                let items: Vec<String> = items.iter().map(|i| i.to_string()).collect();
+                serializer.serialize_str(&items.join(", "))
+            }
+            _ => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D, T>(deserializer: D) -> Result<Option<Vec<T>>, D::Error>
+    where
+        D: Deserializer<'de>,
+        T: FromStr,
+        T::Err: Display,
+    {
+        let text = String::deserialize(deserializer)?;
+        let items = text
+            .split(',')
+            .map(|i| i.trim())
+            .filter(|i| !i.is_empty())
+            .map(|i| {
+                i.parse()
+                    .map_err(|e| de::Error::custom(format!("invalid item {}: {}", i, e)))
+            })
+            .collect::<Result<Vec<T>, D::Error>>()?;
+
+        if items.is_empty() {
+            Ok(None)
+        } else {
+            Ok(Some(items))
+        }
+    }
+}
+
+/// Serde helper for RFC 2822 date fields, e.g. the Date of a changes file.
+///
+/// Use as `#[serde(default, with = "rfc2822")]` on an `Option<DateTime<FixedOffset>>`.
+pub mod rfc2822 {
+    use chrono::{DateTime, FixedOffset};
+    use serde::{de, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(
+        value: &Option<DateTime<FixedOffset>>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match value {
+            Some(date) => serializer.serialize_str(&date.to_rfc2822()),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<DateTime<FixedOffset>>, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        DateTime::parse_from_rfc2822(&text)
+            .map(Some)
+            .map_err(|e| de::Error::custom(format!("invalid date {}: {}", text, e)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        comma_separated, from_reader, from_stanza, from_stanza_extra, to_stanza, Stanza,
+        StanzaReader,
+    };
+    use serde::{Deserialize, Serialize};
+    use std::fs::{self, File};
+    use std::io::BufReader;
+
+    fn data() -> String {
+        format!(
+            "{}/tests/data/repo/dists/stable",
+            env!("CARGO_MANIFEST_DIR")
+        )
+    }
+
+    /// Field names and value line counts of all stanzas, split by hand.
+    fn raw_fields(content: &str) -> Vec<Vec<(String, usize)>> {
+        let mut stanzas = Vec::new();
+        for paragraph in content.split("\n\n") {
+            let mut fields: Vec<(String, usize)> = Vec::new();
+            for line in paragraph.lines() {
+                if line.starts_with(' ') {
+                    fields.last_mut().unwrap().1 += 1;
+                } else if let Some((name, _)) = line.split_once(':') {
+                    fields.push((name.to_string(), 1));
+                }
+            }
+            if !fields.is_empty() {
+                stanzas.push(fields);
+            }
+        }
+        stanzas
+    }
+
+    fn parsed_fields(content: &str) -> Vec<Vec<(String, usize)>> {
+        StanzaReader::new(content.as_bytes())
+            .map(|stanza| {
+                stanza
+                    .unwrap()
+                    .fields()
+                    .iter()
+                    .map(|f| (f.name.to_string(), f.value.len()))
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn read_stanzas() {
+        let content = "\n\nPackage: a\nDescription: short\n more\n .\n\tend\n\n\n\
+                       Package: b\ninvalid line\nVersion: 1.0\n";
+        let stanzas: Vec<_> = StanzaReader::new(content.as_bytes())
+            .collect::<Result<_, _>>()
+            .unwrap();
+
+        assert_eq!(stanzas.len(), 2);
+        assert_eq!(stanzas[0]["Package"], vec![" a"]);
+        assert_eq!(
+            stanzas[0]["Description"],
+            vec![" short", " more", " .", "\tend"]
+        );
+        assert_eq!(stanzas[1]["Package"], vec![" b"]);
+        assert_eq!(stanzas[1]["Version"], vec![" 1.0"]);
+        assert_eq!(stanzas[1].len(), 2);
+    }
+
+    #[test]
+    fn read_stanzas_lazily() {
+        let file = File::open(format!(
+            "{}/tests/data/repo/dists/stable/main/binary-amd64/Packages",
+            env!("CARGO_MANIFEST_DIR")
+        ))
+        .unwrap();
+
+        let mut reader = StanzaReader::new(BufReader::new(file));
+        let first = reader.next().unwrap().unwrap();
+        assert_eq!(first["Package"], vec![" accountsservice"]);
+        assert_eq!(reader.count(), 7);
+    }
+
+    #[test]
+    fn read_error() {
+        let mut reader = StanzaReader::new(&b"Package: a\nDescription: \xFF\n"[..]);
+        assert!(reader.next().unwrap().is_err());
+        assert!(reader.next().is_none());
+    }
+
+    #[test]
+    fn keep_all_fields() {
+        for file in ["Release", "main/binary-amd64/Packages"] {
+            let content = fs::read_to_string(format!("{}/{}", data(), file)).unwrap();
+            let expected = raw_fields(&content);
+            assert!(!expected.is_empty());
+            assert_eq!(parsed_fields(&content), expected, "{}", file);
+
+            // without trailing newline
+            let content = content.trim_end();
+            assert_eq!(parsed_fields(content), expected, "{}", file);
+        }
+    }
+
+    #[test]
+    fn stanza_lookup() {
+        let mut stanza = StanzaReader::new(&b"Package: a\nMD5sum: 1\nVersion: 1.0\n"[..])
+            .next()
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(stanza.get("md5SUM"), Some(&vec![String::from(" 1")]));
+        assert!(stanza.contains_key("package"));
+        assert!(!stanza.contains_key("Depends"));
+        assert_eq!(
+            stanza.keys().collect::<Vec<_>>(),
+            ["Package", "MD5sum", "Version"]
+        );
+
+        stanza.insert("md5sum", vec![String::from(" 2")]);
+        stanza.insert("Depends", vec![String::from(" b")]);
+        assert_eq!(
+            stanza.keys().collect::<Vec<_>>(),
+            ["Package", "MD5sum", "Version", "Depends"]
+        );
+        assert_eq!(stanza["MD5Sum"], vec![" 2"]);
+
+        assert_eq!(stanza.remove("VERSION").unwrap().name, "Version");
+        assert_eq!(stanza.len(), 3);
+        assert_ne!(stanza, Stanza::new());
+    }
+
+    #[derive(Debug, PartialEq, Deserialize, Serialize)]
+    struct Checksum {
+        hash: String,
+        size: u64,
+        path: String,
+    }
+
+    #[derive(Debug, PartialEq, Deserialize, Serialize)]
+    #[serde(rename_all = "PascalCase")]
+    struct Upload {
+        source: String,
+        version: String,
+        #[serde(default, with = "comma_separated")]
+        binary: Option<Vec<String>>,
+        architecture: Vec<String>,
+        #[serde(rename = "Installed-Size")]
+        installed_size: Option<u64>,
+        essential: bool,
+        description: Option<String>,
+        #[serde(rename = "Checksums-Sha256", default)]
+        checksums: Vec<Checksum>,
+    }
+
+    #[test]
+    fn serde_roundtrip() {
+        let content = "Source: hello\n\
+                       Version: 2.10-3\n\
+                       Binary: hello, hello-dbg\n\
+                       Architecture: amd64 arm64\n\
+                       Installed-Size: 280\n\
+                       Essential: no\n\
+                       Description: example package\n \
+                       The GNU hello program.\n \
+                       .\n \
+                       It prints a greeting.\n\
+                       Checksums-Sha256:\n \
+                       e0b1 1000 hello_2.10.orig.tar.gz\n \
+                       a1b2 200 hello_2.10-3.debian.tar.xz\n";
+
+        let upload: Upload = from_reader(content.as_bytes()).next().unwrap().unwrap();
+        assert_eq!(upload.source, "hello");
+        assert_eq!(
+            upload.binary,
+            Some(vec![String::from("hello"), String::from("hello-dbg")])
+        );
+        assert_eq!(upload.architecture, ["amd64", "arm64"]);
+        assert_eq!(upload.installed_size, Some(280));
+        assert!(!upload.essential);
+        assert_eq!(
+            upload.description.as_deref(),
+            Some("example package\nThe GNU hello program.\n\nIt prints a greeting.")
+        );
+        assert_eq!(upload.checksums.len(), 2);
+        assert_eq!(
+            upload.checksums[1],
+            Checksum {
+                hash: String::from("a1b2"),
+                size: 200,
+                path: String::from("hello_2.10-3.debian.tar.xz"),
+            }
+        );
+
+        assert_eq!(to_stanza(&upload).unwrap().to_string(), content);
+
+        // field names are matched ignoring the case, None values are skipped
+        let stanza =
+            StanzaReader::new(&b"source: a\nVERSION: 1\narchitecture: all\nEssential: yes\n"[..])
+                .next()
+                .unwrap()
+                .unwrap();
+        let upload: Upload = from_stanza(&stanza).unwrap();
+        assert!(upload.essential);
+        assert_eq!(upload.binary, None);
+        assert_eq!(
+            to_stanza(&upload).unwrap().keys().collect::<Vec<_>>(),
+            ["Source", "Version", "Architecture", "Essential"]
+        );
+    }
+
+    #[test]
+    fn serde_verbatim_lines() {
+        let content = "Source: hello\n\
+                       Version: 2.10-3\n\
+                       Architecture: all\n\
+                       Essential: no\n\
+                       Description: example package\n \
+                       Usage:\n \
+                       .\n  \
+                       hello --greeting=text\n   \
+                       indented  twice\n \
+                       .\n \
+                       The end.\n";
+
+        let upload: Upload = from_reader(content.as_bytes()).next().unwrap().unwrap();
+        assert_eq!(
+            upload.description.as_deref(),
+            Some(
+                "example package\nUsage:\n\n hello --greeting=text\n  indented  twice\n\nThe end."
+            )
+        );
+        assert_eq!(to_stanza(&upload).unwrap().to_string(), content);
+    }
+
+    #[test]
+    fn serde_text_roundtrip() {
+        let upload = Upload {
+            source: String::from("hello"),
+            version: String::from("2.10-3"),
+            binary: None,
+            architecture: vec![String::from("all")],
+            installed_size: None,
+            essential: false,
+            description: Some(String::from("short\npara one\n\npara two\n  verbatim")),
+            checksums: Vec::new(),
+        };
+
+        let text = to_stanza(&upload).unwrap().to_string();
+        assert!(text.contains("Description: short\n para one\n .\n para two\n   verbatim\n"));
+        let read: Upload = from_reader(text.as_bytes()).next().unwrap().unwrap();
+        assert_eq!(read, upload);
+    }
+
+    #[test]
+    fn serde_field_errors() {
+        let read = |content: &str| {
+            let stanza = StanzaReader::new(content.as_bytes())
+                .next()
+                .unwrap()
+                .unwrap();
+            from_stanza::<Upload>(&stanza).unwrap_err().to_string()
+        };
+
+        let error = read("Source: a\nVersion: 1\nArchitecture: all\nEssential: maybe\n");
+        assert_eq!(
+            error,
+            "[deb822] field Essential: invalid boolean maybe, expected yes or no"
+        );
+
+        let error =
+            read("Source: a\nVersion: 1\nArchitecture: all\nEssential: no\nInstalled-Size: big\n");
+        assert!(error.starts_with("[deb822] field Installed-Size: invalid number big"));
+
+        let error = read("Source: a\nVersion: 1\nEssential: no\n");
+        assert_eq!(error, "missing field `Architecture`");
+    }
+
+    #[test]
+    fn serde_extra_fields() {
+        let content = "Source: a\nVersion: 1\nArchitecture: all\nX-Custom: value\n\
+                       Installed-Size: big\nVcs-Git: https://example.org/a.git\nEssential: no\n";
+        let stanza = StanzaReader::new(content.as_bytes())
+            .next()
+            .unwrap()
+            .unwrap();
+
+        let (upload, extra, errors): (Upload, _, _) =
+            from_stanza_extra(&stanza, &[], &["Vcs-Git"]).unwrap();
+        assert_eq!(upload.installed_size, None);
+        assert_eq!(
+            extra.keys().collect::<Vec<_>>(),
+            ["X-Custom", "Installed-Size"]
+        );
+        assert_eq!(errors.len(), 1);
+
+        assert!(from_stanza_extra::<Upload>(&stanza, &["Installed-Size"], &[]).is_err());
+    }
+}
@@ -0,0 +1,468 @@
+use super::{Field, Stanza, StanzaReader};
+use crate::error::{ErrorKind, RaptoboError};
+use serde::de::{
+    self, DeserializeOwned, DeserializeSeed, IntoDeserializer, MapAccess, SeqAccess, Visitor,
+};
+use std::io::BufRead;
+use std::slice;
+
+/// Deserialize a value, usually a struct, from a stanza.
+///
+/// Struct fields are matched case-insensitively against the field names,
+/// unknown fields are ignored unless the struct denies them.
+pub fn from_stanza<T: DeserializeOwned>(stanza: &Stanza) -> Result<T, RaptoboError> {
+    T::deserialize(StanzaDeserializer { stanza })
+}
+
+/// Deserialize a value like from_stanza, but skip the fields with invalid values.
+///
+/// The skipped fields are returned with their errors; if a required field or
+/// one of the `strict` fields is invalid, its error is returned.
+pub fn from_stanza_lenient<T: DeserializeOwned>(
+    stanza: &Stanza,
+    strict: &[&str],
+) -> Result<(T, Vec<(Field, RaptoboError)>), RaptoboError> {
+    let mut valid = stanza.clone();
+    let mut invalid: Vec<(Field, RaptoboError)> = Vec::new();
+    loop {
+        let e = match from_stanza(&valid) {
+            Ok(value) => return Ok((value, invalid)),
+            Err(e) => e,
+        };
+        let field = match e.kind() {
+            ErrorKind::InvalidField(name)
+                if !strict.iter().any(|s| s.eq_ignore_ascii_case(name)) =>
+            {
+                valid.remove(name)
+            }
+            _ => None,
+        };
+        match field {
+            Some(field) => invalid.push((field, e)),
+            None => {
+                // e.g. the missing field error of a skipped required field
+                let message = e.to_string().to_lowercase();
+                let skipped = invalid
+                    .into_iter()
+                    .find(|(f, _)| message.contains(&format!("`{}`", f.name.to_lowercase())));
+                return Err(skipped.map(|(_, skipped)| skipped).unwrap_or(e));
+            }
+        }
+    }
+}
+
+/// Deserialize a value like from_stanza_lenient, and keep the fields without a
+/// typed value as extra fields, in their original order.
+///
+/// Fields with invalid values are extra fields too, their errors are returned
+/// separately. The `known` fields are handled by the caller, e.g. Vcs-Git, and
+/// are not extra fields.
+pub fn from_stanza_extra<T: DeserializeOwned>(
+    stanza: &Stanza,
+    strict: &[&str],
+    known: &[&str],
+) -> Result<(T, Stanza, Vec<RaptoboError>), RaptoboError> {
+    let (value, invalid) = from_stanza_lenient::<T>(stanza, strict)?;
+
+    let names = field_names::<T>();
+    let mut extra = Stanza::new();
+    for field in stanza.fields() {
+        let typed = names
+            .iter()
+            .chain(known)
+            .any(|n| n.eq_ignore_ascii_case(&field.name))
+            && !invalid.iter().any(|(f, _)| f.name == field.name);
+        if !typed {
+            extra.push(&field.name, field.value.clone());
+        }
+    }
+
+    Ok((value, extra, invalid.into_iter().map(|(_, e)| e).collect()))
+}
+
+/// Parse the stanzas lazily with the given function, e.g. into packages.
+///
+/// Stanzas which can't be parsed are logged with the context and skipped,
+/// read errors are returned.
+pub fn parse_stanzas<T, I, F>(
+    stanzas: I,
+    parse: F,
+    context: &'static str,
+) -> impl Iterator<Item = Result<T, RaptoboError>>
+where
+    I: Iterator<Item = Result<Stanza, RaptoboError>>,
+    F: Fn(Stanza) -> Result<T, RaptoboError>,
+{
+    stanzas.filter_map(move |stanza| match stanza.map(&parse) {
+        Ok(Ok(value)) => Some(Ok(value)),
+        Ok(Err(e)) => {
+            log::error!("[{}] error: {}", context, e);
+            None
+        }
+        Err(e) => Some(Err(e)),
+    })
+}
+
+/// Deserialize all stanzas of the reader lazily.
+pub fn from_reader<T: DeserializeOwned, R: BufRead>(
+    reader: R,
+) -> impl Iterator<Item = Result<T, RaptoboError>> {
+    StanzaReader::new(reader).map(|stanza| stanza.and_then(|s| from_stanza(&s)))
+}
+
+/// Field names of a struct deserialized with from_stanza, without the skipped fields.
+///
+/// Fields of a stanza not in this list are ignored by the deserialization,
+/// e.g. to keep them separately.
+pub fn field_names<T: DeserializeOwned>() -> &'static [&'static str] {
+    let mut names: &'static [&'static str] = &[];
+    // the deserializer only records the names, the error is expected
+    let _ = T::deserialize(FieldNames { names: &mut names });
+    names
+}
+
+/// Deserializer for a whole stanza, which is a map of fields.
+pub struct StanzaDeserializer<'a> {
+    stanza: &'a Stanza,
+}
+
+impl<'a> StanzaDeserializer<'a> {
+    pub fn new(stanza: &'a Stanza) -> StanzaDeserializer<'a> {
+        StanzaDeserializer { stanza }
+    }
+}
+
+impl<'de, 'a> de::Deserializer<'de> for StanzaDeserializer<'a> {
+    type Error = RaptoboError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, RaptoboError> {
+        self.deserialize_map(visitor)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, RaptoboError> {
+        visitor.visit_map(FieldAccess {
+            fields: self.stanza.fields().iter(),
+            names: &[],
+            value: None,
+        })
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, RaptoboError> {
+        visitor.visit_map(FieldAccess {
+            fields: self.stanza.fields().iter(),
+            names: fields,
+            value: None,
+        })
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct enum identifier ignored_any
+    }
+}
+
+/// Access to the fields of a stanza.
+struct FieldAccess<'a> {
+    fields: slice::Iter<'a, Field>,
+    /// Field names of the struct, to map the field names ignoring the case.
+    names: &'static [&'static str],
+    value: Option<&'a Field>,
+}
+
+impl<'de, 'a> MapAccess<'de> for FieldAccess<'a> {
+    type Error = RaptoboError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, RaptoboError> {
+        let field = match self.fields.next() {
+            Some(field) => field,
+            None => return Ok(None),
+        };
+        self.value = Some(field);
+
+        let name = self
+            .names
+            .iter()
+            .find(|n| n.eq_ignore_ascii_case(&field.name))
+            .copied()
+            .unwrap_or(field.name.as_str());
+        seed.deserialize(IntoDeserializer::<RaptoboError>::into_deserializer(name))
+            .map(Some)
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(
+        &mut self,
+        seed: V,
+    ) -> Result<V::Value, RaptoboError> {
+        let field = self
+            .value
+            .take()
+            .ok_or(RaptoboError::new("[deb822] value without field!"))?;
+
+        seed.deserialize(ValueDeserializer {
+            lines: field.value.clone(),
+        })
+        .map_err(|e| {
+            RaptoboError::with_kind(
+                ErrorKind::InvalidField(field.name.clone()),
+                &format!("[deb822] field {}: {}", field.name, e),
+            )
+        })
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.fields.len())
+    }
+}
+
+/// Deserializer for the value of a field.
+///
+/// - strings: the value lines, trimmed and joined by newlines
+/// - booleans: yes or no
+/// - numbers: parsed from the trimmed value
+/// - sequences: the continuation lines of a multi-line field, otherwise the
+///   comma separated items, or the whitespace separated items
+/// - structs and tuples: the whitespace separated columns, e.g. of a file list line
+/// - enums: unit variants by name
+pub struct ValueDeserializer {
+    lines: Vec<String>,
+}
+
+impl ValueDeserializer {
+    pub fn new(lines: Vec<String>) -> ValueDeserializer {
+        ValueDeserializer { lines }
+    }
+
+    /// Value text, continuation lines lose only the single folding space,
+    /// to keep verbatim lines, e.g. of a Description, indented.
+    ///
+    /// A " ." continuation line is an empty line, the inverse of `text_lines`.
+    fn text(&self) -> String {
+        let lines: Vec<&str> = self
+            .lines
+            .iter()
+            .enumerate()
+            .map(|(i, l)| match i {
+                0 => l.trim(),
+                _ => match l.trim() {
+                    "." => "",
+                    _ => l
+                        .strip_prefix([' ', '\t'])
+                        .unwrap_or(l.trim_start())
+                        .trim_end(),
+                },
+            })
+            .collect();
+        lines.join("\n")
+    }
+
+    fn items(&self) -> Vec<String> {
+        let first = self.lines.first().map(|l| l.trim()).unwrap_or("");
+
+        if self.lines.len() > 1 {
+            self.lines
+                .iter()
+                .map(|l| l.trim())
+                .filter(|l| !l.is_empty())
+                .map(|l| l.to_string())
+                .collect()
+        } else if first.contains(',') {
+            first
+                .split(',')
+                .map(|i| i.trim())
+                .filter(|i| !i.is_empty())
+                .map(|i| i.to_string())
+                .collect()
+        } else {
+            first.split_whitespace().map(|i| i.to_string()).collect()
+        }
+    }
+
+    fn columns(&self) -> Vec<String> {
+        self.text()
+            .split_whitespace()
+            .map(|c| c.to_string())
+            .collect()
+    }
+}
+
+macro_rules! deserialize_number {
+    ($($method:ident => $visit:ident),*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, RaptoboError> {
+                let text = self.text();
+                let value = text
+                    .parse()
+                    .map_err(|e| RaptoboError::new(&format!("invalid number {}: {}", text, e)))?;
+                visitor.$visit(value)
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for ValueDeserializer {
+    type Error = RaptoboError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, RaptoboError> {
+        visitor.visit_string(self.text())
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, RaptoboError> {
+        let text = self.text();
+        if text.eq_ignore_ascii_case("yes") {
+            visitor.visit_bool(true)
+        } else if text.eq_ignore_ascii_case("no") {
+            visitor.visit_bool(false)
+        } else {
+            Err(RaptoboError::new(&format!(
+                "invalid boolean {}, expected yes or no",
+                text
+            )))
+        }
+    }
+
+    deserialize_number! {
+        deserialize_i8 => visit_i8,
+        deserialize_i16 => visit_i16,
+        deserialize_i32 => visit_i32,
+        deserialize_i64 => visit_i64,
+        deserialize_u8 => visit_u8,
+        deserialize_u16 => visit_u16,
+        deserialize_u32 => visit_u32,
+        deserialize_u64 => visit_u64,
+        deserialize_f32 => visit_f32,
+        deserialize_f64 => visit_f64
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, RaptoboError> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, RaptoboError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, RaptoboError> {
+        visitor.visit_seq(Items {
+            items: self.items().into_iter(),
+        })
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(
+        self,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, RaptoboError> {
+        visitor.visit_seq(Items {
+            items: self.columns().into_iter(),
+        })
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, RaptoboError> {
+        self.deserialize_tuple(len, visitor)
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, RaptoboError> {
+        self.deserialize_tuple(fields.len(), visitor)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, RaptoboError> {
+        visitor.visit_enum(IntoDeserializer::<RaptoboError>::into_deserializer(
+            self.text(),
+        ))
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(
+        self,
+        visitor: V,
+    ) -> Result<V::Value, RaptoboError> {
+        visitor.visit_unit()
+    }
+
+    serde::forward_to_deserialize_any! {
+        i128 u128 char str string bytes byte_buf unit unit_struct map identifier
+    }
+}
+
+/// Access to the items of a list value.
+struct Items {
+    items: std::vec::IntoIter<String>,
+}
+
+impl<'de> SeqAccess<'de> for Items {
+    type Error = RaptoboError;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, RaptoboError> {
+        match self.items.next() {
+            Some(item) => seed
+                .deserialize(ValueDeserializer::new(vec![item]))
+                .map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.items.len())
+    }
+}
+
+/// Deserializer which only records the field names of a struct.
+struct FieldNames<'a> {
+    names: &'a mut &'static [&'static str],
+}
+
+impl<'de, 'a> de::Deserializer<'de> for FieldNames<'a> {
+    type Error = RaptoboError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, RaptoboError> {
+        Err(RaptoboError::new(
+            "[deb822] field names of a non-struct type!",
+        ))
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        _visitor: V,
+    ) -> Result<V::Value, RaptoboError> {
+        *self.names = fields;
+        Err(RaptoboError::new("[deb822] field names recorded"))
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map enum identifier ignored_any
+    }
+}
@@ -0,0 +1,515 @@
+use super::{text_lines, Stanza};
+use crate::error::RaptoboError;
+use serde::ser::{self, Impossible, Serialize};
+
+/// Serialize a value, usually a struct, to a stanza.
+///
+/// The fields keep the order of the struct, None values are skipped.
+pub fn to_stanza<T: Serialize + ?Sized>(value: &T) -> Result<Stanza, RaptoboError> {
+    value.serialize(StanzaSerializer)
+}
+
+/// Serialize a value to deb822 text, without a terminating empty line.
+pub fn to_string<T: Serialize + ?Sized>(value: &T) -> Result<String, RaptoboError> {
+    Ok(to_stanza(value)?.to_string())
+}
+
+fn unsupported(what: &str) -> RaptoboError {
+    RaptoboError::new(&format!("[deb822] {} are not supported!", what))
+}
+
+/// Serializer for a whole stanza, which is a struct or a map of fields.
+pub struct StanzaSerializer;
+
+/// Serializer for the fields of a struct or map.
+pub struct FieldSerializer {
+    stanza: Stanza,
+    key: Option<String>,
+}
+
+impl FieldSerializer {
+    fn field<T: Serialize + ?Sized>(&mut self, name: &str, value: &T) -> Result<(), RaptoboError> {
+        let value = value
+            .serialize(ValueSerializer)
+            .map_err(|e| RaptoboError::new(&format!("[deb822] field {}: {}", name, e)))?;
+        if let Some(lines) = value {
+            self.stanza.insert(name, lines);
+        }
+        Ok(())
+    }
+}
+
+impl ser::Serializer for StanzaSerializer {
+    type Ok = Stanza;
+    type Error = RaptoboError;
+
+    type SerializeSeq = Impossible<Stanza, RaptoboError>;
+    type SerializeTuple = Impossible<Stanza, RaptoboError>;
+    type SerializeTupleStruct = Impossible<Stanza, RaptoboError>;
+    type SerializeTupleVariant = Impossible<Stanza, RaptoboError>;
+    type SerializeMap = FieldSerializer;
+    type SerializeStruct = FieldSerializer;
+    type SerializeStructVariant = Impossible<Stanza, RaptoboError>;
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<FieldSerializer, RaptoboError> {
+        Ok(FieldSerializer {
+            stanza: Stanza::new(),
+            key: None,
+        })
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<FieldSerializer, RaptoboError> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Stanza, RaptoboError> {
+        value.serialize(self)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Stanza, RaptoboError> {
+        value.serialize(self)
+    }
+
+    fn serialize_bool(self, _v: bool) -> Result<Stanza, RaptoboError> {
+        Err(unsupported("top level values"))
+    }
+
+    fn serialize_i8(self, _v: i8) -> Result<Stanza, RaptoboError> {
+        Err(unsupported("top level values"))
+    }
+
+    fn serialize_i16(self, _v: i16) -> Result<Stanza, RaptoboError> {
+        Err(unsupported("top level values"))
+    }
+
+    fn serialize_i32(self, _v: i32) -> Result<Stanza, RaptoboError> {
+        Err(unsupported("top level values"))
+    }
+
+    fn serialize_i64(self, _v: i64) -> Result<Stanza, RaptoboError> {
+        Err(unsupported("top level values"))
+    }
+
+    fn serialize_u8(self, _v: u8) -> Result<Stanza, RaptoboError> {
+        Err(unsupported("top level values"))
+    }
+
+    fn serialize_u16(self, _v: u16) -> Result<Stanza, RaptoboError> {
+        Err(unsupported("top level values"))
+    }
+
+    fn serialize_u32(self, _v: u32) -> Result<Stanza, RaptoboError> {
+        Err(unsupported("top level values"))
+    }
+
+    fn serialize_u64(self, _v: u64) -> Result<Stanza, RaptoboError> {
+        Err(unsupported("top level values"))
+    }
+
+    fn serialize_f32(self, _v: f32) -> Result<Stanza, RaptoboError> {
+        Err(unsupported("top level values"))
+    }
+
+    fn serialize_f64(self, _v: f64) -> Result<Stanza, RaptoboError> {
+        Err(unsupported("top level values"))
+    }
+
+    fn serialize_char(self, _v: char) -> Result<Stanza, RaptoboError> {
+        Err(unsupported("top level values"))
+    }
+
+    fn serialize_str(self, _v: &str) -> Result<Stanza, RaptoboError> {
+        Err(unsupported("top level values"))
+    }
+
+    fn serialize_bytes(self, _v: &[u8]) -> Result<Stanza, RaptoboError> {
+        Err(unsupported("top level values"))
+    }
+
+    fn serialize_none(self) -> Result<Stanza, RaptoboError> {
+        Ok(Stanza::new())
+    }
+
+    fn serialize_unit(self) -> Result<Stanza, RaptoboError> {
+        Ok(Stanza::new())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Stanza, RaptoboError> {
+        Ok(Stanza::new())
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+    ) -> Result<Stanza, RaptoboError> {
+        Err(unsupported("top level values"))
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<Stanza, RaptoboError> {
+        Err(unsupported("enum variants with values"))
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, RaptoboError> {
+        Err(unsupported("top level sequences"))
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, RaptoboError> {
+        Err(unsupported("top level sequences"))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct, RaptoboError> {
+        Err(unsupported("top level sequences"))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, RaptoboError> {
+        Err(unsupported("enum variants with values"))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, RaptoboError> {
+        Err(unsupported("enum variants with values"))
+    }
+}
+
+impl ser::SerializeStruct for FieldSerializer {
+    type Ok = Stanza;
+    type Error = RaptoboError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), RaptoboError> {
+        self.field(key, value)
+    }
+
+    fn end(self) -> Result<Stanza, RaptoboError> {
+        Ok(self.stanza)
+    }
+}
+
+impl ser::SerializeMap for FieldSerializer {
+    type Ok = Stanza;
+    type Error = RaptoboError;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), RaptoboError> {
+        let key = key
+            .serialize(ValueSerializer)?
+            .map(|lines| lines.join("\n").trim().to_string())
+            .ok_or(RaptoboError::new("[deb822] empty field name!"))?;
+        self.key = Some(key);
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), RaptoboError> {
+        let key = self
+            .key
+            .take()
+            .ok_or(RaptoboError::new("[deb822] value without field name!"))?;
+        self.field(&key, value)
+    }
+
+    fn end(self) -> Result<Stanza, RaptoboError> {
+        Ok(self.stanza)
+    }
+}
+
+/// Serializer for the value of a field, the counterpart of the ValueDeserializer.
+///
+/// The result are the value lines, None if the field is skipped.
+/// Sequences are written as whitespace separated items, or as continuation
+/// lines if an item contains whitespace, e.g. a file list.
+pub struct ValueSerializer;
+
+fn value(text: &str) -> Result<Option<Vec<String>>, RaptoboError> {
+    Ok(Some(text_lines(text)))
+}
+
+/// Text of a single item, e.g. a list entry or a column.
+fn item<T: Serialize + ?Sized>(value: &T) -> Result<Option<String>, RaptoboError> {
+    Ok(value.serialize(ValueSerializer)?.map(|lines| {
+        let lines: Vec<&str> = lines.iter().map(|l| l.trim()).collect();
+        lines.join(" ").trim().to_string()
+    }))
+}
+
+impl ser::Serializer for ValueSerializer {
+    type Ok = Option<Vec<String>>;
+    type Error = RaptoboError;
+
+    type SerializeSeq = ItemSerializer;
+    type SerializeTuple = ColumnSerializer;
+    type SerializeTupleStruct = ColumnSerializer;
+    type SerializeTupleVariant = Impossible<Option<Vec<String>>, RaptoboError>;
+    type SerializeMap = Impossible<Option<Vec<String>>, RaptoboError>;
+    type SerializeStruct = ColumnSerializer;
+    type SerializeStructVariant = Impossible<Option<Vec<String>>, RaptoboError>;
+
+    fn serialize_bool(self, v: bool) -> Result<Self::Ok, RaptoboError> {
+        value(if v { "yes" } else { "no" })
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Self::Ok, RaptoboError> {
+        value(&v.to_string())
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Self::Ok, RaptoboError> {
+        value(&v.to_string())
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Self::Ok, RaptoboError> {
+        value(&v.to_string())
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Self::Ok, RaptoboError> {
+        value(&v.to_string())
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Self::Ok, RaptoboError> {
+        value(&v.to_string())
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Self::Ok, RaptoboError> {
+        value(&v.to_string())
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Self::Ok, RaptoboError> {
+        value(&v.to_string())
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Self::Ok, RaptoboError> {
+        value(&v.to_string())
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Self::Ok, RaptoboError> {
+        value(&v.to_string())
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Self::Ok, RaptoboError> {
+        value(&v.to_string())
+    }
+
+    fn serialize_char(self, v: char) -> Result<Self::Ok, RaptoboError> {
+        value(&v.to_string())
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Self::Ok, RaptoboError> {
+        value(v)
+    }
+
+    fn serialize_bytes(self, _v: &[u8]) -> Result<Self::Ok, RaptoboError> {
+        Err(unsupported("byte values"))
+    }
+
+    fn serialize_none(self) -> Result<Self::Ok, RaptoboError> {
+        Ok(None)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Self::Ok, RaptoboError> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Self::Ok, RaptoboError> {
+        Ok(None)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Self::Ok, RaptoboError> {
+        Ok(None)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+    ) -> Result<Self::Ok, RaptoboError> {
+        value(variant)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok, RaptoboError> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<Self::Ok, RaptoboError> {
+        Err(unsupported("enum variants with values"))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<ItemSerializer, RaptoboError> {
+        Ok(ItemSerializer {
+            items: Vec::with_capacity(len.unwrap_or(0)),
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<ColumnSerializer, RaptoboError> {
+        Ok(ColumnSerializer {
+            columns: Vec::with_capacity(len),
+        })
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<ColumnSerializer, RaptoboError> {
+        self.serialize_tuple(len)
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, RaptoboError> {
+        Err(unsupported("enum variants with values"))
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, RaptoboError> {
+        Err(unsupported("nested maps"))
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<ColumnSerializer, RaptoboError> {
+        self.serialize_tuple(len)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, RaptoboError> {
+        Err(unsupported("enum variants with values"))
+    }
+}
+
+/// Serializer for the items of a list value.
+pub struct ItemSerializer {
+    items: Vec<String>,
+}
+
+impl ser::SerializeSeq for ItemSerializer {
+    type Ok = Option<Vec<String>>;
+    type Error = RaptoboError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), RaptoboError> {
+        if let Some(item) = item(value)? {
+            self.items.push(item);
+        }
+        Ok(())
+    }
+
+    fn end(self) -> Result<Self::Ok, RaptoboError> {
+        if self.items.is_empty() {
+            Ok(None)
+        } else if self.items.iter().any(|i| i.contains(char::is_whitespace)) {
+            let mut lines = vec![String::new()];
+            lines.extend(self.items.into_iter().map(|i| format!(" {}", i)));
+            Ok(Some(lines))
+        } else {
+            value(&self.items.join(" "))
+        }
+    }
+}
+
+/// Serializer for the whitespace separated columns of a struct or tuple value.
+pub struct ColumnSerializer {
+    columns: Vec<String>,
+}
+
+impl ColumnSerializer {
+    fn column<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), RaptoboError> {
+        if let Some(column) = item(value)? {
+            self.columns.push(column);
+        }
+        Ok(())
+    }
+}
+
+impl ser::SerializeTuple for ColumnSerializer {
+    type Ok = Option<Vec<String>>;
+    type Error = RaptoboError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), RaptoboError> {
+        self.column(value)
+    }
+
+    fn end(self) -> Result<Self::Ok, RaptoboError> {
+        value(&self.columns.join(" "))
+    }
+}
+
+impl ser::SerializeTupleStruct for ColumnSerializer {
+    type Ok = Option<Vec<String>>;
+    type Error = RaptoboError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), RaptoboError> {
+        self.column(value)
+    }
+
+    fn end(self) -> Result<Self::Ok, RaptoboError> {
+        value(&self.columns.join(" "))
+    }
+}
+
+impl ser::SerializeStruct for ColumnSerializer {
+    type Ok = Option<Vec<String>>;
+    type Error = RaptoboError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        _key: &'static str,
+        value: &T,
+    ) -> Result<(), RaptoboError> {
+        self.column(value)
+    }
+
+    fn end(self) -> Result<Self::Ok, RaptoboError> {
+        value(&self.columns.join(" "))
+    }
+}
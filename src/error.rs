@@ -1,9 +1,41 @@
 use std::error::Error;
 use std::fmt;
+use std::io;
+
+/// Kind of a Raptobo Error.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ErrorKind {
+    /// Generic error.
+    Other,
+    /// The metadata is not signed.
+    SignatureMissing,
+    /// The signature is expired.
+    SignatureExpired,
+    /// The key used for the signature is expired.
+    KeyExpired,
+    /// The key used for the signature is revoked.
+    KeyRevoked,
+    /// The signature was made by a key not contained in the keyring.
+    UnknownKey,
+    /// The signature is invalid or uses an unsupported algorithm.
+    SignatureInvalid,
+    /// The size of a downloaded file doesn't match the Release file.
+    SizeMismatch,
+    /// The hash of a downloaded file doesn't match the Release file.
+    HashMismatch,
+    /// The version string violates Debian Policy 5.6.12.
+    InvalidVersion,
+    /// The requested packages can't be installed together.
+    Unsatisfiable,
+    /// The value of the named field of a stanza is invalid.
+    InvalidField(String),
+}
 
 /// A Raptobo Error.
 #[derive(Debug)]
 pub struct RaptoboError {
+    /// The error kind.
+    kind: ErrorKind,
     /// The error description.
     details: String,
 }
@@ -11,10 +43,34 @@ pub struct RaptoboError {
 impl RaptoboError {
     /// Create a new error with the given message as description.
     pub fn new(msg: &str) -> RaptoboError {
+        RaptoboError::with_kind(ErrorKind::Other, msg)
+    }
+
+    /// Create a new error of the given kind with the given message as description.
+    pub fn with_kind(kind: ErrorKind, msg: &str) -> RaptoboError {
         RaptoboError {
+            kind,
             details: msg.to_string(),
         }
     }
+
+    /// Create an error from an I/O error with the given context, e.g. "[StanzaReader]".
+    ///
+    /// A Raptobo error wrapped by the I/O error, e.g. a hash mismatch found by a
+    /// verifying reader, keeps its kind.
+    pub fn from_io(e: io::Error, context: &str) -> RaptoboError {
+        match e.get_ref().and_then(|i| i.downcast_ref::<RaptoboError>()) {
+            Some(inner) => {
+                RaptoboError::with_kind(inner.kind.clone(), &format!("{} {}", context, inner))
+            }
+            None => RaptoboError::new(&format!("{} {}", context, e)),
+        }
+    }
+
+    /// Get the kind of the error.
+    pub fn kind(&self) -> &ErrorKind {
+        &self.kind
+    }
 }
 
 impl fmt::Display for RaptoboError {
@@ -28,3 +84,15 @@ impl Error for RaptoboError {
         &self.details
     }
 }
+
+impl serde::de::Error for RaptoboError {
+    fn custom<T: fmt::Display>(msg: T) -> RaptoboError {
+        RaptoboError::new(&msg.to_string())
+    }
+}
+
+impl serde::ser::Error for RaptoboError {
+    fn custom<T: fmt::Display>(msg: T) -> RaptoboError {
+        RaptoboError::new(&msg.to_string())
+    }
+}
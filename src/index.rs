@@ -0,0 +1,441 @@
+use crate::error::RaptoboError;
+#[cfg(feature = "bzip2")]
+use crate::utils::decompress_bz2;
+#[cfg(feature = "lz4")]
+use crate::utils::decompress_lz4;
+#[cfg(feature = "zstd")]
+use crate::utils::decompress_zst;
+use crate::utils::{decompress_gz, decompress_xz};
+use clap::ValueEnum;
+use std::io::{BufRead, BufReader};
+
+/// Kind of an index file listed in the Release file.
+#[derive(Debug, Clone, PartialEq)]
+pub enum IndexKind {
+    /// Binary package index, Packages
+    Packages,
+    /// Source package index, Sources
+    Sources,
+    /// File to package mapping, Contents-{arch}
+    Contents,
+    /// Translated package descriptions, Translation-{lang}
+    Translation,
+    /// Command to package mapping of command-not-found, Commands-{arch}
+    Commands,
+    /// Component Release file
+    Release,
+    /// Any other file, e.g. i18n/Index or dep11 metadata
+    Other,
+}
+
+/// Compression of an index file.
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum Compression {
+    None,
+    Gzip,
+    Xz,
+    Bzip2,
+    Lzma,
+    Lz4,
+    Zstd,
+}
+
+impl Compression {
+    const ALL: [Compression; 7] = [
+        Compression::None,
+        Compression::Gzip,
+        Compression::Xz,
+        Compression::Bzip2,
+        Compression::Lzma,
+        Compression::Lz4,
+        Compression::Zstd,
+    ];
+
+    /// File extension used for this compression, including the dot.
+    pub fn extension(&self) -> &str {
+        match self {
+            Compression::None => "",
+            Compression::Gzip => ".gz",
+            Compression::Xz => ".xz",
+            Compression::Bzip2 => ".bz2",
+            Compression::Lzma => ".lzma",
+            Compression::Lz4 => ".lz4",
+            Compression::Zstd => ".zst",
+        }
+    }
+
+    /// Default preference order of the supported compressions, best first.
+    pub fn default_preference() -> Vec<Compression> {
+        [
+            Compression::Zstd,
+            Compression::Xz,
+            Compression::Bzip2,
+            Compression::Lzma,
+            Compression::Gzip,
+            Compression::Lz4,
+            Compression::None,
+        ]
+        .into_iter()
+        .filter(|c| c.is_supported())
+        .collect()
+    }
+
+    /// Is decompression of this compression supported, i.e. the cargo feature enabled?
+    pub fn is_supported(&self) -> bool {
+        match self {
+            Compression::None | Compression::Gzip | Compression::Xz | Compression::Lzma => true,
+            Compression::Bzip2 => cfg!(feature = "bzip2"),
+            Compression::Lz4 => cfg!(feature = "lz4"),
+            Compression::Zstd => cfg!(feature = "zstd"),
+        }
+    }
+
+    /// Detect the compression of the data by its magic bytes.
+    pub fn detect(content: &[u8]) -> Compression {
+        if content.starts_with(&[0xFD, b'7', b'z', b'X', b'Z', 0x00]) {
+            Compression::Xz
+        } else if content.starts_with(&[0x1F, 0x8B]) {
+            Compression::Gzip
+        } else if content.starts_with(b"BZh") {
+            Compression::Bzip2
+        } else if content.starts_with(&[0x28, 0xB5, 0x2F, 0xFD]) {
+            Compression::Zstd
+        } else if content.starts_with(&[0x04, 0x22, 0x4D, 0x18]) {
+            Compression::Lz4
+        } else if content.starts_with(&[0x5D, 0x00, 0x00]) {
+            // lzma_alone header: default properties and a dictionary size of a multiple of 64 KiB
+            Compression::Lzma
+        } else {
+            Compression::None
+        }
+    }
+
+    /// Decompress the given data.
+    pub fn decompress(&self, content: &[u8]) -> Result<Vec<u8>, RaptoboError> {
+        match self {
+            Compression::None => Ok(content.to_vec()),
+            Compression::Gzip => decompress_gz(content),
+            Compression::Xz | Compression::Lzma => decompress_xz(content),
+            #[cfg(feature = "bzip2")]
+            Compression::Bzip2 => decompress_bz2(content),
+            #[cfg(feature = "lz4")]
+            Compression::Lz4 => decompress_lz4(content),
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => decompress_zst(content),
+            #[allow(unreachable_patterns)]
+            c => Err(RaptoboError::new(&format!(
+                "[Compression::decompress] unsupported compression {:?}, enable the cargo feature!",
+                c
+            ))),
+        }
+    }
+
+    /// Wrap the reader with a reader decompressing the data on the fly.
+    pub fn reader<'a, R: BufRead + 'a>(
+        &self,
+        reader: R,
+    ) -> Result<Box<dyn BufRead + 'a>, RaptoboError> {
+        let reader: Box<dyn BufRead + 'a> = match self {
+            Compression::None => Box::new(reader),
+            Compression::Gzip => {
+                Box::new(BufReader::new(flate2::bufread::MultiGzDecoder::new(reader)))
+            }
+            Compression::Xz | Compression::Lzma => Box::new(BufReader::new(
+                lzma::LzmaReader::new_decompressor(reader)
+                    .map_err(|e| RaptoboError::new(&e.to_string()))?,
+            )),
+            #[cfg(feature = "bzip2")]
+            Compression::Bzip2 => {
+                Box::new(BufReader::new(bzip2::bufread::MultiBzDecoder::new(reader)))
+            }
+            #[cfg(feature = "lz4")]
+            Compression::Lz4 => {
+                Box::new(BufReader::new(lz4_flex::frame::FrameDecoder::new(reader)))
+            }
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => Box::new(BufReader::new(
+                zstd::stream::read::Decoder::with_buffer(reader)
+                    .map_err(|e| RaptoboError::new(&e.to_string()))?,
+            )),
+            #[allow(unreachable_patterns)]
+            c => {
+                return Err(RaptoboError::new(&format!(
+                    "[Compression::reader] unsupported compression {:?}, enable the cargo feature!",
+                    c
+                )))
+            }
+        };
+
+        Ok(reader)
+    }
+
+    /// Split the compression extension from the file name.
+    pub fn from_file_name(name: &str) -> (&str, Compression) {
+        for compression in Compression::ALL {
+            if compression == Compression::None {
+                continue;
+            }
+            if let Some(base) = name.strip_suffix(compression.extension()) {
+                return (base, compression);
+            }
+        }
+        (name, Compression::None)
+    }
+}
+
+/// An index file listed in the Release file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IndexTarget {
+    /// Path relative to the Release file.
+    pub path: String,
+    /// Component, e.g. main, None for files of flat repositories and global files.
+    pub component: Option<String>,
+    pub kind: IndexKind,
+    /// Architecture, for Packages, Contents and Commands files.
+    pub architecture: Option<String>,
+    /// Language, for Translation files.
+    pub language: Option<String>,
+    pub compression: Compression,
+    /// Index of the debian-installer udeb packages.
+    pub installer: bool,
+}
+
+impl IndexTarget {
+    /// Parse a path of the Release file, using the known components of the repository.
+    ///
+    /// Examples: main/binary-amd64/Packages.xz, main/source/Sources.gz,
+    /// main/i18n/Translation-en.bz2, main/Contents-amd64.gz, main/cnf/Commands-amd64.xz
+    pub fn parse(path: &str, components: &[String]) -> IndexTarget {
+        // the longest matching component, components may contain a slash, e.g. updates/main
+        let component = components
+            .iter()
+            .filter(|c| path.starts_with(&format!("{}/", c)))
+            .max_by_key(|c| c.len());
+
+        let rest = match component {
+            Some(c) => &path[c.len() + 1..],
+            None => path,
+        };
+
+        let mut target = IndexTarget {
+            path: path.to_string(),
+            component: component.cloned(),
+            kind: IndexKind::Other,
+            architecture: None,
+            language: None,
+            compression: Compression::None,
+            installer: false,
+        };
+
+        let (dirs, file) = match rest.rsplit_once('/') {
+            Some((dirs, file)) => (dirs, file),
+            None => ("", rest),
+        };
+
+        for dir in dirs.split('/') {
+            if dir == "debian-installer" {
+                target.installer = true;
+            } else if let Some(arch) = dir.strip_prefix("binary-") {
+                target.architecture = Some(arch.to_string());
+            }
+        }
+
+        let (name, compression) = Compression::from_file_name(file);
+        target.compression = compression;
+
+        if name == "Packages" {
+            target.kind = IndexKind::Packages;
+        } else if name == "Sources" {
+            target.kind = IndexKind::Sources;
+        } else if name == "Release" {
+            target.kind = IndexKind::Release;
+        } else if let Some(lang) = name.strip_prefix("Translation-") {
+            target.kind = IndexKind::Translation;
+            target.language = Some(lang.to_string());
+        } else if let Some(arch) = name.strip_prefix("Contents-") {
+            target.kind = IndexKind::Contents;
+            let arch = match arch.strip_prefix("udeb-") {
+                Some(arch) => {
+                    target.installer = true;
+                    arch
+                }
+                None => arch,
+            };
+            target.architecture = Some(arch.to_string());
+        } else if let Some(arch) = name.strip_prefix("Commands-") {
+            target.kind = IndexKind::Commands;
+            target.architecture = Some(arch.to_string());
+        }
+
+        target
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Compression, IndexKind, IndexTarget};
+    use crate::utils::{decompress, decompress_reader};
+    use std::fs;
+    use std::io::{Read, Write};
+
+    fn components() -> Vec<String> {
+        vec![
+            String::from("main"),
+            String::from("updates"),
+            String::from("updates/main"),
+        ]
+    }
+
+    #[test]
+    fn parse_packages() {
+        let t = IndexTarget::parse("main/binary-amd64/Packages.xz", &components());
+        assert_eq!(t.component.as_deref(), Some("main"));
+        assert_eq!(t.kind, IndexKind::Packages);
+        assert_eq!(t.architecture.as_deref(), Some("amd64"));
+        assert_eq!(t.language, None);
+        assert_eq!(t.compression, Compression::Xz);
+        assert!(!t.installer);
+
+        let t = IndexTarget::parse("main/binary-i386/Packages", &components());
+        assert_eq!(t.kind, IndexKind::Packages);
+        assert_eq!(t.architecture.as_deref(), Some("i386"));
+        assert_eq!(t.compression, Compression::None);
+
+        let t = IndexTarget::parse(
+            "main/debian-installer/binary-arm64/Packages.gz",
+            &components(),
+        );
+        assert_eq!(t.kind, IndexKind::Packages);
+        assert_eq!(t.architecture.as_deref(), Some("arm64"));
+        assert_eq!(t.compression, Compression::Gzip);
+        assert!(t.installer);
+
+        let t = IndexTarget::parse("updates/main/binary-amd64/Packages.bz2", &components());
+        assert_eq!(t.component.as_deref(), Some("updates/main"));
+        assert_eq!(t.compression, Compression::Bzip2);
+    }
+
+    #[test]
+    fn parse_other_kinds() {
+        let t = IndexTarget::parse("main/binary-amd64/Release", &components());
+        assert_eq!(t.kind, IndexKind::Release);
+        assert_eq!(t.architecture.as_deref(), Some("amd64"));
+
+        let t = IndexTarget::parse("main/source/Sources.zst", &components());
+        assert_eq!(t.kind, IndexKind::Sources);
+        assert_eq!(t.architecture, None);
+        assert_eq!(t.compression, Compression::Zstd);
+
+        let t = IndexTarget::parse("main/i18n/Translation-de_DE.bz2", &components());
+        assert_eq!(t.kind, IndexKind::Translation);
+        assert_eq!(t.language.as_deref(), Some("de_DE"));
+
+        let t = IndexTarget::parse("main/Contents-udeb-amd64.gz", &components());
+        assert_eq!(t.kind, IndexKind::Contents);
+        assert_eq!(t.architecture.as_deref(), Some("amd64"));
+        assert!(t.installer);
+
+        let t = IndexTarget::parse("Contents-arm64.gz", &components());
+        assert_eq!(t.component, None);
+        assert_eq!(t.kind, IndexKind::Contents);
+        assert_eq!(t.architecture.as_deref(), Some("arm64"));
+
+        let t = IndexTarget::parse("main/cnf/Commands-amd64.xz", &components());
+        assert_eq!(t.kind, IndexKind::Commands);
+        assert_eq!(t.architecture.as_deref(), Some("amd64"));
+
+        let t = IndexTarget::parse("main/i18n/Index", &components());
+        assert_eq!(t.kind, IndexKind::Other);
+
+        let t = IndexTarget::parse("main/dep11/Components-amd64.yml.gz", &components());
+        assert_eq!(t.kind, IndexKind::Other);
+        assert_eq!(t.compression, Compression::Gzip);
+    }
+
+    #[test]
+    fn parse_flat() {
+        let t = IndexTarget::parse("Packages.gz", &[]);
+        assert_eq!(t.component, None);
+        assert_eq!(t.kind, IndexKind::Packages);
+        assert_eq!(t.architecture, None);
+        assert_eq!(t.compression, Compression::Gzip);
+    }
+
+    fn packages_path() -> String {
+        format!(
+            "{}/tests/data/repo/dists/stable/main/binary-amd64/Packages",
+            env!("CARGO_MANIFEST_DIR")
+        )
+    }
+
+    fn packages() -> Vec<u8> {
+        fs::read(packages_path()).unwrap()
+    }
+
+    fn check_roundtrip(compressed: &[u8], compression: Compression) {
+        assert_eq!(Compression::detect(compressed), compression);
+        assert_eq!(decompress(compressed).unwrap(), packages());
+
+        let mut content = Vec::new();
+        decompress_reader(compressed)
+            .unwrap()
+            .read_to_end(&mut content)
+            .unwrap();
+        assert_eq!(content, packages());
+    }
+
+    #[test]
+    fn detect_and_decompress() {
+        let path = packages_path();
+        check_roundtrip(&fs::read(format!("{}.xz", path)).unwrap(), Compression::Xz);
+        check_roundtrip(
+            &fs::read(format!("{}.gz", path)).unwrap(),
+            Compression::Gzip,
+        );
+        check_roundtrip(&packages(), Compression::None);
+
+        // the extension is not trusted
+        let (_, compression) = Compression::from_file_name("Packages.gz");
+        assert_eq!(compression, Compression::Gzip);
+        assert!(compression.decompress(&packages()).is_err());
+    }
+
+    #[test]
+    fn decompress_gzip_members() {
+        // e.g. the result of pigz or of appending to a gzip file
+        let content = packages();
+        let (first, second) = content.split_at(content.len() / 2);
+        let mut compressed = Vec::new();
+        for part in [first, second] {
+            let mut encoder =
+                flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+            encoder.write_all(part).unwrap();
+            compressed.extend(encoder.finish().unwrap());
+        }
+        check_roundtrip(&compressed, Compression::Gzip);
+        assert_eq!(Compression::Gzip.decompress(&compressed).unwrap(), content);
+    }
+
+    #[cfg(feature = "bzip2")]
+    #[test]
+    fn decompress_bzip2() {
+        let mut encoder = bzip2::write::BzEncoder::new(Vec::new(), bzip2::Compression::default());
+        encoder.write_all(&packages()).unwrap();
+        check_roundtrip(&encoder.finish().unwrap(), Compression::Bzip2);
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn decompress_zstd() {
+        let compressed = zstd::stream::encode_all(packages().as_slice(), 0).unwrap();
+        check_roundtrip(&compressed, Compression::Zstd);
+    }
+
+    #[cfg(feature = "lz4")]
+    #[test]
+    fn decompress_lz4() {
+        let mut encoder = lz4_flex::frame::FrameEncoder::new(Vec::new());
+        encoder.write_all(&packages()).unwrap();
+        check_roundtrip(&encoder.finish().unwrap(), Compression::Lz4);
+    }
+}
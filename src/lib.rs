@@ -1,5 +1,14 @@
+pub mod checker;
+pub mod deb822;
 pub mod error;
+pub mod index;
 pub mod logger;
 pub mod package;
+pub mod person;
+pub mod rdepends;
 pub mod repository;
+pub mod resolver;
+pub mod signature;
+pub mod source;
+pub mod transport;
 pub mod utils;
@@ -1,229 +1,392 @@
-use crate::error::RaptoboError;
-use crate::utils::{
-    stanza_date, stanza_lines, stanza_opt_files, stanza_opt_list, stanza_opt_text,
-    stanza_opt_value, stanza_value, File, parse_metadata
+use crate::deb822::{
+    comma_separated, from_stanza_extra, parse_stanzas, rfc2822, to_stanza, Stanza, StanzaReader,
+    PACKAGES_FIELD_ORDER, SOURCES_FIELD_ORDER,
 };
+use crate::error::{ErrorKind, RaptoboError};
+use crate::person::{persons, Person};
+use crate::utils::{stanza_value, File};
 use chrono::{DateTime, FixedOffset};
+use clap::ValueEnum;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
 use std::cmp::{max, Ordering};
 use std::collections::HashMap;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::io::BufRead;
 use std::iter::repeat;
+use std::ops::Bound;
+use std::str::FromStr;
 
-#[derive(Debug)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct PackageMetadata {
     // Debian control values
     /// Source package name, Debian policy 5.6.1
+    #[serde(rename = "Source")]
     pub source: Option<String>,
     /// Maintainers name and email address, RFC822 format, Debian policy 5.6.2
-    pub maintainer: Option<String>,
+    #[serde(rename = "Maintainer")]
+    pub maintainer: Option<Person>,
     /// List of the names and email addresses of co-maintainers of the package, Debian policy 5.6.3
-    pub uploaders: Option<Vec<String>>,
+    #[serde(rename = "Uploaders", default, with = "persons")]
+    pub uploaders: Option<Vec<Person>>,
     /// Name and email address of the person who prepared this version, RFC822 format, Debian policy 5.6.2
-    pub changed_by: Option<String>,
+    #[serde(rename = "Changed-By")]
+    pub changed_by: Option<Person>,
     /// Section, Debian policy 5.6.5, allowed values see Debian policy 2.4
-    pub section: Option<String>,
-    /// Section, Debian policy 5.6.6, allowed values see Debian policy 2.5
-    pub priority: Option<String>,
+    #[serde(rename = "Section")]
+    pub section: Option<Section>,
+    /// Priority, Debian policy 5.6.6, allowed values see Debian policy 2.5
+    #[serde(rename = "Priority")]
+    pub priority: Option<Priority>,
     /// Name, Debian policy 5.6.7
+    #[serde(rename = "Package")]
     pub package: String,
     /// Architecture, Debian policy 5.6.8
+    #[serde(rename = "Architecture")]
     pub architecture: String,
+    /// Multi-Arch, how the package may be installed and used for other architectures
+    #[serde(rename = "Multi-Arch")]
+    pub multi_arch: Option<MultiArch>,
     /// Essential, cannot be removed, boolean field, values: yes or no, Debian policy 5.6.9
-    pub essential: Option<String>,
+    #[serde(rename = "Essential")]
+    pub essential: Option<bool>,
+    /// Protected, should not be removed without care, e.g. the running init system
+    #[serde(rename = "Protected")]
+    pub protected: Option<bool>,
+    /// Important, like Protected, but installed by default
+    #[serde(rename = "Important")]
+    pub important: Option<bool>,
+    /// Build-Essential, part of the build-essential set
+    #[serde(rename = "Build-Essential")]
+    pub build_essential: Option<bool>,
     // Package relationships, Debian Policy 7
     /// declares an absolute dependency
+    #[serde(rename = "Depends", default, with = "relations")]
     pub depends: Option<Vec<PackageRelation>>,
     /// Pre-Depends is like Depends, but forces that the installation of the linked packages is completed
+    #[serde(rename = "Pre-Depends", default, with = "relations")]
     pub pre_depends: Option<Vec<PackageRelation>>,
     /// strong, but not absolute, dependency
+    #[serde(rename = "Recommends", default, with = "relations")]
     pub recommends: Option<Vec<PackageRelation>>,
     /// may be more useful with linked ones
+    #[serde(rename = "Suggests", default, with = "relations")]
     pub suggests: Option<Vec<PackageRelation>>,
     /// like suggests, but other direction
+    #[serde(rename = "Enhances", default, with = "relations")]
     pub enhances: Option<Vec<PackageRelation>>,
     /// breaks linked packages
+    #[serde(rename = "Breaks", default, with = "relations")]
     pub breaks: Option<Vec<PackageRelation>>,
     /// conflicts with linked packages
+    #[serde(rename = "Conflicts", default, with = "relations")]
     pub conflicts: Option<Vec<PackageRelation>>,
     /// provides the named virtual packages, see Debian Policy 7.5
+    #[serde(rename = "Provides", default, with = "relations")]
     pub provides: Option<Vec<PackageRelation>>,
     /// overwrites files of linked packages, see Debian Policy 7.6
+    #[serde(rename = "Replaces", default, with = "relations")]
     pub replaces: Option<Vec<PackageRelation>>,
+    /// source packages used to build this binary package, for license compliance
+    #[serde(rename = "Built-Using", default, with = "relations")]
+    pub built_using: Option<Vec<PackageRelation>>,
+    /// source packages statically linked into this binary package
+    #[serde(rename = "Static-Built-Using", default, with = "relations")]
+    pub static_built_using: Option<Vec<PackageRelation>>,
     /// Version, Debian policy 5.6.11
+    #[serde(rename = "Standards-Version")]
     pub standards_version: Option<String>,
     /// Version, Debian policy 5.6.12
+    #[serde(rename = "Version")]
     pub version: PackageVersion,
     /// Package description, Debian policy 5.6.13
+    #[serde(rename = "Description")]
     pub description: Option<String>,
     /// List of distribution names containing this package, Debian Policy 5.6.14
+    #[serde(rename = "Distribution")]
     pub distribution: Option<Vec<String>>,
     /// Date when the package was last built, Debian Policy 5.6.15
+    #[serde(rename = "Date", default, with = "rfc2822")]
     pub date: Option<DateTime<FixedOffset>>,
     /// Package format, Debian Policy 5.6.16
+    #[serde(rename = "Format")]
     pub format: Option<String>,
     /// Urgency, how important is it to install the new version, Debian Policy 5.6.17
+    #[serde(rename = "Urgency")]
     pub urgency: Option<PackageUpdateUrgency>,
     /// human-readable changes description, Debian Policy 5.6.18
+    #[serde(rename = "Changes")]
     pub changes: Option<String>,
     /// list of binary package names built from this source, Debian Policy 5.6.19
+    #[serde(rename = "Binary", default, with = "comma_separated")]
     pub binary: Option<Vec<String>>,
     /// estimated installed package size in bytes, Debian Policy 5.6.20
-    pub installed_size: Option<String>,
+    #[serde(rename = "Installed-Size")]
+    pub installed_size: Option<u64>,
     /// list of files which are part of this source package with md5 checksums, Debian Policy 5.6.21
+    #[serde(rename = "Files")]
     pub files: Option<Vec<File>>,
     /// list of bugs closed with this version, Debian Policy 5.6.22
+    #[serde(rename = "Closes")]
     pub closes: Option<Vec<String>>,
     /// Homepage of the project this package belongs to, Debian Policy 5.6.23
+    #[serde(rename = "Homepage")]
     pub homepage: Option<String>,
     /// list of files which are part of this source package with md5 checksums, Debian Policy 5.6.24
+    #[serde(rename = "Checksums-Sha1")]
     pub checksums_sha1: Option<Vec<File>>,
     /// list of files which are part of this source package with md5 checksums, Debian Policy 5.6.24
+    #[serde(rename = "Checksums-Sha256")]
     pub checksums_sha256: Option<Vec<File>>,
     /// web interface for browsing the source repository, Debian Policy 5.6.26
+    #[serde(rename = "Vcs-Browser")]
     pub vcs_browser: Option<String>,
     /// source repository, Debian Policy 5.6.26
+    #[serde(skip)]
     pub vcs: Option<PackageVcs>,
     /// list of packages built by this source package, Debian Policy 5.6.27
+    #[serde(rename = "Package-List")]
     pub package_list: Option<Vec<PackageListItem>>,
     /// package type, deb or udeb, Debian Policy 5.6.28
+    #[serde(rename = "Package-Type")]
     pub package_type: Option<String>,
     /// git hash of package, Debian Policy 5.6.29
+    #[serde(rename = "Dgit")]
     pub dgit: Option<String>,
     /// list of test names, Debian Policy 5.6.30
+    #[serde(rename = "Testsuite", default, with = "comma_separated")]
     pub testsuite: Option<Vec<String>>,
     /// root or fakeroot is needed to build the package, Debian Policy 5.6.31
+    #[serde(rename = "Rules-Requires-Root")]
     pub rules_requires_root: Option<String>,
     /// origin of the package
+    #[serde(rename = "Origin")]
     pub origin: Option<String>,
     /// maintainer of the original package
-    pub original_maintainer: Option<String>,
+    #[serde(rename = "Original-Maintainer")]
+    pub original_maintainer: Option<Person>,
     /// link to the bug-tracker
+    #[serde(rename = "Bugs")]
     pub bugs: Option<String>,
     /// list of tasks
+    #[serde(rename = "Task", default, with = "comma_separated")]
     pub task: Option<Vec<String>>,
+    /// debtags, e.g. "role::program"
+    #[serde(rename = "Tag", default, with = "comma_separated")]
+    pub tag: Option<Vec<String>>,
+    /// support period of the package, e.g. "9m"
+    #[serde(rename = "Supported")]
+    pub supported: Option<String>,
+    /// percentage of the systems which get this update, for phased updates
+    #[serde(rename = "Phased-Update-Percentage")]
+    pub phased_update_percentage: Option<u8>,
+    /// package generated automatically, e.g. "debug-symbols"
+    #[serde(rename = "Auto-Built-Package")]
+    pub auto_built_package: Option<String>,
+    /// build IDs of the binaries contained in a debug symbols package
+    #[serde(rename = "Build-Ids")]
+    pub build_ids: Option<String>,
+    /// package ID of a Haskell library
+    #[serde(rename = "Ghc-Package")]
+    pub ghc_package: Option<String>,
+    /// Go import path of a Go library
+    #[serde(rename = "Go-Import-Path")]
+    pub go_import_path: Option<String>,
     // Package index additional values
     /// path to file, relative to the base of the repository
+    #[serde(rename = "Filename")]
     pub filename: Option<String>,
     /// compressed size, as bytes
-    pub size: Option<String>,
+    #[serde(rename = "Size")]
+    pub size: Option<u64>,
     /// md5 hash of the package binary package
+    #[serde(rename = "MD5sum")]
     pub md5sum: Option<String>,
     /// sha1 hash of the package binary package
+    #[serde(rename = "SHA1")]
     pub sha1: Option<String>,
     /// sha256 hash of the package binary package
+    #[serde(rename = "SHA256")]
     pub sha256: Option<String>,
     /// sha512 hash of the package binary package
+    #[serde(rename = "SHA512")]
     pub sha512: Option<String>,
     /// lookup key for translations
+    #[serde(rename = "Description-md5")]
     pub description_md5: Option<String>,
+    // Repository values
+    /// component of the package index this package was loaded from
+    #[serde(skip)]
+    pub component: Option<String>,
+    /// architecture of the package index this package was loaded from
+    #[serde(skip)]
+    pub index_architecture: Option<String>,
+    /// fields without a typed value, in their original order
+    #[serde(skip)]
+    pub extra_fields: Stanza,
+    /// errors of the fields with invalid values, which are kept as extra fields
+    #[serde(skip)]
+    pub field_errors: Vec<RaptoboError>,
 }
 
 impl PackageMetadata {
-    pub fn new(stanza: HashMap<String, Vec<String>>) -> Result<PackageMetadata, RaptoboError> {
-        Ok(PackageMetadata {
-            source: stanza_opt_value("Source", &stanza),
-            maintainer: stanza_opt_value("Maintainer", &stanza),
-            uploaders: stanza_opt_list("Uploaders", &stanza),
-            changed_by: stanza_opt_value("Changed-By", &stanza),
-            section: stanza_opt_value("Section", &stanza),
-            priority: stanza_opt_value("Priority", &stanza),
-            package: stanza_value("Package", &stanza)?,
-            architecture: stanza_value("Architecture", &stanza)?,
-            essential: stanza_opt_value("Essential", &stanza),
-            depends: PackageRelation::parse("Depends", &stanza),
-            pre_depends: PackageRelation::parse("Pre-Depends", &stanza),
-            recommends: PackageRelation::parse("Recommends", &stanza),
-            suggests: PackageRelation::parse("Suggests", &stanza),
-            enhances: PackageRelation::parse("Enhances", &stanza),
-            breaks: PackageRelation::parse("Breaks", &stanza),
-            conflicts: PackageRelation::parse("Conflicts", &stanza),
-            provides: PackageRelation::parse("Provides", &stanza),
-            replaces: PackageRelation::parse("Replaces", &stanza),
-            standards_version: stanza_opt_value("Standards-Version", &stanza),
-            version: PackageVersion::parse("Version", &stanza)?,
-            description: stanza_opt_text("Description", &stanza),
-            distribution: stanza_opt_list("Distribution", &stanza),
-            date: stanza_date("Date", &stanza),
-            format: stanza_opt_value("Format", &stanza),
-            urgency: PackageUpdateUrgency::parse("Urgency", &stanza),
-            changes: stanza_opt_value("Changes", &stanza),
-            binary: stanza_opt_list("Binary", &stanza),
-            installed_size: stanza_opt_value("Installed-Size", &stanza),
-            files: stanza_opt_files("Files", &stanza),
-            closes: stanza_opt_list("Closes", &stanza),
-            homepage: stanza_opt_value("Homepage", &stanza),
-            checksums_sha1: stanza_opt_files("Checksums-Sha1", &stanza),
-            checksums_sha256: stanza_opt_files("Checksums-Sha256", &stanza),
-            vcs_browser: stanza_opt_value("Vcs-Browser", &stanza),
-            vcs: PackageVcs::parse(&stanza),
-            package_list: PackageListItem::parse("Package-List", &stanza),
-            package_type: stanza_opt_value("Package-Type", &stanza),
-            dgit: stanza_opt_value("Dgit", &stanza),
-            testsuite: stanza_opt_list("Testsuite", &stanza),
-            rules_requires_root: stanza_opt_value("Rules-Requires-Root", &stanza),
-            origin: stanza_opt_value("Origin", &stanza),
-            original_maintainer: stanza_opt_value("Original-Maintainer", &stanza),
-            bugs: stanza_opt_value("Bugs", &stanza),
-            task: stanza_opt_list("Task", &stanza),
-            filename: stanza_opt_value("Filename", &stanza),
-            size: stanza_opt_value("Size", &stanza),
-            md5sum: stanza_opt_value("MD5sum", &stanza),
-            sha1: stanza_opt_value("SHA1", &stanza),
-            sha256: stanza_opt_value("SHA256", &stanza),
-            sha512: stanza_opt_value("SHA512", &stanza),
-            description_md5: stanza_opt_value("Description-md5", &stanza),
-        })
+    /// Parse a package from its stanza.
+    ///
+    /// Optional fields with invalid values are kept as extra fields, and their
+    /// errors are reported in `field_errors`. Invalid relations are errors, to not
+    /// lose dependencies.
+    pub fn new(stanza: Stanza) -> Result<PackageMetadata, RaptoboError> {
+        let relations = RelationField::ALL.map(|f| f.name());
+        let vcs = PackageVcs::parse(&stanza);
+        let known: Vec<&str> = vcs.iter().map(|v| v.vcs_type.field_name()).collect();
+        let (mut package, extra, errors): (PackageMetadata, _, _) =
+            from_stanza_extra(&stanza, &relations, &known)?;
+        package.vcs = vcs;
+        package.extra_fields = extra;
+
+        for e in errors {
+            log::warn!(
+                "[PackageMetadata] {} {}: {}",
+                package.package,
+                package.version,
+                e
+            );
+            package.field_errors.push(e);
+        }
+
+        Ok(package)
+    }
+
+    /// Convert the package back to a stanza.
+    ///
+    /// The fields are ordered like in Sources indices for source packages,
+    /// and like in Packages indices otherwise.
+    pub fn to_stanza(&self) -> Result<Stanza, RaptoboError> {
+        let mut stanza = to_stanza(self)?;
+
+        if let Some(vcs) = &self.vcs {
+            stanza.set_value(vcs.vcs_type.field_name(), &vcs.url);
+        }
+
+        for field in self.extra_fields.fields() {
+            stanza.insert(&field.name, field.value.clone());
+        }
+
+        if self.binary.is_some() {
+            stanza.sort_fields(SOURCES_FIELD_ORDER);
+        } else {
+            stanza.sort_fields(PACKAGES_FIELD_ORDER);
+        }
+
+        Ok(stanza)
     }
 
     pub fn parse(content: Vec<String>) -> Result<Vec<PackageMetadata>, RaptoboError> {
-        let stanzas = parse_metadata(content)?;
-
-        Ok(stanzas.into_iter()
-        .map(|s| PackageMetadata::new(s))
-        .filter(|r| match r {
-            Ok(_) => true,
-            Err(e) => {
-                log::error!("[PackageMetadata::parse] error: {}", e);
-                false
-            }
-        })
-        .map(|r| r.unwrap())
-        .collect())
+        PackageMetadata::from_stanzas(StanzaReader::from_lines(content.into_iter().map(Ok)))
+            .collect()
+    }
+
+    /// Parse the packages of an index, e.g. a Packages file, lazily from the reader.
+    pub fn read<R: BufRead>(
+        reader: R,
+    ) -> impl Iterator<Item = Result<PackageMetadata, RaptoboError>> {
+        PackageMetadata::from_stanzas(StanzaReader::new(reader))
+    }
+
+    /// Parse the packages of the given stanzas lazily, invalid packages are skipped.
+    pub fn from_stanzas<I>(
+        stanzas: I,
+    ) -> impl Iterator<Item = Result<PackageMetadata, RaptoboError>>
+    where
+        I: Iterator<Item = Result<Stanza, RaptoboError>>,
+    {
+        parse_stanzas(stanzas, PackageMetadata::new, "PackageMetadata::parse")
     }
 }
 
-#[derive(Debug, Clone)]
+/// Line of a Package-List, e.g. "bash deb shells required arch=any essential=yes".
+#[derive(Debug, Clone, PartialEq)]
 pub struct PackageListItem {
     pub name: String,
     pub type_name: String,
     pub section: String,
     pub priority: String,
+    /// additional key=value columns, e.g. ("arch", "any"), in their original order
+    pub options: Vec<(String, String)>,
 }
 
 impl PackageListItem {
-    pub fn parse(key: &str, stanza: &HashMap<String, Vec<String>>) -> Option<Vec<PackageListItem>> {
-        let lines = match stanza_lines(key, &stanza, true) {
-            Ok(v) => v,
-            Err(_) => return None,
+    pub fn new(line: &str) -> Result<PackageListItem, RaptoboError> {
+        let mut columns = line.split_whitespace();
+        let mut column = || {
+            columns
+                .next()
+                .map(|c| c.to_string())
+                .ok_or(RaptoboError::new(&format!(
+                    "[PackageListItem] {}: expected name, type, section and priority!",
+                    line
+                )))
+        };
+        let mut item = PackageListItem {
+            name: column()?,
+            type_name: column()?,
+            section: column()?,
+            priority: column()?,
+            options: Vec::new(),
         };
 
-        let items: Vec<PackageListItem> = lines
-            .into_iter()
-            .map(|l| {
-                let parts: Vec<&str> = l.split(" ").collect();
-                PackageListItem {
-                    name: parts[0].trim().to_string(),
-                    type_name: parts[1].trim().to_string(),
-                    section: parts[3].trim().to_string(),
-                    priority: parts[4].trim().to_string(),
-                }
-            })
-            .collect();
+        for option in columns {
+            let (key, value) = option.split_once('=').ok_or(RaptoboError::new(&format!(
+                "[PackageListItem] {}: invalid column {}, expected key=value!",
+                line, option
+            )))?;
+            item.options.push((key.to_string(), value.to_string()));
+        }
 
-        if items.is_empty() {
-            None
-        } else {
-            Some(items)
+        Ok(item)
+    }
+
+    /// Value of the given key=value column, e.g. "any" for "arch".
+    pub fn option(&self, key: &str) -> Option<&str> {
+        self.options
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+impl fmt::Display for PackageListItem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} {} {} {}",
+            self.name, self.type_name, self.section, self.priority
+        )?;
+        for (key, value) in &self.options {
+            write!(f, " {}={}", key, value)?;
         }
+        Ok(())
+    }
+}
+
+impl Serialize for PackageListItem {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for PackageListItem {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<PackageListItem, D::Error> {
+        let line = String::deserialize(deserializer)?;
+        PackageListItem::new(&line).map_err(de::Error::custom)
+    }
+}
+
+impl FromStr for PackageListItem {
+    type Err = RaptoboError;
+
+    fn from_str(line: &str) -> Result<PackageListItem, RaptoboError> {
+        PackageListItem::new(line)
     }
 }
 
@@ -239,6 +402,22 @@ pub enum VcsType {
     Svn,
 }
 
+impl VcsType {
+    /// Name of the control field for this VCS type.
+    pub fn field_name(&self) -> &str {
+        match self {
+            VcsType::Arch => "Vcs-Arch",
+            VcsType::Bzr => "Vcs-Bzr",
+            VcsType::Cvs => "Vcs-Cvs",
+            VcsType::Darcs => "Vcs-Darcs",
+            VcsType::Git => "Vcs-Git",
+            VcsType::Hg => "Vcs-Hg",
+            VcsType::Mtn => "Vcs-Mtn",
+            VcsType::Svn => "Vcs-Svn",
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct PackageVcs {
     pub vcs_type: VcsType,
@@ -246,7 +425,7 @@ pub struct PackageVcs {
 }
 
 impl PackageVcs {
-    pub fn parse(stanza: &HashMap<String, Vec<String>>) -> Option<PackageVcs> {
+    pub fn parse(stanza: &Stanza) -> Option<PackageVcs> {
         let types = vec![
             (VcsType::Arch, "Vcs-Arch"),
             (VcsType::Bzr, "Vcs-Bzr"),
@@ -274,7 +453,8 @@ impl PackageVcs {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
 pub enum PackageUpdateUrgency {
     Low,
     Medium,
@@ -283,30 +463,176 @@ pub enum PackageUpdateUrgency {
     Critical,
 }
 
-impl PackageUpdateUrgency {
-    pub fn parse(key: &str, stanza: &HashMap<String, Vec<String>>) -> Option<PackageUpdateUrgency> {
-        let value = match stanza_value(key, &stanza) {
-            Ok(v) => v,
-            Err(_) => return None,
+impl fmt::Display for PackageUpdateUrgency {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let urgency = match self {
+            PackageUpdateUrgency::Low => "low",
+            PackageUpdateUrgency::Medium => "medium",
+            PackageUpdateUrgency::High => "high",
+            PackageUpdateUrgency::Emergency => "emergency",
+            PackageUpdateUrgency::Critical => "critical",
         };
+        write!(f, "{}", urgency)
+    }
+}
 
-        if value.trim().to_lowercase() == "low" {
-            Some(PackageUpdateUrgency::Low)
-        } else if value.trim().to_lowercase() == "medium" {
-            Some(PackageUpdateUrgency::Medium)
-        } else if value.trim().to_lowercase() == "high" {
-            Some(PackageUpdateUrgency::High)
-        } else if value.trim().to_lowercase() == "emergency" {
-            Some(PackageUpdateUrgency::Emergency)
-        } else if value.trim().to_lowercase() == "critical" {
-            Some(PackageUpdateUrgency::Critical)
-        } else {
-            None
+/// Multi-Arch value of a package, see the multiarch specification.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MultiArch {
+    /// Only one architecture of the package can be installed, the default.
+    #[default]
+    No,
+    /// Several architectures of the package can be installed at the same time.
+    Same,
+    /// The package satisfies the dependencies of packages of all architectures.
+    Foreign,
+    /// The package satisfies dependencies qualified with ":any".
+    Allowed,
+}
+
+impl fmt::Display for MultiArch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let multi_arch = match self {
+            MultiArch::No => "no",
+            MultiArch::Same => "same",
+            MultiArch::Foreign => "foreign",
+            MultiArch::Allowed => "allowed",
+        };
+        write!(f, "{}", multi_arch)
+    }
+}
+
+/// Priority of a package, Debian Policy 2.5.
+///
+/// The values are compared ignoring the case, like dpkg does, unknown values are kept.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Priority {
+    /// Necessary for the proper functioning of the system.
+    Required,
+    /// Expected on any Unix-like system.
+    Important,
+    /// Reasonably small, but not too specialized system.
+    Standard,
+    /// The default priority for the majority of the archive.
+    Optional,
+    /// Deprecated, equivalent to optional.
+    Extra,
+    /// Value not defined by the Debian Policy, e.g. "source".
+    Other(String),
+}
+
+impl Priority {
+    pub fn new(priority: &str) -> Priority {
+        match priority.to_ascii_lowercase().as_str() {
+            "required" => Priority::Required,
+            "important" => Priority::Important,
+            "standard" => Priority::Standard,
+            "optional" => Priority::Optional,
+            "extra" => Priority::Extra,
+            _ => Priority::Other(priority.to_string()),
         }
     }
 }
 
-#[derive(Debug, Clone)]
+impl fmt::Display for Priority {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let priority = match self {
+            Priority::Required => "required",
+            Priority::Important => "important",
+            Priority::Standard => "standard",
+            Priority::Optional => "optional",
+            Priority::Extra => "extra",
+            Priority::Other(other) => other,
+        };
+        write!(f, "{}", priority)
+    }
+}
+
+impl Serialize for Priority {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Priority {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Priority, D::Error> {
+        let priority = String::deserialize(deserializer)?;
+        Ok(Priority::new(&priority))
+    }
+}
+
+/// Section of a package, Debian Policy 2.4.
+///
+/// Packages outside of the main archive area prefix the section with the area,
+/// e.g. "non-free/libs".
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Section {
+    /// archive area, e.g. "non-free" or "universe", None for main
+    pub area: Option<String>,
+    /// section name, e.g. "libs"
+    pub name: String,
+}
+
+impl Section {
+    /// Parse a section value, e.g. "libs" or "contrib/net".
+    pub fn new(section: &str) -> Result<Section, RaptoboError> {
+        let invalid =
+            |msg: &str| RaptoboError::new(&format!("[Section] {} in \"{}\"", msg, section));
+
+        let (area, name) = match section.split_once('/') {
+            Some((area, name)) => (Some(area), name),
+            None => (None, section),
+        };
+
+        if name.is_empty() || area.is_some_and(|a| a.is_empty()) {
+            return Err(invalid("empty section or area"));
+        }
+        if section.chars().filter(|c| *c == '/').count() > 1 {
+            return Err(invalid("more than one area"));
+        }
+        if section.chars().any(|c| c.is_whitespace()) {
+            return Err(invalid("whitespace"));
+        }
+
+        Ok(Section {
+            area: area.map(String::from),
+            name: name.to_string(),
+        })
+    }
+}
+
+impl fmt::Display for Section {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.area {
+            Some(area) => write!(f, "{}/{}", area, self.name),
+            None => write!(f, "{}", self.name),
+        }
+    }
+}
+
+impl Serialize for Section {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Section {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Section, D::Error> {
+        let section = String::deserialize(deserializer)?;
+        Section::new(&section).map_err(de::Error::custom)
+    }
+}
+
+impl FromStr for Section {
+    type Err = RaptoboError;
+
+    fn from_str(section: &str) -> Result<Section, RaptoboError> {
+        Section::new(section)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub enum PackageVersionRelation {
     LT,
     LTE,
@@ -335,6 +661,8 @@ impl PackageVersionRelation {
         }
     }
 
+    /// Check if a version with the given order compared to the required version
+    /// satisfies the relation, e.g. Greater for GTE.
     pub fn is(&self, o: Ordering) -> bool {
         match o {
             Ordering::Equal => match self {
@@ -355,111 +683,659 @@ impl PackageVersionRelation {
     }
 }
 
+impl fmt::Display for PackageVersionRelation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let relation = match self {
+            PackageVersionRelation::LT => "<<",
+            PackageVersionRelation::LTE => "<=",
+            PackageVersionRelation::EQ => "=",
+            PackageVersionRelation::GTE => ">=",
+            PackageVersionRelation::GT => ">>",
+        };
+        write!(f, "{}", relation)
+    }
+}
+
+/// Architecture qualifier of a relation, e.g. "any" of "python3:any", Debian Policy 7.1.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ArchQualifier {
+    /// Any architecture can satisfy the relation, needs Multi-Arch: allowed.
+    Any,
+    /// Only the native architecture of the build can satisfy the relation.
+    Native,
+    /// The given architecture.
+    Arch(String),
+}
+
+impl fmt::Display for ArchQualifier {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ArchQualifier::Any => write!(f, "any"),
+            ArchQualifier::Native => write!(f, "native"),
+            ArchQualifier::Arch(arch) => write!(f, "{}", arch),
+        }
+    }
+}
+
+/// Entry of an architecture restriction list, e.g. "!i386" of "[!i386 !armhf]".
+#[derive(Debug, Clone, PartialEq)]
+pub struct ArchRestriction {
+    pub negated: bool,
+    /// Architecture name or wildcard, e.g. amd64 or linux-any.
+    pub architecture: String,
+}
+
+impl fmt::Display for ArchRestriction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.negated {
+            write!(f, "!")?;
+        }
+        write!(f, "{}", self.architecture)
+    }
+}
+
+/// Term of a build profile restriction, e.g. "!nocheck" of "<!nocheck>".
+#[derive(Debug, Clone, PartialEq)]
+pub struct BuildProfile {
+    pub negated: bool,
+    pub name: String,
+}
+
+impl fmt::Display for BuildProfile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.negated {
+            write!(f, "!")?;
+        }
+        write!(f, "{}", self.name)
+    }
+}
+
+/// A package relation, Debian Policy 7.1, e.g. "foo:any (>= 1.0) [amd64] <!nocheck> | bar".
 #[derive(Debug, Clone)]
 pub struct PackageRelation {
     pub package: String,
+    /// Architecture qualifier, e.g. any of "python3:any"
+    pub arch_qualifier: Option<ArchQualifier>,
     pub relation: PackageVersionRelation,
     pub version: Option<PackageVersion>,
+    /// Architecture restriction list, only used by build relations; empty if not restricted
+    pub architectures: Vec<ArchRestriction>,
+    /// Build profile restriction formula, the relation applies if any of the lists
+    /// matches completely; empty if not restricted
+    pub profiles: Vec<Vec<BuildProfile>>,
     pub alternative: Option<Box<PackageRelation>>,
 }
 
 impl PackageRelation {
-    pub fn parse(key: &str, stanza: &HashMap<String, Vec<String>>) -> Option<Vec<PackageRelation>> {
-        let value = match stanza_value(key, &stanza) {
-            Ok(v) => v,
-            Err(_) => return None,
+    /// Parse a single relation with its alternatives, e.g. "foo (>= 1.0) | bar".
+    pub fn new(relation: &str) -> Result<PackageRelation, RaptoboError> {
+        let mut parser = RelationParser::new(relation);
+        let relation = parser.alternatives()?;
+        parser.skip_whitespace();
+        if parser.peek().is_some() {
+            return Err(parser.error("unexpected character"));
+        }
+        Ok(relation)
+    }
+
+    /// Parse the value of a relation field, e.g. Depends or Build-Depends.
+    ///
+    /// The relations are separated by commas, a trailing comma is allowed.
+    /// Errors contain the character position in the value.
+    pub fn parse(relations: &str) -> Result<Vec<PackageRelation>, RaptoboError> {
+        RelationParser::new(relations).relations()
+    }
+
+    /// The relation and its alternatives.
+    pub fn alternatives(&self) -> impl Iterator<Item = &PackageRelation> {
+        let mut next = Some(self);
+        std::iter::from_fn(move || {
+            let current = next?;
+            next = current.alternative.as_deref();
+            Some(current)
+        })
+    }
+
+    /// Range of the versions satisfying this relation, ignoring the alternatives.
+    pub fn version_range(&self) -> VersionRange {
+        match &self.version {
+            Some(version) => VersionRange::new(&self.relation, version),
+            None => VersionRange::any(),
+        }
+    }
+
+    /// Check if the package satisfies the relation or one of its alternatives,
+    /// by its name or by its Provides.
+    pub fn is(&self, package: &PackageMetadata) -> bool {
+        self.alternatives().any(|p| p.matches(package))
+    }
+
+    /// Check if the package satisfies this relation, ignoring the alternatives.
+    ///
+    /// A package matches by its name and version, or if it provides the named
+    /// virtual package, see `is_provided_by`.
+    pub fn matches(&self, package: &PackageMetadata) -> bool {
+        if self.package == package.package {
+            return match &self.version {
+                None => true,
+                Some(v) => self.relation.is(package.version.cmp(v)),
+            };
+        }
+        package
+            .provides
+            .iter()
+            .flatten()
+            .any(|provided| self.is_provided_by(provided))
+    }
+
+    /// Check if the package satisfies this relation, taking the architectures into account.
+    ///
+    /// The relation is declared in the given field of a package of the architecture
+    /// `arch`, `native` is the native architecture of the system. Like dpkg, Architecture
+    /// all packages count as native packages, and the Multi-Arch value of the package
+    /// decides if it satisfies relations of other architectures:
+    /// - unqualified relations need the architecture of the declaring package, or a
+    ///   Multi-Arch foreign package, Breaks, Conflicts and Replaces match all architectures
+    /// - "foo:any" needs a Multi-Arch allowed package, Breaks, Conflicts and Replaces
+    ///   match all architectures
+    /// - "foo:native" and "foo:amd64" need a package of this architecture
+    pub fn matches_arch(
+        &self,
+        package: &PackageMetadata,
+        arch: &str,
+        native: &str,
+        field: RelationField,
+    ) -> bool {
+        if !self.matches(package) {
+            return false;
+        }
+
+        let multi_arch = package.multi_arch.unwrap_or_default();
+        let negative = matches!(
+            field,
+            RelationField::Breaks | RelationField::Conflicts | RelationField::Replaces
+        );
+        let required = match &self.arch_qualifier {
+            None if multi_arch == MultiArch::Foreign || negative => return true,
+            None => arch,
+            Some(ArchQualifier::Any) => return multi_arch == MultiArch::Allowed || negative,
+            Some(ArchQualifier::Native) => native,
+            Some(ArchQualifier::Arch(a)) => a.as_str(),
         };
 
-        let list: Vec<PackageRelation> = value
-            .split(",")
+        let required = if required == "all" { native } else { required };
+        let actual = match package.architecture.as_str() {
+            "all" => native,
+            actual => actual,
+        };
+        required == actual
+    }
+
+    /// Check if the package satisfies the relation or one of its alternatives,
+    /// taking the architectures into account, see `matches_arch`.
+    pub fn is_arch(
+        &self,
+        package: &PackageMetadata,
+        arch: &str,
+        native: &str,
+        field: RelationField,
+    ) -> bool {
+        self.alternatives()
+            .any(|p| p.matches_arch(package, arch, native, field))
+    }
+
+    /// Check if an entry of a Provides field satisfies this relation, Debian Policy 7.5.
+    ///
+    /// Like dpkg, unversioned relations are satisfied by any Provides of the name,
+    /// versioned relations only by a versioned Provides, e.g. "foo (= 1.2)", with
+    /// a matching version.
+    pub fn is_provided_by(&self, provided: &PackageRelation) -> bool {
+        if self.package != provided.package {
+            return false;
+        }
+        match (&self.version, &provided.version) {
+            (None, _) => true,
+            (Some(_), Some(version)) => {
+                provided.relation == PackageVersionRelation::EQ
+                    && self.version_range().contains(version)
+            }
+            (Some(_), None) => false,
+        }
+    }
+}
+
+/// Index of the packages providing virtual packages, Debian Policy 7.5.
+pub struct ProviderIndex<'a> {
+    /// Providing packages and the Provides entry by virtual package name,
+    /// sorted by package name and highest version first.
+    providers: HashMap<&'a str, Vec<(&'a PackageMetadata, &'a PackageRelation)>>,
+}
+
+impl<'a> ProviderIndex<'a> {
+    pub fn new<I>(packages: I) -> ProviderIndex<'a>
+    where
+        I: IntoIterator<Item = &'a PackageMetadata>,
+    {
+        let mut providers: HashMap<&str, Vec<(&PackageMetadata, &PackageRelation)>> =
+            HashMap::new();
+
+        for package in packages {
+            for provided in package.provides.iter().flatten() {
+                providers
+                    .entry(provided.package.as_str())
+                    .or_default()
+                    .push((package, provided));
+            }
+        }
+
+        for entries in providers.values_mut() {
+            entries.sort_by(|(a, _), (b, _)| {
+                a.package
+                    .cmp(&b.package)
+                    .then_with(|| b.version.cmp(&a.version))
+            });
+        }
+
+        ProviderIndex { providers }
+    }
+
+    /// Check if any package provides the named virtual package.
+    pub fn contains(&self, name: &str) -> bool {
+        self.providers.contains_key(name)
+    }
+
+    /// All packages providing the named virtual package, with the provided version if any.
+    pub fn providers(
+        &self,
+        name: &str,
+    ) -> impl Iterator<Item = (&'a PackageMetadata, Option<&'a PackageVersion>)> + '_ {
+        self.providers
+            .get(name)
             .into_iter()
-            .map(|r| r.trim())
-            .map(|r| PackageRelation::new(r))
-            .filter(|r| match r {
-                Ok(_) => false,
-                Err(e) => {
-                    log::error!("[PackageRelation::parse] relation parse error: {}", e);
-                    true
-                }
-            })
-            .map(|r| r.unwrap())
-            .collect();
+            .flatten()
+            .map(|(package, provided)| (*package, provided.version.as_ref()))
+    }
 
-        if list.is_empty() {
-            None
+    /// Packages providing a virtual package which satisfies the relation,
+    /// ignoring its alternatives.
+    pub fn satisfying(&self, relation: &PackageRelation) -> Vec<&'a PackageMetadata> {
+        let mut packages: Vec<&'a PackageMetadata> = Vec::new();
+        for (package, provided) in self
+            .providers
+            .get(relation.package.as_str())
+            .into_iter()
+            .flatten()
+        {
+            // a package may provide several versions of the same name
+            if relation.is_provided_by(provided)
+                && !packages.iter().any(|p| std::ptr::eq(*p, *package))
+            {
+                packages.push(package);
+            }
+        }
+        packages
+    }
+}
+
+impl FromStr for PackageRelation {
+    type Err = RaptoboError;
+
+    fn from_str(relation: &str) -> Result<PackageRelation, RaptoboError> {
+        PackageRelation::new(relation)
+    }
+}
+
+impl fmt::Display for PackageRelation {
+    /// Format as relation field entry, e.g. "libc6 (>= 2.34) | libc6.1".
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.package)?;
+        if let Some(qualifier) = &self.arch_qualifier {
+            write!(f, ":{}", qualifier)?;
+        }
+        if let Some(version) = &self.version {
+            write!(f, " ({} {})", self.relation, version)?;
+        }
+        if !self.architectures.is_empty() {
+            let architectures: Vec<String> =
+                self.architectures.iter().map(|a| a.to_string()).collect();
+            write!(f, " [{}]", architectures.join(" "))?;
+        }
+        for profiles in &self.profiles {
+            let profiles: Vec<String> = profiles.iter().map(|p| p.to_string()).collect();
+            write!(f, " <{}>", profiles.join(" "))?;
+        }
+        if let Some(alternative) = &self.alternative {
+            write!(f, " | {}", alternative)?;
+        }
+        Ok(())
+    }
+}
+
+/// Serde helper for relation fields, e.g. Depends.
+///
+/// Use as `#[serde(default, with = "relations")]` on an `Option<Vec<PackageRelation>>`.
+pub mod relations {
+    use super::PackageRelation;
+    use serde::{de, Deserialize, Deserializer};
+
+    pub use crate::deb822::comma_separated::serialize;
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<Vec<PackageRelation>>, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        let relations = PackageRelation::parse(&text).map_err(de::Error::custom)?;
+        if relations.is_empty() {
+            Ok(None)
         } else {
-            Some(list)
+            Ok(Some(relations))
         }
     }
+}
 
-    pub fn new(relation: &str) -> Result<PackageRelation, RaptoboError> {
-        // see Debian Policy 7.1
-        let relation = relation.trim();
-        let (r, a) = match relation.split_once("|") {
-            Some((r, a)) => (r.trim(), Some(Box::new(PackageRelation::new(a.trim())?))),
-            None => (relation, None),
-        };
-
-        let (name, version) = match r.split_once(" ") {
-            None => {
-                return Ok(PackageRelation {
-                    package: relation.to_string(),
-                    relation: PackageVersionRelation::EQ,
-                    version: None,
-                    alternative: a,
-                })
+/// Recursive descent parser for the relation grammar of Debian Policy 7.1.
+struct RelationParser<'a> {
+    text: &'a str,
+    chars: Vec<char>,
+    /// Position of the next character.
+    pos: usize,
+}
+
+impl<'a> RelationParser<'a> {
+    fn new(text: &'a str) -> RelationParser<'a> {
+        RelationParser {
+            text,
+            chars: text.chars().collect(),
+            pos: 0,
+        }
+    }
+
+    fn error(&self, msg: &str) -> RaptoboError {
+        self.error_at(self.pos, msg)
+    }
+
+    fn error_at(&self, pos: usize, msg: &str) -> RaptoboError {
+        let found = match self.chars.get(pos) {
+            Some(c) => format!("'{}'", c),
+            None => String::from("end of input"),
+        };
+        RaptoboError::new(&format!(
+            "[PackageRelation] {}, found {} at position {} of \"{}\"",
+            msg, found, pos, self.text
+        ))
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.pos += 1;
+        }
+    }
+
+    /// Consume the given character, after optional whitespace.
+    fn eat(&mut self, c: char) -> bool {
+        self.skip_whitespace();
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), RaptoboError> {
+        if self.eat(c) {
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected '{}'", c)))
+        }
+    }
+
+    /// Consume the characters matching the predicate.
+    fn word(&mut self, valid: fn(char) -> bool) -> String {
+        let start = self.pos;
+        while self.peek().is_some_and(valid) {
+            self.pos += 1;
+        }
+        self.chars[start..self.pos].iter().collect()
+    }
+
+    /// Comma separated relations of a field.
+    fn relations(&mut self) -> Result<Vec<PackageRelation>, RaptoboError> {
+        let mut relations = Vec::new();
+
+        self.skip_whitespace();
+        while self.peek().is_some() {
+            relations.push(self.alternatives()?);
+            if !self.eat(',') {
+                self.skip_whitespace();
+                if self.peek().is_some() {
+                    return Err(self.error("expected ','"));
+                }
             }
-            Some((name, version)) => (name.trim(), version.trim()),
-        };
-        let version = &version[1..version.len() - 1];
-        let (rel, ver) = match version.split_once(" ") {
-            None => {
-                return Err(RaptoboError::new(&format!(
-                    "[PackageRelation] invalid version {}",
-                    version
-                )))
+            self.skip_whitespace();
+        }
+
+        Ok(relations)
+    }
+
+    /// Relation with alternatives separated by '|'.
+    fn alternatives(&mut self) -> Result<PackageRelation, RaptoboError> {
+        let mut relations = vec![self.relation()?];
+        while self.eat('|') {
+            relations.push(self.relation()?);
+        }
+
+        let mut relation = relations.pop().unwrap();
+        while let Some(mut previous) = relations.pop() {
+            previous.alternative = Some(Box::new(relation));
+            relation = previous;
+        }
+        Ok(relation)
+    }
+
+    /// Single relation, e.g. "foo:any (>= 1.0) [amd64] <!nocheck>".
+    fn relation(&mut self) -> Result<PackageRelation, RaptoboError> {
+        self.skip_whitespace();
+        let start = self.pos;
+        let package = self.word(is_package_char);
+        if package.is_empty() {
+            return Err(self.error("expected package name"));
+        }
+        if !package.starts_with(|c: char| c.is_ascii_alphanumeric()) {
+            return Err(self.error_at(start, "invalid package name"));
+        }
+
+        let arch_qualifier = if self.peek() == Some(':') {
+            self.pos += 1;
+            let qualifier = self.word(is_arch_char);
+            match qualifier.as_str() {
+                "" => return Err(self.error("expected architecture qualifier")),
+                "any" => Some(ArchQualifier::Any),
+                "native" => Some(ArchQualifier::Native),
+                _ => Some(ArchQualifier::Arch(qualifier)),
+            }
+        } else {
+            None
+        };
+
+        let (relation, version) = if self.eat('(') {
+            self.skip_whitespace();
+            let start = self.pos;
+            let relation = match self.word(|c| "<=>".contains(c)).as_str() {
+                "<<" => PackageVersionRelation::LT,
+                // "<" and ">" are deprecated forms of "<=" and ">="
+                "<=" | "<" => PackageVersionRelation::LTE,
+                "=" => PackageVersionRelation::EQ,
+                ">=" | ">" => PackageVersionRelation::GTE,
+                ">>" => PackageVersionRelation::GT,
+                _ => return Err(self.error_at(start, "expected version relation")),
+            };
+
+            self.skip_whitespace();
+            let start = self.pos;
+            let version = self.word(|c| !c.is_whitespace() && !"()[]<>,|".contains(c));
+            if version.is_empty() {
+                return Err(self.error("expected version"));
             }
-            Some((rel, ver)) => (rel.trim(), ver.trim()),
+            let version = PackageVersion::new(&version)
+                .map_err(|e| self.error_at(start, &format!("invalid version: {}", e)))?;
+            self.expect(')')?;
+
+            (relation, Some(version))
+        } else {
+            (PackageVersionRelation::EQ, None)
         };
-        let relation = PackageVersionRelation::new(rel)?;
-        let version = PackageVersion::new(ver)?;
+
+        let mut architectures = Vec::new();
+        if self.eat('[') {
+            loop {
+                self.skip_whitespace();
+                let start = self.pos;
+                let negated = self.peek() == Some('!');
+                if negated {
+                    self.pos += 1;
+                }
+                let architecture = self.word(is_arch_char);
+                if architecture.is_empty() {
+                    if !architectures.is_empty() && !negated && self.eat(']') {
+                        break;
+                    }
+                    return Err(self.error("expected architecture"));
+                }
+                if architectures
+                    .first()
+                    .is_some_and(|a: &ArchRestriction| a.negated != negated)
+                {
+                    return Err(self.error_at(
+                        start,
+                        "mixed negated and plain architectures in restriction list",
+                    ));
+                }
+                architectures.push(ArchRestriction {
+                    negated,
+                    architecture,
+                });
+            }
+        }
+
+        let mut profiles = Vec::new();
+        while self.eat('<') {
+            let mut terms = Vec::new();
+            loop {
+                self.skip_whitespace();
+                let negated = self.peek() == Some('!');
+                if negated {
+                    self.pos += 1;
+                }
+                let name = self.word(is_profile_char);
+                if name.is_empty() {
+                    if !terms.is_empty() && !negated && self.eat('>') {
+                        break;
+                    }
+                    return Err(self.error("expected build profile"));
+                }
+                terms.push(BuildProfile { negated, name });
+            }
+            profiles.push(terms);
+        }
 
         Ok(PackageRelation {
-            package: name.to_string(),
+            package,
+            arch_qualifier,
             relation,
-            version: Some(version),
-            alternative: a,
+            version,
+            architectures,
+            profiles,
+            alternative: None,
         })
     }
+}
 
-    pub fn is(&self, package: &PackageMetadata) -> bool {
-        let mut p = self;
+/// Characters of package names, Debian Policy 5.6.1.
+fn is_package_char(c: char) -> bool {
+    c.is_ascii_lowercase() || c.is_ascii_digit() || "+-.".contains(c)
+}
 
-        loop {
-            if p.package == package.package {
-                match &p.version {
-                    None => {
-                        return true;
-                    }
-                    Some(v) => match v.partial_cmp(&package.version) {
-                        None => {
-                            return false;
-                        }
-                        Some(ord) => {
-                            return p.relation.is(ord);
-                        }
-                    },
-                }
-            } else if let Some(alternative) = &p.alternative {
-                p = alternative;
-            } else {
-                return false;
-            }
+/// Characters of architecture names and wildcards, e.g. linux-any.
+fn is_arch_char(c: char) -> bool {
+    c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-'
+}
+
+/// Characters of build profile names, e.g. pkg.foo.nobar.
+fn is_profile_char(c: char) -> bool {
+    c.is_ascii_lowercase() || c.is_ascii_digit() || "+-.".contains(c)
+}
+
+/// Relation field of a binary package, Debian Policy 7.2 to 7.6.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, ValueEnum)]
+pub enum RelationField {
+    PreDepends,
+    Depends,
+    Recommends,
+    Suggests,
+    Enhances,
+    Breaks,
+    Conflicts,
+    Replaces,
+}
+
+impl RelationField {
+    /// All relation fields, except Provides which names no other packages.
+    pub const ALL: [RelationField; 8] = [
+        RelationField::PreDepends,
+        RelationField::Depends,
+        RelationField::Recommends,
+        RelationField::Suggests,
+        RelationField::Enhances,
+        RelationField::Breaks,
+        RelationField::Conflicts,
+        RelationField::Replaces,
+    ];
+
+    /// Name of the control field.
+    pub fn name(&self) -> &'static str {
+        match self {
+            RelationField::PreDepends => "Pre-Depends",
+            RelationField::Depends => "Depends",
+            RelationField::Recommends => "Recommends",
+            RelationField::Suggests => "Suggests",
+            RelationField::Enhances => "Enhances",
+            RelationField::Breaks => "Breaks",
+            RelationField::Conflicts => "Conflicts",
+            RelationField::Replaces => "Replaces",
         }
     }
+
+    /// Relations of this field of the package, empty if the field is not set.
+    pub fn relations<'a>(&self, package: &'a PackageMetadata) -> &'a [PackageRelation] {
+        let relations = match self {
+            RelationField::PreDepends => &package.pre_depends,
+            RelationField::Depends => &package.depends,
+            RelationField::Recommends => &package.recommends,
+            RelationField::Suggests => &package.suggests,
+            RelationField::Enhances => &package.enhances,
+            RelationField::Breaks => &package.breaks,
+            RelationField::Conflicts => &package.conflicts,
+            RelationField::Replaces => &package.replaces,
+        };
+        relations.as_deref().unwrap_or(&[])
+    }
+}
+
+impl fmt::Display for RelationField {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
 }
 
-#[derive(Debug, PartialEq, Clone)]
+/// Version of a package, Debian Policy 5.6.12.
+///
+/// The order is the one of dpkg, so versions can be used as keys of ordered maps.
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
 pub struct PackageVersion {
     pub epoch: u64,
     pub upstream_version: Version,
@@ -467,30 +1343,73 @@ pub struct PackageVersion {
 }
 
 impl PackageVersion {
-    pub fn parse(
-        key: &str,
-        stanza: &HashMap<String, Vec<String>>,
-    ) -> Result<PackageVersion, RaptoboError> {
-        let value = stanza_value(key, stanza)?;
-        PackageVersion::new(&value)
-    }
-
+    /// Parse a version string, Debian Policy 5.6.12.
+    ///
+    /// Versions with invalid characters, an invalid epoch or an empty part are rejected.
     pub fn new(version: &str) -> Result<PackageVersion, RaptoboError> {
-        let res = version.split_once(":");
-        let (epoch, tail) = match res {
+        let invalid = |msg: String| {
+            RaptoboError::with_kind(
+                ErrorKind::InvalidVersion,
+                &format!("[PackageVersion] {} in \"{}\"", msg, version),
+            )
+        };
+
+        if version.is_empty() {
+            return Err(invalid(String::from("empty version")));
+        }
+
+        let (epoch, tail, offset) = match version.split_once(':') {
             Some((e, r)) => {
+                if e.is_empty() || !e.chars().all(|c| c.is_ascii_digit()) {
+                    return Err(invalid(format!("invalid epoch \"{}\"", e)));
+                }
                 let epoch = e
                     .parse::<u64>()
-                    .map_err(|err| RaptoboError::new(&err.to_string()))?;
-                (epoch, r)
+                    .map_err(|err| invalid(format!("invalid epoch \"{}\": {}", e, err)))?;
+                (epoch, r, e.len() + 1)
             }
-            None => (0, version),
+            None => (0, version, 0),
         };
 
-        let res = tail.split_once("-");
-        let (upstream_version, debian_revision) = match res {
-            Some((v, r)) => (v, r),
-            None => (tail, ""),
+        // the revision starts after the last hyphen, the upstream version may contain hyphens
+        let (upstream_version, debian_revision) = match tail.rsplit_once('-') {
+            Some((v, r)) => (v, Some(r)),
+            None => (tail, None),
+        };
+
+        if upstream_version.is_empty() {
+            return Err(invalid(String::from("empty upstream version")));
+        }
+        if let Some((i, c)) = upstream_version
+            .chars()
+            .enumerate()
+            .find(|(_, c)| !c.is_ascii_alphanumeric() && !".+-~".contains(*c))
+        {
+            return Err(invalid(format!(
+                "invalid character '{}' at position {} of the upstream version",
+                c,
+                offset + i
+            )));
+        }
+
+        let debian_revision = match debian_revision {
+            None => "",
+            Some("") => return Err(invalid(String::from("empty revision"))),
+            Some(r) => {
+                let offset = offset + upstream_version.chars().count() + 1;
+                if let Some((i, c)) = r
+                    .chars()
+                    .enumerate()
+                    .find(|(_, c)| !c.is_ascii_alphanumeric() && !".+~".contains(*c))
+                {
+                    return Err(invalid(format!(
+                        "invalid character '{}' at position {} of the revision",
+                        c,
+                        offset + i
+                    )));
+                }
+                r
+            }
         };
 
         Ok(PackageVersion {
@@ -499,6 +1418,48 @@ impl PackageVersion {
             debian_revision: Version::new(debian_revision),
         })
     }
+
+    /// Check a version string against Debian Policy 5.6.12.
+    ///
+    /// The error describes why the version is illegal, e.g. the position of an
+    /// invalid character.
+    pub fn validate(version: &str) -> Result<(), RaptoboError> {
+        PackageVersion::new(version).map(|_| ())
+    }
+}
+
+impl fmt::Display for PackageVersion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.epoch > 0 {
+            write!(f, "{}:", self.epoch)?;
+        }
+        write!(f, "{}", self.upstream_version.version)?;
+        if !self.debian_revision.version.is_empty() {
+            write!(f, "-{}", self.debian_revision.version)?;
+        }
+        Ok(())
+    }
+}
+
+impl Serialize for PackageVersion {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for PackageVersion {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<PackageVersion, D::Error> {
+        let version = String::deserialize(deserializer)?;
+        PackageVersion::new(&version).map_err(de::Error::custom)
+    }
+}
+
+impl FromStr for PackageVersion {
+    type Err = RaptoboError;
+
+    fn from_str(version: &str) -> Result<PackageVersion, RaptoboError> {
+        PackageVersion::new(version)
+    }
 }
 
 impl PartialEq<str> for PackageVersion {
@@ -514,7 +1475,7 @@ impl PartialEq<str> for PackageVersion {
             None => (0, version),
         };
 
-        let res = tail.split_once("-");
+        let res = tail.rsplit_once("-");
         let (upstream_version, debian_revision) = match res {
             Some((v, r)) => (v, Some(r)),
             None => (tail, None),
@@ -530,131 +1491,108 @@ impl PartialEq<str> for PackageVersion {
     }
 }
 
+impl Ord for PackageVersion {
+    /// Compare like dpkg: epoch, then upstream version, then Debian revision.
+    fn cmp(&self, other: &PackageVersion) -> Ordering {
+        self.epoch
+            .cmp(&other.epoch)
+            .then_with(|| self.upstream_version.cmp(&other.upstream_version))
+            .then_with(|| self.debian_revision.cmp(&other.debian_revision))
+    }
+}
+
 impl PartialOrd for PackageVersion {
     fn partial_cmp(&self, other: &PackageVersion) -> Option<Ordering> {
-        if self.epoch != other.epoch {
-            self.epoch.partial_cmp(&other.epoch)
-        } else if self.upstream_version != other.upstream_version {
-            self.upstream_version.partial_cmp(&other.upstream_version)
-        } else {
-            self.debian_revision.partial_cmp(&other.debian_revision)
-        }
+        Some(self.cmp(other))
     }
 }
 
-#[derive(Debug, PartialEq, Clone)]
+/// Block of a version string: a non-digit prefix followed by a number.
+///
+/// A version is compared block by block, the missing blocks of the shorter
+/// version are empty, like in verrevcmp of dpkg.
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
 pub struct VersionBlock {
     pub prefix: String,
-    pub number: u64,
+    /// digits of the number without leading zeros, empty for zero, so numbers
+    /// of any length can be compared
+    pub number: String,
 }
 
 impl VersionBlock {
     fn new() -> VersionBlock {
         VersionBlock {
             prefix: String::new(),
-            number: 0,
+            number: String::new(),
         }
     }
 
     fn from(version: &str) -> Vec<VersionBlock> {
-        if version.len() == 0 {
-            return Vec::new();
-        }
-
         let mut blocks: Vec<VersionBlock> = Vec::new();
+        let mut chars = version.chars().peekable();
 
-        let mut start = 0;
-        let mut start_digit = 0;
-        let mut digit = false;
-
-        for (i, c) in version.chars().enumerate() {
-            if c.is_ascii_digit() {
-                digit = true;
-                start_digit = i;
-                continue;
+        while chars.peek().is_some() {
+            let mut block = VersionBlock::new();
+            while let Some(c) = chars.next_if(|c| !c.is_ascii_digit()) {
+                block.prefix.push(c);
             }
-
-            if !c.is_ascii_digit() && digit {
-                let prefix = version[start..start_digit].to_string();
-                let number = match version[start_digit..i].parse::<u64>() {
-                    Ok(n) => n,
-                    Err(e) => {
-                        log::error!(
-                            "[VersionBlock::from] invalid number: {} - {}",
-                            &version[start_digit..i],
-                            e
-                        );
-                        0
-                    }
-                };
-                blocks.push(VersionBlock { prefix, number });
-
-                digit = false;
-                start = i;
+            // leading zeros are ignored like in dpkg
+            while let Some(d) = chars.next_if(char::is_ascii_digit) {
+                if d != '0' || !block.number.is_empty() {
+                    block.number.push(d);
+                }
             }
+            blocks.push(block);
         }
 
-        let len = version.len();
-        if start_digit < start {
-            start_digit = len;
-        }
-        let prefix = version[start..start_digit].to_string();
-        let number = match version[start_digit..len].parse::<u64>() {
-            Ok(n) => n,
-            Err(e) => {
-                log::error!(
-                    "[VersionBlock::from] invalid number: {} - {}",
-                    &version[start_digit..len],
-                    e
-                );
-                0
-            }
-        };
-        blocks.push(VersionBlock { prefix, number });
-
         blocks
     }
-}
 
-impl PartialOrd for VersionBlock {
-    fn partial_cmp(&self, other: &VersionBlock) -> Option<Ordering> {
-        if (self.prefix.is_empty() && other.prefix.is_empty()) || (self.prefix == other.prefix) {
-            return self.number.partial_cmp(&other.number);
+    /// Sort weight of a prefix character like in dpkg: '~' sorts before the end
+    /// of the prefix, letters after the end, and all other characters after letters.
+    fn order(c: Option<char>) -> i64 {
+        match c {
+            None => 0,
+            Some('~') => -1,
+            Some(c) if c.is_ascii_alphabetic() => i64::from(u32::from(c)),
+            Some(c) => i64::from(u32::from(c)) + 256,
         }
+    }
+}
 
-        if self.prefix.is_empty() {
-            if other.prefix.chars().next().unwrap() == '~' {
-                return Some(Ordering::Greater);
-            } else {
-                return Some(Ordering::Less);
-            }
-        }
+impl Ord for VersionBlock {
+    fn cmp(&self, other: &VersionBlock) -> Ordering {
+        let mut s = self.prefix.chars();
+        let mut o = other.prefix.chars();
 
-        if other.prefix.is_empty() {
-            if self.prefix.chars().next().unwrap() == '~' {
-                return Some(Ordering::Less);
-            } else {
-                return Some(Ordering::Greater);
+        loop {
+            match (s.next(), o.next()) {
+                (None, None) => break,
+                (sc, oc) => match VersionBlock::order(sc).cmp(&VersionBlock::order(oc)) {
+                    Ordering::Equal => continue,
+                    ord => return ord,
+                },
             }
         }
 
-        for (s, o) in self.prefix.chars().zip(other.prefix.chars()) {
-            if s != o {
-                if s == '~' {
-                    return Some(Ordering::Less);
-                } else if o == '~' {
-                    return Some(Ordering::Greater);
-                } else {
-                    return s.partial_cmp(&o);
-                }
-            }
-        }
+        // without leading zeros, the longer number is the larger one
+        self.number
+            .len()
+            .cmp(&other.number.len())
+            .then_with(|| self.number.cmp(&other.number))
+    }
+}
 
-        self.prefix.len().partial_cmp(&other.prefix.len())
+impl PartialOrd for VersionBlock {
+    fn partial_cmp(&self, other: &VersionBlock) -> Option<Ordering> {
+        Some(self.cmp(other))
     }
 }
 
-#[derive(Debug, PartialEq, Clone)]
+/// Upstream version or Debian revision, compared with the algorithm of dpkg.
+///
+/// Equality follows the order, e.g. "1.01" equals "1.1" and "" equals "0".
+#[derive(Debug, Clone)]
 pub struct Version {
     pub version: String,
 }
@@ -667,8 +1605,8 @@ impl Version {
     }
 }
 
-impl PartialOrd for Version {
-    fn partial_cmp(&self, other: &Version) -> Option<Ordering> {
+impl Ord for Version {
+    fn cmp(&self, other: &Version) -> Ordering {
         let sl = VersionBlock::from(&self.version);
         let ol = VersionBlock::from(&other.version);
 
@@ -678,23 +1616,282 @@ impl PartialOrd for Version {
         let ol = ol.into_iter().chain(repeat(VersionBlock::new())).take(len);
 
         for (sb, ob) in sl.zip(ol) {
-            match sb.partial_cmp(&ob) {
-                None => panic!("[Version::partial_cmp] blocks not compareable!"),
-                Some(o) => match o {
-                    Ordering::Equal => continue,
-                    Ordering::Greater => return Some(Ordering::Greater),
-                    Ordering::Less => return Some(Ordering::Less),
-                },
+            match sb.cmp(&ob) {
+                Ordering::Equal => continue,
+                ord => return ord,
+            }
+        }
+
+        Ordering::Equal
+    }
+}
+
+impl PartialOrd for Version {
+    fn partial_cmp(&self, other: &Version) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Version {
+    fn eq(&self, other: &Version) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Version {}
+
+impl Hash for Version {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        // trailing empty blocks don't change the order, skip them to match Eq
+        let mut blocks = VersionBlock::from(&self.version);
+        while blocks.last() == Some(&VersionBlock::new()) {
+            blocks.pop();
+        }
+        blocks.hash(state);
+    }
+}
+
+/// Set of package versions, e.g. the versions satisfying ">= 1.2, << 2.0".
+///
+/// The range is a sorted list of disjoint intervals, so constraints of several
+/// relations can be intersected or united. A range without intervals is empty.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VersionRange {
+    intervals: Vec<(Bound<PackageVersion>, Bound<PackageVersion>)>,
+}
+
+impl VersionRange {
+    /// Range containing all versions.
+    pub fn any() -> VersionRange {
+        VersionRange {
+            intervals: vec![(Bound::Unbounded, Bound::Unbounded)],
+        }
+    }
+
+    /// Range containing no version.
+    pub fn none() -> VersionRange {
+        VersionRange {
+            intervals: Vec::new(),
+        }
+    }
+
+    /// Range of the versions satisfying the relation to the given version, e.g. ">= 1.0".
+    pub fn new(relation: &PackageVersionRelation, version: &PackageVersion) -> VersionRange {
+        let version = version.clone();
+        let interval = match relation {
+            PackageVersionRelation::LT => (Bound::Unbounded, Bound::Excluded(version)),
+            PackageVersionRelation::LTE => (Bound::Unbounded, Bound::Included(version)),
+            PackageVersionRelation::EQ => {
+                (Bound::Included(version.clone()), Bound::Included(version))
+            }
+            PackageVersionRelation::GTE => (Bound::Included(version), Bound::Unbounded),
+            PackageVersionRelation::GT => (Bound::Excluded(version), Bound::Unbounded),
+        };
+        VersionRange {
+            intervals: vec![interval],
+        }
+    }
+
+    /// Intersection of the ranges of all given relations, any version if there are none.
+    pub fn from_relations<'a, I>(relations: I) -> VersionRange
+    where
+        I: IntoIterator<Item = (&'a PackageVersionRelation, &'a PackageVersion)>,
+    {
+        relations
+            .into_iter()
+            .fold(VersionRange::any(), |range, (relation, version)| {
+                range.intersection(&VersionRange::new(relation, version))
+            })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.intervals.is_empty()
+    }
+
+    pub fn contains(&self, version: &PackageVersion) -> bool {
+        self.intervals.iter().any(|(lower, upper)| {
+            let above = match lower {
+                Bound::Included(l) => version >= l,
+                Bound::Excluded(l) => version > l,
+                Bound::Unbounded => true,
+            };
+            let below = match upper {
+                Bound::Included(u) => version <= u,
+                Bound::Excluded(u) => version < u,
+                Bound::Unbounded => true,
+            };
+            above && below
+        })
+    }
+
+    /// Versions contained in both ranges.
+    pub fn intersection(&self, other: &VersionRange) -> VersionRange {
+        let mut intervals = Vec::new();
+
+        for (sl, su) in &self.intervals {
+            for (ol, ou) in &other.intervals {
+                let lower = if cmp_lower(sl, ol) == Ordering::Less {
+                    ol
+                } else {
+                    sl
+                };
+                let upper = if cmp_upper(su, ou) == Ordering::Less {
+                    su
+                } else {
+                    ou
+                };
+                if !is_empty_interval(lower, upper) {
+                    intervals.push((lower.clone(), upper.clone()));
+                }
+            }
+        }
+
+        // the intervals of both ranges are sorted and disjoint, so are the intersections
+        intervals.sort_by(|(a, _), (b, _)| cmp_lower(a, b));
+        VersionRange { intervals }
+    }
+
+    /// Versions contained in any of the ranges.
+    pub fn union(&self, other: &VersionRange) -> VersionRange {
+        let mut all: Vec<_> = self
+            .intervals
+            .iter()
+            .chain(other.intervals.iter())
+            .cloned()
+            .collect();
+        all.sort_by(|(a, _), (b, _)| cmp_lower(a, b));
+
+        let mut intervals: Vec<(Bound<PackageVersion>, Bound<PackageVersion>)> = Vec::new();
+        for (lower, upper) in all {
+            match intervals.last_mut() {
+                Some((_, last)) if !is_gap(last, &lower) => {
+                    if cmp_upper(last, &upper) == Ordering::Less {
+                        *last = upper;
+                    }
+                }
+                _ => intervals.push((lower, upper)),
             }
         }
 
-        Some(Ordering::Equal)
+        VersionRange { intervals }
+    }
+
+    /// The package with the highest version contained in the range.
+    pub fn highest<'a, I>(&self, packages: I) -> Option<&'a PackageMetadata>
+    where
+        I: IntoIterator<Item = &'a PackageMetadata>,
+    {
+        packages
+            .into_iter()
+            .filter(|p| self.contains(&p.version))
+            .max_by(|a, b| a.version.cmp(&b.version))
+    }
+}
+
+impl fmt::Display for VersionRange {
+    /// Write the range as alternatives of relations, e.g. ">= 1.0, << 2.0 | = 3.0".
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.intervals.is_empty() {
+            return write!(f, "none");
+        }
+
+        let intervals: Vec<String> = self
+            .intervals
+            .iter()
+            .map(|interval| match interval {
+                (Bound::Unbounded, Bound::Unbounded) => String::from("any"),
+                (Bound::Included(l), Bound::Included(u)) if l == u => format!("= {}", l),
+                (lower, upper) => {
+                    let mut relations = Vec::new();
+                    match lower {
+                        Bound::Included(l) => relations.push(format!(">= {}", l)),
+                        Bound::Excluded(l) => relations.push(format!(">> {}", l)),
+                        Bound::Unbounded => {}
+                    }
+                    match upper {
+                        Bound::Included(u) => relations.push(format!("<= {}", u)),
+                        Bound::Excluded(u) => relations.push(format!("<< {}", u)),
+                        Bound::Unbounded => {}
+                    }
+                    relations.join(", ")
+                }
+            })
+            .collect();
+
+        write!(f, "{}", intervals.join(" | "))
+    }
+}
+
+/// Order of lower bounds, an included version starts before an excluded one.
+fn cmp_lower(a: &Bound<PackageVersion>, b: &Bound<PackageVersion>) -> Ordering {
+    match (a, b) {
+        (Bound::Unbounded, Bound::Unbounded) => Ordering::Equal,
+        (Bound::Unbounded, _) => Ordering::Less,
+        (_, Bound::Unbounded) => Ordering::Greater,
+        (Bound::Included(a), Bound::Excluded(b)) if a == b => Ordering::Less,
+        (Bound::Excluded(a), Bound::Included(b)) if a == b => Ordering::Greater,
+        (Bound::Included(a) | Bound::Excluded(a), Bound::Included(b) | Bound::Excluded(b)) => {
+            a.cmp(b)
+        }
+    }
+}
+
+/// Order of upper bounds, an excluded version ends before an included one.
+fn cmp_upper(a: &Bound<PackageVersion>, b: &Bound<PackageVersion>) -> Ordering {
+    match (a, b) {
+        (Bound::Unbounded, Bound::Unbounded) => Ordering::Equal,
+        (Bound::Unbounded, _) => Ordering::Greater,
+        (_, Bound::Unbounded) => Ordering::Less,
+        (Bound::Excluded(a), Bound::Included(b)) if a == b => Ordering::Less,
+        (Bound::Included(a), Bound::Excluded(b)) if a == b => Ordering::Greater,
+        (Bound::Included(a) | Bound::Excluded(a), Bound::Included(b) | Bound::Excluded(b)) => {
+            a.cmp(b)
+        }
+    }
+}
+
+/// Check if an interval doesn't contain any version, e.g. ">> 1.0, <= 1.0".
+fn is_empty_interval(lower: &Bound<PackageVersion>, upper: &Bound<PackageVersion>) -> bool {
+    match (lower, upper) {
+        (Bound::Included(l), Bound::Included(u)) => l > u,
+        (Bound::Included(l) | Bound::Excluded(l), Bound::Included(u) | Bound::Excluded(u)) => {
+            l >= u
+        }
+        _ => false,
+    }
+}
+
+/// Check if there are versions between an upper bound and the following lower bound.
+fn is_gap(upper: &Bound<PackageVersion>, lower: &Bound<PackageVersion>) -> bool {
+    match (upper, lower) {
+        (Bound::Excluded(u), Bound::Excluded(l)) => u <= l,
+        (Bound::Included(u) | Bound::Excluded(u), Bound::Included(l) | Bound::Excluded(l)) => u < l,
+        _ => false,
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{PackageVersion, Version, VersionBlock};
+    use super::{
+        ArchQualifier, ArchRestriction, BuildProfile, MultiArch, PackageMetadata, PackageRelation,
+        PackageVersion, PackageVersionRelation, Priority, ProviderIndex, RelationField, Section,
+        Version, VersionBlock, VersionRange,
+    };
+    use crate::deb822::{write_stanzas, Stanza, StanzaReader, PACKAGES_FIELD_ORDER};
+    use crate::error::ErrorKind;
+    use crate::person::Person;
+    use crate::utils::decompress;
+    use std::cmp::Ordering;
+    use std::collections::hash_map::DefaultHasher;
+    use std::collections::{BTreeMap, HashSet};
+    use std::fs;
+    use std::hash::{Hash, Hasher};
+
+    fn hash(version: &PackageVersion) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        version.hash(&mut hasher);
+        hasher.finish()
+    }
 
     #[test]
     fn version_parsing_works() {
@@ -769,41 +1966,41 @@ mod tests {
     fn compare_versions() {
         let v1 = VersionBlock {
             prefix: String::from(""),
-            number: 1,
+            number: String::from("1"),
         };
         let v2 = VersionBlock {
             prefix: String::from(""),
-            number: 2,
+            number: String::from("2"),
         };
         assert!(v1 < v2);
 
         let v1 = VersionBlock {
             prefix: String::from(""),
-            number: 1,
+            number: String::from("1"),
         };
         let v2 = VersionBlock {
             prefix: String::from(""),
-            number: 1,
+            number: String::from("1"),
         };
         assert!(v1 == v2);
 
         let v1 = VersionBlock {
             prefix: String::from("b"),
-            number: 1,
+            number: String::from("1"),
         };
         let v2 = VersionBlock {
             prefix: String::from("a"),
-            number: 2,
+            number: String::from("2"),
         };
         assert!(v2 < v1);
 
         let v1 = VersionBlock {
             prefix: String::from(""),
-            number: 1,
+            number: String::from("1"),
         };
         let v2 = VersionBlock {
             prefix: String::from("~"),
-            number: 2,
+            number: String::from("2"),
         };
         assert!(v2 < v1);
     }
@@ -814,13 +2011,873 @@ mod tests {
 
         assert_eq!(blocks.len(), 3);
 
-        assert_eq!(blocks[0].number, 1);
+        assert_eq!(blocks[0].number, "1");
         assert_eq!(blocks[0].prefix, "");
 
-        assert_eq!(blocks[1].number, 2);
+        assert_eq!(blocks[1].number, "2");
         assert_eq!(blocks[1].prefix, ".");
 
-        assert_eq!(blocks[2].number, 3);
+        assert_eq!(blocks[2].number, "3");
         assert_eq!(blocks[2].prefix, ".");
     }
+
+    fn bundled_packages() -> Vec<u8> {
+        let path = format!("{}/Packages.xz", env!("CARGO_MANIFEST_DIR"));
+        decompress(&fs::read(path).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn write_packages_roundtrip() {
+        let content = bundled_packages();
+        let stanzas: Vec<Stanza> = StanzaReader::new(content.as_slice())
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(stanzas.len(), 6090);
+
+        // stanzas are written back byte by byte
+        let mut written = Vec::new();
+        write_stanzas(&mut written, &stanzas).unwrap();
+        assert!(written == content);
+
+        // packages keep the value of all fields, the unknown ones as extra
+        // fields, only trailing whitespace is removed and persons are normalized
+        let canonical = |value: &Vec<String>| -> Vec<String> {
+            value.iter().map(|l| l.trim_end().to_string()).collect()
+        };
+        let persons = |value: &Vec<String>| Person::parse_list(&value.join("\n")).unwrap();
+        for stanza in stanzas {
+            let package = PackageMetadata::new(stanza.clone()).unwrap();
+            let written = package.to_stanza().unwrap();
+
+            assert!(written.len() >= 16, "{}", package.package);
+            for field in written.fields() {
+                if ["Maintainer", "Original-Maintainer"].contains(&field.name.as_str()) {
+                    assert_eq!(persons(&field.value), persons(&stanza[&field.name]));
+                    continue;
+                }
+                assert_eq!(
+                    Some(canonical(&field.value)),
+                    stanza.get(&field.name).map(canonical),
+                    "{} {}",
+                    package.package,
+                    field.name
+                );
+            }
+            assert_eq!(written.len(), stanza.len(), "{}", package.package);
+
+            // typed fields are in the canonical order, the extra fields are appended
+            let typed = |k: &&str| !package.extra_fields.contains_key(k);
+            let mut sorted = stanza.clone();
+            sorted.sort_fields(PACKAGES_FIELD_ORDER);
+            let order: Vec<&str> = sorted.keys().filter(typed).collect();
+            assert_eq!(written.keys().filter(typed).collect::<Vec<_>>(), order);
+            assert_eq!(
+                written.keys().filter(|k| !typed(k)).collect::<Vec<_>>(),
+                package.extra_fields.keys().collect::<Vec<_>>()
+            );
+        }
+    }
+
+    #[test]
+    fn parse_control_fields() {
+        let content = "Package: init\n\
+                       Architecture: amd64\n\
+                       Version: 1.65.2\n\
+                       Built-Using: glibc (= 2.36-9)\n\
+                       Protected: yes\n\
+                       Important: yes\n\
+                       Filename: pool/main/i/init/init_1.65.2_amd64.deb\n\
+                       Tag: admin::boot, role::program\n\
+                       Phased-Update-Percentage: 10\n\
+                       X-Cargo-Built-Using: rust-clap (= 4.0.32-1)\n";
+        let stanza = StanzaReader::new(content.as_bytes())
+            .next()
+            .unwrap()
+            .unwrap();
+        let package = PackageMetadata::new(stanza).unwrap();
+
+        assert_eq!(package.protected, Some(true));
+        assert_eq!(package.important, Some(true));
+        assert_eq!(package.build_essential, None);
+        assert_eq!(package.phased_update_percentage, Some(10));
+        assert_eq!(
+            package.tag,
+            Some(vec!["admin::boot".to_string(), "role::program".to_string()])
+        );
+        let built_using = package.built_using.as_ref().unwrap();
+        assert_eq!(built_using.len(), 1);
+        assert_eq!(built_using[0].to_string(), "glibc (= 2.36-9)");
+
+        // unknown fields are kept and written back
+        assert_eq!(
+            package.extra_fields.keys().collect::<Vec<_>>(),
+            vec!["X-Cargo-Built-Using"]
+        );
+        assert_eq!(package.to_stanza().unwrap().to_string(), content);
+    }
+
+    #[test]
+    fn parse_typed_fields() {
+        let content = "Package: nvidia-kernel-common\n\
+                       Architecture: amd64\n\
+                       Version: 525.105.17-1\n\
+                       Priority: optional\n\
+                       Essential: no\n\
+                       Section: non-free/libs\n\
+                       Installed-Size: 1024\n\
+                       Size: 311624\n";
+        let stanza = StanzaReader::new(content.as_bytes())
+            .next()
+            .unwrap()
+            .unwrap();
+        let package = PackageMetadata::new(stanza.clone()).unwrap();
+
+        assert_eq!(package.priority, Some(Priority::Optional));
+        assert_eq!(package.essential, Some(false));
+        assert_eq!(package.installed_size, Some(1024));
+        assert_eq!(package.size, Some(311624));
+        let section = package.section.as_ref().unwrap();
+        assert_eq!(section.area.as_deref(), Some("non-free"));
+        assert_eq!(section.name, "libs");
+        assert_eq!(package.to_stanza().unwrap().to_string(), content);
+
+        let main = Section::new("libs").unwrap();
+        assert_eq!(main.area, None);
+        assert_eq!(main.to_string(), "libs");
+        assert!(Section::new("non-free/").is_err());
+        assert!(Section::new("a/b/c").is_err());
+
+        // invalid optional values are reported and kept as extra fields
+        for (name, value) in [
+            ("Section", "a/b/c"),
+            ("Essential", "maybe"),
+            ("Installed-Size", "1k"),
+        ] {
+            let mut invalid = stanza.clone();
+            invalid.set_value(name, value);
+            let package = PackageMetadata::new(invalid.clone()).unwrap();
+            assert_eq!(package.field_errors.len(), 1);
+            assert_eq!(
+                package.field_errors[0].kind(),
+                &ErrorKind::InvalidField(name.to_string())
+            );
+            assert_eq!(package.extra_fields.keys().collect::<Vec<_>>(), [name]);
+            assert_eq!(package.to_stanza().unwrap(), invalid);
+        }
+
+        // invalid required values are errors
+        let mut invalid = stanza.clone();
+        invalid.set_value("Version", "1:");
+        let err = PackageMetadata::new(invalid).unwrap_err();
+        assert_eq!(err.kind(), &ErrorKind::InvalidField("Version".to_string()));
+    }
+
+    #[test]
+    fn keep_packages_with_unknown_priority() {
+        let content = "Package: hello\n\
+                       Architecture: amd64\n\
+                       Version: 2.10-3\n\
+                       Priority: Optional\n\
+                       \n\
+                       Package: hello\n\
+                       Architecture: source\n\
+                       Version: 2.10-3\n\
+                       Priority: source\n\
+                       \n\
+                       Package: bash\n\
+                       Architecture: amd64\n\
+                       Version: 5.2.15-2\n\
+                       Priority: required\n";
+        let packages: Vec<PackageMetadata> = PackageMetadata::read(content.as_bytes())
+            .collect::<Result<_, _>>()
+            .unwrap();
+
+        let priorities: Vec<Option<Priority>> =
+            packages.iter().map(|p| p.priority.clone()).collect();
+        assert_eq!(
+            priorities,
+            [
+                Some(Priority::Optional),
+                Some(Priority::Other("source".to_string())),
+                Some(Priority::Required)
+            ]
+        );
+        assert_eq!(packages[1].priority.as_ref().unwrap().to_string(), "source");
+    }
+
+    #[test]
+    fn write_multiline_fields() {
+        let content = "Package: hello\n\
+                       Binary: hello\n\
+                       Version: 1:2.10-3\n\
+                       Architecture: any\n\
+                       Description: example package\n \
+                       The GNU hello program.\n \
+                       .\n \
+                       It prints a greeting.\n\
+                       Checksums-Sha256:\n \
+                       e0b1 1000 hello_2.10.orig.tar.gz\n \
+                       a1b2 200 hello_2.10-3.debian.tar.xz\n";
+        let stanza = StanzaReader::new(content.as_bytes())
+            .next()
+            .unwrap()
+            .unwrap();
+        let package = PackageMetadata::new(stanza).unwrap();
+        assert_eq!(
+            package.description.as_deref(),
+            Some("example package\nThe GNU hello program.\n\nIt prints a greeting.")
+        );
+
+        assert_eq!(
+            package.to_stanza().unwrap().to_string(),
+            "Package: hello\n\
+             Binary: hello\n\
+             Architecture: any\n\
+             Version: 1:2.10-3\n\
+             Description: example package\n \
+             The GNU hello program.\n \
+             .\n \
+             It prints a greeting.\n\
+             Checksums-Sha256:\n \
+             e0b1 1000 hello_2.10.orig.tar.gz\n \
+             a1b2 200 hello_2.10-3.debian.tar.xz\n"
+        );
+    }
+
+    #[test]
+    fn parse_relations() {
+        let content = "Package: hello\n\
+                       Architecture: amd64\n\
+                       Version: 2.10-3\n\
+                       Depends: libc6 (>= 2.34), debconf | debconf-2.0\n\
+                       Suggests: hello-doc\n";
+        let stanza = StanzaReader::new(content.as_bytes())
+            .next()
+            .unwrap()
+            .unwrap();
+        let package = PackageMetadata::new(stanza).unwrap();
+
+        let depends = package.depends.as_ref().unwrap();
+        assert_eq!(depends.len(), 2);
+        assert_eq!(depends[0].package, "libc6");
+        assert!(depends[0].version.as_ref().unwrap() == "2.34");
+        assert_eq!(depends[1].package, "debconf");
+        assert_eq!(
+            depends[1].alternative.as_ref().unwrap().package,
+            "debconf-2.0"
+        );
+        assert_eq!(package.suggests.as_ref().unwrap()[0].package, "hello-doc");
+        assert_eq!(package.to_stanza().unwrap().to_string(), content);
+
+        let stanza = StanzaReader::new(
+            &b"Package: a\nVersion: 1\nArchitecture: all\nDepends: b (~ 1)\n"[..],
+        )
+        .next()
+        .unwrap()
+        .unwrap();
+        let error = PackageMetadata::new(stanza).unwrap_err().to_string();
+        assert_eq!(
+            error,
+            "[deb822] field Depends: [PackageRelation] expected version relation, \
+             found '~' at position 3 of \"b (~ 1)\""
+        );
+    }
+
+    #[test]
+    fn relation_grammar() {
+        let r = PackageRelation::new("foo:any (>= 1.0) [amd64] <!nocheck>").unwrap();
+        assert_eq!(r.package, "foo");
+        assert_eq!(r.arch_qualifier, Some(ArchQualifier::Any));
+        assert_eq!(r.relation, PackageVersionRelation::GTE);
+        assert!(r.version.as_ref().unwrap() == "1.0");
+        assert_eq!(
+            r.architectures,
+            [ArchRestriction {
+                negated: false,
+                architecture: String::from("amd64")
+            }]
+        );
+        assert_eq!(
+            r.profiles,
+            [[BuildProfile {
+                negated: true,
+                name: String::from("nocheck")
+            }]]
+        );
+        assert!(r.alternative.is_none());
+
+        let r = PackageRelation::new("perl:native").unwrap();
+        assert_eq!(r.arch_qualifier, Some(ArchQualifier::Native));
+        assert!(r.version.is_none());
+        let r = PackageRelation::new("libc6-dev:i386").unwrap();
+        assert_eq!(
+            r.arch_qualifier,
+            Some(ArchQualifier::Arch(String::from("i386")))
+        );
+
+        // arbitrary alternatives, each with its own restrictions
+        let r = PackageRelation::new(
+            "a [linux-any] | b:any (<< 2) [!amd64 !i386] <stage1 cross> <!nocheck> | c",
+        )
+        .unwrap();
+        let alternatives: Vec<&PackageRelation> = r.alternatives().collect();
+        assert_eq!(alternatives.len(), 3);
+        assert_eq!(alternatives[0].architectures[0].architecture, "linux-any");
+        assert_eq!(alternatives[1].relation, PackageVersionRelation::LT);
+        assert_eq!(alternatives[1].architectures.len(), 2);
+        assert!(alternatives[1].architectures.iter().all(|a| a.negated));
+        assert_eq!(alternatives[1].profiles.len(), 2);
+        assert_eq!(alternatives[1].profiles[0].len(), 2);
+        assert_eq!(alternatives[1].profiles[0][1].name, "cross");
+        assert_eq!(alternatives[2].package, "c");
+
+        // canonical form
+        for (relation, canonical) in [
+            ("libc6 (>= 2.34)", "libc6 (>= 2.34)"),
+            ("libc6(>=2.34)", "libc6 (>= 2.34)"),
+            ("  libc6  (  <<  2.34  )  ", "libc6 (<< 2.34)"),
+            ("foo (< 1)", "foo (<= 1)"),
+            ("foo (> 1)", "foo (>= 1)"),
+            ("foo (= 1:2.0-1~bpo1)", "foo (= 1:2.0-1~bpo1)"),
+            ("foo|bar", "foo | bar"),
+            ("foo [ amd64  i386 ]", "foo [amd64 i386]"),
+            (
+                "foo <!nocheck><pkg.foo.bar>",
+                "foo <!nocheck> <pkg.foo.bar>",
+            ),
+            ("g++ (>= 4:12) | clang++-14", "g++ (>= 4:12) | clang++-14"),
+        ] {
+            assert_eq!(
+                PackageRelation::new(relation).unwrap().to_string(),
+                canonical
+            );
+        }
+
+        // fields, with line breaks and a trailing comma
+        let relations = PackageRelation::parse(
+            "debhelper-compat (= 13),\ndh-golang,\nsamba-dsdb-modules [amd64 arm64] <!nocheck>,\n",
+        )
+        .unwrap();
+        assert_eq!(relations.len(), 3);
+        assert_eq!(relations[2].profiles[0][0].name, "nocheck");
+        assert!(PackageRelation::parse("").unwrap().is_empty());
+        assert!(PackageRelation::parse(" \n ").unwrap().is_empty());
+    }
+
+    #[test]
+    fn relation_errors() {
+        for (relations, message) in [
+            (",", "expected package name, found ',' at position 0"),
+            ("foo,,bar", "expected package name, found ',' at position 4"),
+            ("foo | ", "expected package name, found end of input at position 6"),
+            ("foo bar", "expected ',', found 'b' at position 4"),
+            ("Foo", "expected package name, found 'F' at position 0"),
+            ("-foo", "invalid package name, found '-' at position 0"),
+            ("foo:", "expected architecture qualifier, found end of input at position 4"),
+            ("foo (>= 1.0", "expected ')', found end of input at position 11"),
+            ("foo (1.0)", "expected version relation, found '1' at position 5"),
+            ("foo (=> 1.0)", "expected version relation, found '=' at position 5"),
+            ("foo (>= )", "expected version, found ')' at position 8"),
+            (
+                "foo (>= a:1)",
+                "invalid version: [PackageVersion] invalid epoch \"a\" in \"a:1\", found 'a' at position 8",
+            ),
+            ("foo []", "expected architecture, found ']' at position 5"),
+            ("foo [amd64", "expected architecture, found end of input at position 10"),
+            ("foo [amd64 !i386]", "mixed negated and plain architectures in restriction list, found '!' at position 11"),
+            ("foo [!amd64 i386]", "mixed negated and plain architectures in restriction list, found 'i' at position 12"),
+            ("foo [amd64] [i386]", "expected ',', found '[' at position 12"),
+            ("foo <>", "expected build profile, found '>' at position 5"),
+            ("foo <!>", "expected build profile, found '>' at position 6"),
+            ("foo <nocheck", "expected build profile, found end of input at position 12"),
+            ("foo <nocheck> (>= 1)", "expected ',', found '(' at position 14"),
+        ] {
+            let error = PackageRelation::parse(relations).unwrap_err().to_string();
+            assert_eq!(
+                error,
+                format!("[PackageRelation] {} of \"{}\"", message, relations),
+                "{}",
+                relations
+            );
+        }
+
+        let error = PackageRelation::new("foo, bar").unwrap_err().to_string();
+        assert!(error.contains("unexpected character, found ',' at position 3"));
+    }
+
+    #[test]
+    fn compare_versions_dpkg() {
+        // expected results of dpkg --compare-versions
+        for (a, b, expected) in [
+            ("1.0", "1.0", Ordering::Equal),
+            ("1.0", "2.0", Ordering::Less),
+            ("2.0", "1.0", Ordering::Greater),
+            ("1.0-1", "1.0-1", Ordering::Equal),
+            ("1.0-1", "1.0-2", Ordering::Less),
+            ("1:1.0", "0:1.0", Ordering::Greater),
+            ("0:1.0", "1.0", Ordering::Equal),
+            ("1:1.0", "2:0.1", Ordering::Less),
+            ("2:1.0", "10:0.9", Ordering::Less),
+            ("1.0", "1.0-0", Ordering::Equal),
+            ("1.0-0", "1.0-00", Ordering::Equal),
+            ("0:0-0", "0:0-00", Ordering::Equal),
+            ("0:0-0", "0:0-0.0", Ordering::Less),
+            ("0:0.0-0", "0:0-0", Ordering::Greater),
+            ("1.0", "1.0.0", Ordering::Less),
+            ("1.0~rc1", "1.0", Ordering::Less),
+            ("1.0~rc1", "1.0~rc2", Ordering::Less),
+            ("1.0~rc1~beta", "1.0~rc1", Ordering::Less),
+            ("1~~", "1~~a", Ordering::Less),
+            ("1~~a", "1~", Ordering::Less),
+            ("1~", "1", Ordering::Less),
+            ("1", "1a", Ordering::Less),
+            ("1a", "1b", Ordering::Less),
+            ("1.0a", "1.0.1", Ordering::Less),
+            ("1.0+1", "1.0.1", Ordering::Less),
+            ("1.0+dfsg", "1.0", Ordering::Greater),
+            ("1.0+dfsg", "1.0a", Ordering::Greater),
+            ("1.0-1", "1.0-1ubuntu1", Ordering::Less),
+            ("1.0-1ubuntu1", "1.0-1build1", Ordering::Greater),
+            ("1.10", "1.9", Ordering::Greater),
+            ("1.001", "1.1", Ordering::Equal),
+            ("0001", "1", Ordering::Equal),
+            ("1.2.3-4-5", "1.2.3-4-4", Ordering::Greater),
+            ("1.2.3-4-5", "1.2.3", Ordering::Greater),
+            ("9", "10", Ordering::Less),
+            ("a", "b", Ordering::Less),
+            ("a", "A", Ordering::Greater),
+            ("A", "a", Ordering::Less),
+            ("..", ".a", Ordering::Greater),
+            ("~", "~~", Ordering::Greater),
+            ("~~", "~a", Ordering::Less),
+            ("~a", ".", Ordering::Less),
+            ("1.0~~", "1.0~", Ordering::Less),
+            ("2.34-0ubuntu3", "2.34-0ubuntu3.2", Ordering::Less),
+            ("1:2.30.2-1ubuntu1", "1:2.30.2-1", Ordering::Greater),
+            (
+                "3.20191218.1ubuntu2",
+                "3.20200609.0ubuntu0.20.04.1",
+                Ordering::Less,
+            ),
+            ("1.0-1~bpo1", "1.0-1", Ordering::Less),
+            ("7.6.q-31", "7.6-31", Ordering::Greater),
+            ("0.0~git20230101", "0.0~git20221231", Ordering::Greater),
+            (
+                "0.0~git123456789012345678901",
+                "0.0~git123456789012345678902",
+                Ordering::Less,
+            ),
+            (
+                "0.0~git000123456789012345678901",
+                "0.0~git123456789012345678901",
+                Ordering::Equal,
+            ),
+            (
+                "1.99999999999999999999",
+                "1.100000000000000000000",
+                Ordering::Less,
+            ),
+        ] {
+            let va: PackageVersion = a.parse().unwrap();
+            let vb: PackageVersion = b.parse().unwrap();
+            assert_eq!(va.cmp(&vb), expected, "{} {}", a, b);
+            assert_eq!(vb.cmp(&va), expected.reverse(), "{} {}", b, a);
+            assert_eq!(va == vb, expected == Ordering::Equal, "{} {}", a, b);
+            if expected == Ordering::Equal {
+                assert_eq!(hash(&va), hash(&vb), "{} {}", a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn version_keys() {
+        let v: PackageVersion = "1:2.30.2-1ubuntu1".parse().unwrap();
+        assert_eq!(v.epoch, 1);
+        assert_eq!(v.to_string(), "1:2.30.2-1ubuntu1");
+
+        let v: PackageVersion = "1.2.3-4-5".parse().unwrap();
+        assert_eq!(v.upstream_version.version, "1.2.3-4");
+        assert_eq!(v.debian_revision.version, "5");
+        assert_eq!(v.to_string(), "1.2.3-4-5");
+
+        let versions: BTreeMap<PackageVersion, &str> = [
+            ("1.0-1", "a"),
+            ("1.0~rc1-1", "b"),
+            ("1:0.9", "c"),
+            ("1.0-1ubuntu1", "d"),
+            ("1.00-1", "e"),
+        ]
+        .into_iter()
+        .map(|(v, n)| (v.parse().unwrap(), n))
+        .collect();
+        // 1.00-1 replaces 1.0-1, both are equal for dpkg
+        assert_eq!(
+            versions.values().collect::<Vec<_>>(),
+            [&"b", &"e", &"d", &"c"]
+        );
+
+        let versions: HashSet<PackageVersion> = ["1.0", "1.0-0", "0:1.00", "1.0-1"]
+            .into_iter()
+            .map(|v| v.parse().unwrap())
+            .collect();
+        assert_eq!(versions.len(), 2);
+    }
+
+    #[test]
+    fn validate_versions() {
+        for version in [
+            "1.0",
+            "1:2.30.2-1ubuntu1",
+            "1.2.3-4-5",
+            "0.0~git2023+dfsg.1-1~bpo12+1",
+            "~1-4.5.6",
+        ] {
+            PackageVersion::validate(version).unwrap();
+        }
+
+        for (version, message) in [
+            ("", "empty version"),
+            (":1.0", "invalid epoch \"\""),
+            ("a:1.0", "invalid epoch \"a\""),
+            ("-1:1.0", "invalid epoch \"-1\""),
+            (
+                "99999999999999999999:1.0",
+                "invalid epoch \"99999999999999999999\": number too large to fit in target type",
+            ),
+            ("1:", "empty upstream version"),
+            ("-1", "empty upstream version"),
+            ("1.0-", "empty revision"),
+            (
+                "1.0 beta",
+                "invalid character ' ' at position 3 of the upstream version",
+            ),
+            (
+                "1:1.0_1",
+                "invalid character '_' at position 5 of the upstream version",
+            ),
+            (
+                "1:2:3",
+                "invalid character ':' at position 3 of the upstream version",
+            ),
+            ("1.0-1:2", "invalid epoch \"1.0-1\""),
+            (
+                "1:1.0-1:2",
+                "invalid character ':' at position 7 of the revision",
+            ),
+            (
+                "1.0-1-a_b",
+                "invalid character '_' at position 7 of the revision",
+            ),
+            // non-ASCII characters are rejected instead of splitting a character
+            (
+                "1.0\u{e9}-1",
+                "invalid character '\u{e9}' at position 3 of the upstream version",
+            ),
+            (
+                "2.0-1\u{2028}",
+                "invalid character '\u{2028}' at position 5 of the revision",
+            ),
+        ] {
+            let error = PackageVersion::validate(version).unwrap_err();
+            assert_eq!(error.kind(), &ErrorKind::InvalidVersion);
+            assert_eq!(
+                error.to_string(),
+                format!("[PackageVersion] {} in \"{}\"", message, version)
+            );
+        }
+
+        // comparison of unchecked versions doesn't panic on non-ASCII characters
+        assert!(Version::new("1.0\u{e9}") > Version::new("1.0"));
+        assert!(Version::new("1.\u{e9}1") > Version::new("1.a1"));
+        assert!(Version::new("\u{1f980}~") < Version::new("\u{1f980}"));
+
+        // a malformed package of an index is skipped, the others are loaded
+        let content = "Package: a\nVersion: 1.0\u{e9}\nArchitecture: all\n\n\
+                       Package: b\nVersion: 1.0\nArchitecture: all\n";
+        let packages = PackageMetadata::read(content.as_bytes())
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(packages.len(), 1);
+        assert_eq!(packages[0].package, "b");
+    }
+
+    fn package(name: &str, version: &str) -> PackageMetadata {
+        let content = format!(
+            "Package: {}\nVersion: {}\nArchitecture: all\n",
+            name, version
+        );
+        PackageMetadata::new(
+            StanzaReader::new(content.as_bytes())
+                .next()
+                .unwrap()
+                .unwrap(),
+        )
+        .unwrap()
+    }
+
+    fn range(relations: &str) -> VersionRange {
+        let relations = PackageRelation::parse(relations).unwrap();
+        VersionRange::from_relations(
+            relations
+                .iter()
+                .filter_map(|r| r.version.as_ref().map(|v| (&r.relation, v))),
+        )
+    }
+
+    #[test]
+    fn relation_is() {
+        let relation = PackageRelation::new("foo (>= 2.0) | bar (<< 1:0)").unwrap();
+        assert!(relation.is(&package("foo", "2.1")));
+        assert!(relation.is(&package("foo", "2.0")));
+        assert!(!relation.is(&package("foo", "2.0~rc1")));
+        assert!(relation.is(&package("bar", "5.0")));
+        assert!(!relation.is(&package("bar", "1:0")));
+        assert!(!relation.is(&package("baz", "2.0")));
+
+        // a later alternative of the same package is checked too
+        let relation = PackageRelation::new("foo (<< 1.0) | foo (>> 2.0)").unwrap();
+        assert!(relation.is(&package("foo", "0.9")));
+        assert!(relation.is(&package("foo", "2.1")));
+        assert!(!relation.is(&package("foo", "1.5")));
+    }
+
+    #[test]
+    fn version_ranges() {
+        let v = |version: &str| PackageVersion::new(version).unwrap();
+
+        let range_1x = range("foo (>= 1.2), foo (<< 2.0)");
+        assert_eq!(range_1x.to_string(), ">= 1.2, << 2.0");
+        assert!(range_1x.contains(&v("1.2")));
+        assert!(range_1x.contains(&v("1.9-1")));
+        assert!(!range_1x.contains(&v("2.0")));
+        assert!(!range_1x.contains(&v("1.2~rc1")));
+        assert!(range_1x.contains(&v("2.0~rc1")));
+
+        // relations of several dependers
+        let range_old = range("foo (<= 1.5)");
+        let both = range_1x.intersection(&range_old);
+        assert_eq!(both.to_string(), ">= 1.2, <= 1.5");
+        assert!(!both.is_empty());
+        assert!(range_1x.intersection(&range("foo (>> 2.0)")).is_empty());
+        assert!(range("foo (>= 2.0), foo (<< 2.0)").is_empty());
+        assert!(range("foo (>> 1:0), foo (<= 1.0)").is_empty());
+        assert_eq!(range("foo (>= 1.0), foo (<= 1.0)").to_string(), "= 1.0");
+        assert_eq!(range("foo (= 1.0), foo (>= 0.9)"), range("foo (= 1.0)"));
+        assert!(range("foo (= 1.0), foo (>> 1.0)").is_empty());
+        assert_eq!(range("foo").to_string(), "any");
+        assert_eq!(VersionRange::none().to_string(), "none");
+
+        let union = range("foo (<< 1.0)").union(&range("foo (>> 2.0)"));
+        assert_eq!(union.to_string(), "<< 1.0 | >> 2.0");
+        assert!(union.contains(&v("0.9")));
+        assert!(!union.contains(&v("1.0")));
+        assert!(union.contains(&v("3")));
+        assert_eq!(
+            union
+                .intersection(&range("foo (>= 0.5), foo (<= 3.0)"))
+                .to_string(),
+            ">= 0.5, << 1.0 | >> 2.0, <= 3.0"
+        );
+
+        // adjacent and overlapping intervals are merged, a single excluded version isn't
+        assert_eq!(
+            range("foo (<< 1.0)").union(&range("foo (>= 1.0)")),
+            VersionRange::any()
+        );
+        assert_eq!(
+            range("foo (<< 1.0)")
+                .union(&range("foo (= 1.0)"))
+                .to_string(),
+            "<= 1.0"
+        );
+        assert_eq!(
+            range("foo (<< 1.0)")
+                .union(&range("foo (>> 1.0)"))
+                .to_string(),
+            "<< 1.0 | >> 1.0"
+        );
+        assert_eq!(
+            range("foo (>= 3.0)")
+                .union(&range("foo (<< 2.0)"))
+                .union(&range("foo (>= 1.5), foo (<< 3.5)"))
+                .to_string(),
+            "any"
+        );
+        assert_eq!(
+            range("foo (= 1.0)").union(&VersionRange::none()),
+            range("foo (= 1.0)")
+        );
+        assert!(VersionRange::none()
+            .intersection(&VersionRange::any())
+            .is_empty());
+
+        let packages = vec![
+            package("foo", "1.0-1"),
+            package("foo", "1.9-2"),
+            package("foo", "2.0-1"),
+            package("foo", "1:0.1-1"),
+        ];
+        let highest =
+            |range: &VersionRange| range.highest(&packages).map(|p| p.version.to_string());
+        assert_eq!(highest(&range_1x).as_deref(), Some("1.9-2"));
+        assert_eq!(highest(&VersionRange::any()).as_deref(), Some("1:0.1-1"));
+        assert_eq!(highest(&range("foo (<< 1:0)")).as_deref(), Some("2.0-1"));
+        assert_eq!(highest(&range("foo (<< 1.0)")), None);
+
+        let relation = PackageRelation::new("foo (>> 1.9-2)").unwrap();
+        assert_eq!(relation.version_range(), range("foo (>> 1.9-2)"));
+        assert_eq!(
+            highest(&relation.version_range()).as_deref(),
+            Some("1:0.1-1")
+        );
+    }
+
+    #[test]
+    fn versioned_provides() {
+        let mut provider = package("librust-serde-dev", "1.0.190-1");
+        provider.provides = Some(
+            PackageRelation::parse("librust-serde-1-dev (= 1.0.190-1), librust-serde+std-dev")
+                .unwrap(),
+        );
+        let mut other = package("librust-serde-dev", "1.0.150-1");
+        other.provides = Some(PackageRelation::parse("librust-serde-1-dev (= 1.0.150-1)").unwrap());
+
+        for (relation, expected) in [
+            ("librust-serde-dev (>= 1.0.160)", true),
+            ("librust-serde-1-dev", true),
+            ("librust-serde-1-dev (>= 1.0.160)", true),
+            ("librust-serde-1-dev (= 1.0.190-1)", true),
+            ("librust-serde-1-dev (<< 1.0.160)", false),
+            ("librust-serde+std-dev", true),
+            // an unversioned Provides never satisfies a versioned relation
+            ("librust-serde+std-dev (>= 1.0)", false),
+            ("librust-serde-2-dev", false),
+            (
+                "librust-serde-2-dev | librust-serde-1-dev (>= 1.0.180)",
+                true,
+            ),
+        ] {
+            let relation = PackageRelation::new(relation).unwrap();
+            assert_eq!(relation.is(&provider), expected, "{}", relation);
+        }
+
+        let packages = [provider, other];
+        let index = ProviderIndex::new(&packages);
+        assert!(index.contains("librust-serde+std-dev"));
+        assert!(!index.contains("librust-serde-dev"));
+        let providers: Vec<String> = index
+            .providers("librust-serde-1-dev")
+            .map(|(p, v)| format!("{} {} {}", p.package, p.version, v.unwrap()))
+            .collect();
+        assert_eq!(
+            providers,
+            [
+                "librust-serde-dev 1.0.190-1 1.0.190-1",
+                "librust-serde-dev 1.0.150-1 1.0.150-1"
+            ]
+        );
+
+        let satisfying = |relation: &str| -> Vec<String> {
+            index
+                .satisfying(&PackageRelation::new(relation).unwrap())
+                .iter()
+                .map(|p| p.version.to_string())
+                .collect()
+        };
+        assert_eq!(
+            satisfying("librust-serde-1-dev"),
+            ["1.0.190-1", "1.0.150-1"]
+        );
+        assert_eq!(
+            satisfying("librust-serde-1-dev (<< 1.0.160)"),
+            ["1.0.150-1"]
+        );
+        assert_eq!(satisfying("librust-serde+std-dev"), ["1.0.190-1"]);
+        assert!(satisfying("librust-serde+std-dev (>= 1)").is_empty());
+        assert!(satisfying("missing").is_empty());
+    }
+
+    #[test]
+    fn multiarch_relations() {
+        let foo = |architecture: &str, multi_arch: Option<MultiArch>| {
+            let mut package = package("foo", "1.0");
+            package.architecture = architecture.to_string();
+            package.multi_arch = multi_arch;
+            package
+        };
+        let native = foo("amd64", None);
+        let foreign = foo("i386", Some(MultiArch::Foreign));
+        let allowed = foo("i386", Some(MultiArch::Allowed));
+        let same = foo("i386", Some(MultiArch::Same));
+        let all = foo("all", None);
+
+        // relation, declaring architecture, package, field, expected
+        for (relation, arch, package, field, expected) in [
+            ("foo", "amd64", &native, RelationField::Depends, true),
+            ("foo", "i386", &native, RelationField::Depends, false),
+            ("foo", "all", &native, RelationField::Depends, true),
+            ("foo", "amd64", &foreign, RelationField::Depends, true),
+            ("foo", "amd64", &allowed, RelationField::Depends, false),
+            ("foo", "i386", &allowed, RelationField::Depends, true),
+            ("foo", "amd64", &same, RelationField::Depends, false),
+            ("foo", "i386", &all, RelationField::Depends, false),
+            ("foo", "amd64", &all, RelationField::Depends, true),
+            ("foo", "amd64", &same, RelationField::Conflicts, true),
+            ("foo", "amd64", &allowed, RelationField::Breaks, true),
+            ("foo", "i386", &native, RelationField::Replaces, true),
+            ("foo:any", "amd64", &allowed, RelationField::Depends, true),
+            ("foo:any", "amd64", &native, RelationField::Depends, false),
+            ("foo:any", "amd64", &foreign, RelationField::Depends, false),
+            ("foo:any", "amd64", &same, RelationField::Conflicts, true),
+            ("foo:native", "i386", &native, RelationField::Depends, true),
+            (
+                "foo:native",
+                "amd64",
+                &foreign,
+                RelationField::Depends,
+                false,
+            ),
+            ("foo:native", "i386", &all, RelationField::Depends, true),
+            ("foo:i386", "amd64", &same, RelationField::Depends, true),
+            ("foo:i386", "amd64", &native, RelationField::Depends, false),
+            (
+                "foo:amd64 (>= 1.0)",
+                "i386",
+                &native,
+                RelationField::Depends,
+                true,
+            ),
+            (
+                "foo:amd64 (>> 1.0)",
+                "i386",
+                &native,
+                RelationField::Depends,
+                false,
+            ),
+            (
+                "bar | foo:any",
+                "amd64",
+                &allowed,
+                RelationField::Depends,
+                true,
+            ),
+        ] {
+            let relation = PackageRelation::new(relation).unwrap();
+            assert_eq!(
+                relation.is_arch(package, arch, "amd64", field),
+                expected,
+                "{} from {} on {}",
+                relation,
+                arch,
+                package.architecture
+            );
+        }
+
+        assert_eq!(same.multi_arch.unwrap().to_string(), "same");
+        let mut stanza = native.to_stanza().unwrap();
+        assert!(!stanza.contains_key("Multi-Arch"));
+        stanza.set_value("Multi-Arch", "foreign");
+        let package = PackageMetadata::new(stanza).unwrap();
+        assert_eq!(package.multi_arch, Some(MultiArch::Foreign));
+    }
 }
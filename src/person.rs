@@ -0,0 +1,420 @@
+use crate::error::RaptoboError;
+use crate::package::PackageMetadata;
+use crate::repository::RepositoryData;
+use crate::source::SourcePackage;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+/// Name and email address of a person, e.g. of the Maintainer field.
+///
+/// The RFC822 forms "Jane Doe <jane@example.org>", "\"Doe, Jane\" <jane@example.org>",
+/// "jane@example.org (Jane Doe)" and "jane@example.org" are supported.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Person {
+    /// display name, None for a plain address
+    pub name: Option<String>,
+    /// email address
+    pub email: String,
+}
+
+impl Person {
+    /// Parse a single person, Debian Policy 5.6.2.
+    ///
+    /// A trailing comma, as found in some indices, is ignored.
+    pub fn new(text: &str) -> Result<Person, RaptoboError> {
+        let invalid = |msg: &str| RaptoboError::new(&format!("[Person] {} in \"{}\"", msg, text));
+
+        let value = text.trim();
+        let value = value.strip_suffix(',').unwrap_or(value).trim_end();
+
+        let (name, email) = if let Some(rest) = value.strip_suffix('>') {
+            let (name, email) = rest
+                .rsplit_once('<')
+                .ok_or_else(|| invalid("missing '<'"))?;
+            (unquote(name.trim()), email.trim())
+        } else if let Some(rest) = value.strip_suffix(')') {
+            let (email, name) = rest.split_once('(').ok_or_else(|| invalid("missing '('"))?;
+            (name.trim().to_string(), email.trim())
+        } else {
+            (String::new(), value)
+        };
+
+        if email.is_empty() {
+            return Err(invalid("empty email address"));
+        }
+        if !email.contains('@') || email.contains(|c: char| c.is_whitespace() || "<>,".contains(c))
+        {
+            return Err(invalid("invalid email address"));
+        }
+
+        Ok(Person {
+            name: if name.is_empty() { None } else { Some(name) },
+            email: email.to_string(),
+        })
+    }
+
+    /// Parse a comma separated list of persons, e.g. the Uploaders field.
+    ///
+    /// Commas in quoted names, comments and addresses don't split the list.
+    pub fn parse_list(text: &str) -> Result<Vec<Person>, RaptoboError> {
+        let mut items = Vec::new();
+        let mut start = 0;
+        let mut quoted = false;
+        let mut escaped = false;
+        let mut angle = false;
+        let mut comment = 0;
+
+        for (i, c) in text.char_indices() {
+            if escaped {
+                escaped = false;
+                continue;
+            }
+            match c {
+                '\\' if quoted => escaped = true,
+                '"' => quoted = !quoted,
+                '<' if !quoted => angle = true,
+                '>' if !quoted => angle = false,
+                '(' if !quoted => comment += 1,
+                ')' if !quoted && comment > 0 => comment -= 1,
+                ',' if !quoted && !angle && comment == 0 => {
+                    items.push(&text[start..i]);
+                    start = i + 1;
+                }
+                _ => {}
+            }
+        }
+        if quoted {
+            return Err(RaptoboError::new(&format!(
+                "[Person] unterminated quote in \"{}\"",
+                text
+            )));
+        }
+        items.push(&text[start..]);
+
+        items
+            .into_iter()
+            .filter(|i| !i.trim().is_empty())
+            .map(Person::new)
+            .collect()
+    }
+}
+
+/// Remove the quotes and escapes of a quoted name.
+fn unquote(name: &str) -> String {
+    match name.strip_prefix('"').and_then(|n| n.strip_suffix('"')) {
+        Some(inner) => {
+            let mut unquoted = String::with_capacity(inner.len());
+            let mut chars = inner.chars();
+            while let Some(c) = chars.next() {
+                match c {
+                    '\\' => unquoted.extend(chars.next()),
+                    c => unquoted.push(c),
+                }
+            }
+            unquoted
+        }
+        None => name.to_string(),
+    }
+}
+
+impl fmt::Display for Person {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.name {
+            Some(name) if name.contains(|c| ",\"<>".contains(c)) => {
+                let escaped = name.replace('\\', "\\\\").replace('"', "\\\"");
+                write!(f, "\"{}\" <{}>", escaped, self.email)
+            }
+            Some(name) => write!(f, "{} <{}>", name, self.email),
+            None => write!(f, "{}", self.email),
+        }
+    }
+}
+
+impl Serialize for Person {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Person {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Person, D::Error> {
+        let person = String::deserialize(deserializer)?;
+        Person::new(&person).map_err(de::Error::custom)
+    }
+}
+
+impl FromStr for Person {
+    type Err = RaptoboError;
+
+    fn from_str(person: &str) -> Result<Person, RaptoboError> {
+        Person::new(person)
+    }
+}
+
+/// Serde helper for person list fields, e.g. Uploaders.
+///
+/// Use as `#[serde(default, with = "persons")]` on an `Option<Vec<Person>>`.
+pub mod persons {
+    use super::Person;
+    use serde::{de, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(
+        value: &Option<Vec<Person>>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match value {
+            Some(persons) if !persons.is_empty() => {
+                let persons: Vec<String> = persons.iter().map(|p| p.to_string()).collect();
+                serializer.serialize_str(&persons.join(", "))
+            }
+            _ => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<Vec<Person>>, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        let persons = Person::parse_list(&text).map_err(de::Error::custom)?;
+        if persons.is_empty() {
+            Ok(None)
+        } else {
+            Ok(Some(persons))
+        }
+    }
+}
+
+/// Index of the packages by the email addresses of their maintainers and uploaders.
+///
+/// The addresses are compared ignoring the case.
+pub struct MaintainerIndex<'a> {
+    packages: HashMap<String, Vec<&'a PackageMetadata>>,
+    sources: HashMap<String, Vec<&'a SourcePackage>>,
+}
+
+impl<'a> MaintainerIndex<'a> {
+    pub fn new<P, S>(packages: P, sources: S) -> MaintainerIndex<'a>
+    where
+        P: IntoIterator<Item = &'a PackageMetadata>,
+        S: IntoIterator<Item = &'a SourcePackage>,
+    {
+        let mut index = MaintainerIndex {
+            packages: HashMap::new(),
+            sources: HashMap::new(),
+        };
+
+        for package in packages {
+            for email in emails(&package.maintainer, &package.uploaders) {
+                index.packages.entry(email).or_default().push(package);
+            }
+        }
+        for source in sources {
+            for email in emails(&source.maintainer, &source.uploaders) {
+                index.sources.entry(email).or_default().push(source);
+            }
+        }
+
+        index
+    }
+
+    /// Index of all binary and source packages loaded from the repository.
+    pub fn from_repository(data: &'a RepositoryData) -> MaintainerIndex<'a> {
+        MaintainerIndex::new(
+            data.packages.values().flatten().map(|p| p.as_ref()),
+            data.sources.values().flatten().map(|s| s.as_ref()),
+        )
+    }
+
+    /// Binary packages maintained or uploaded by the given address.
+    pub fn packages(&self, email: &str) -> &[&'a PackageMetadata] {
+        self.packages
+            .get(&email.to_lowercase())
+            .map(|p| p.as_slice())
+            .unwrap_or_default()
+    }
+
+    /// Source packages maintained or uploaded by the given address.
+    pub fn sources(&self, email: &str) -> &[&'a SourcePackage] {
+        self.sources
+            .get(&email.to_lowercase())
+            .map(|s| s.as_slice())
+            .unwrap_or_default()
+    }
+
+    /// Addresses of all maintainers and uploaders, lowercase and sorted.
+    pub fn emails(&self) -> Vec<&str> {
+        let mut emails: Vec<&str> = self
+            .packages
+            .keys()
+            .chain(self.sources.keys())
+            .map(|e| e.as_str())
+            .collect();
+        emails.sort();
+        emails.dedup();
+        emails
+    }
+}
+
+/// Lowercase addresses of the maintainer and uploaders, without duplicates.
+fn emails(maintainer: &Option<Person>, uploaders: &Option<Vec<Person>>) -> Vec<String> {
+    let mut emails: Vec<String> = maintainer
+        .iter()
+        .chain(uploaders.iter().flatten())
+        .map(|p| p.email.to_lowercase())
+        .collect();
+    emails.sort();
+    emails.dedup();
+    emails
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MaintainerIndex, Person};
+    use crate::error::ErrorKind;
+    use crate::package::PackageMetadata;
+    use crate::source::SourcePackage;
+
+    fn person(name: Option<&str>, email: &str) -> Person {
+        Person {
+            name: name.map(String::from),
+            email: email.to_string(),
+        }
+    }
+
+    #[test]
+    fn parse_person() {
+        assert_eq!(
+            Person::new("Jane Doe <jane@x.org>").unwrap(),
+            person(Some("Jane Doe"), "jane@x.org")
+        );
+        assert_eq!(
+            Person::new("\"Doe, Jane\" <jane@x.org>").unwrap(),
+            person(Some("Doe, Jane"), "jane@x.org")
+        );
+        assert_eq!(
+            Person::new("jane@x.org (Jane Doe)").unwrap(),
+            person(Some("Jane Doe"), "jane@x.org")
+        );
+        assert_eq!(
+            Person::new("jane@x.org").unwrap(),
+            person(None, "jane@x.org")
+        );
+        assert_eq!(
+            Person::new("Laszlo Boszormenyi (GCS) <gcs@debian.org>,").unwrap(),
+            person(Some("Laszlo Boszormenyi (GCS)"), "gcs@debian.org")
+        );
+        assert!(Person::new("Jane Doe").is_err());
+        assert!(Person::new("Jane Doe <>").is_err());
+        assert!(Person::new("Jane Doe jane@x.org>").is_err());
+
+        let quoted = person(Some("Doe, \"JD\" Jane"), "jane@x.org");
+        assert_eq!(quoted.to_string(), "\"Doe, \\\"JD\\\" Jane\" <jane@x.org>");
+        assert_eq!(Person::new(&quoted.to_string()).unwrap(), quoted);
+    }
+
+    #[test]
+    fn parse_person_list() {
+        let persons =
+            Person::parse_list("Jane Doe <jane@x.org>, Bob <b@y>, \"Doe, John\" <john@x.org>,")
+                .unwrap();
+        assert_eq!(
+            persons,
+            [
+                person(Some("Jane Doe"), "jane@x.org"),
+                person(Some("Bob"), "b@y"),
+                person(Some("Doe, John"), "john@x.org"),
+            ]
+        );
+        assert!(Person::parse_list("").unwrap().is_empty());
+        assert!(Person::parse_list("\"Doe, Jane <jane@x.org>").is_err());
+    }
+
+    #[test]
+    fn keep_packages_with_invalid_maintainer() {
+        let content = "\
+Package: hello
+Architecture: amd64
+Version: 2.10-3
+Maintainer: Some Team
+";
+        let package = PackageMetadata::read(content.as_bytes())
+            .next()
+            .unwrap()
+            .unwrap();
+        assert!(package.maintainer.is_none());
+        assert_eq!(package.extra_fields["Maintainer"], [" Some Team"]);
+        assert_eq!(
+            package.field_errors[0].kind(),
+            &ErrorKind::InvalidField("Maintainer".to_string())
+        );
+        assert_eq!(package.to_stanza().unwrap().to_string(), content);
+
+        let content = "\
+Package: hello
+Version: 2.10-3
+Maintainer: Some Team
+Uploaders: Jane Doe <jane@x.org>, Bob
+Directory: pool/main/h/hello
+";
+        let source = SourcePackage::read(content.as_bytes())
+            .next()
+            .unwrap()
+            .unwrap();
+        assert!(source.maintainer.is_none());
+        assert!(source.uploaders.is_none());
+        assert_eq!(
+            source.extra_fields.keys().collect::<Vec<_>>(),
+            ["Maintainer", "Uploaders"]
+        );
+        assert_eq!(source.field_errors.len(), 2);
+        assert_eq!(source.to_stanza().unwrap().to_string(), content);
+    }
+
+    #[test]
+    fn packages_by_maintainer() {
+        let content = "\
+Package: hello
+Version: 2.10-3
+Architecture: amd64
+Maintainer: Santiago Vila <sanvila@debian.org>
+
+Package: bash
+Version: 5.2.15-2
+Architecture: amd64
+Maintainer: Matthias Klose <doko@debian.org>
+Uploaders: Santiago Vila <SanVila@debian.org>, \"Doe, Jane\" <jane@x.org>
+
+Package: curl
+Version: 7.88.1-10
+Architecture: amd64
+Maintainer: Debian Curl Maintainers <team+curl@tracker.debian.org>
+";
+        let packages: Vec<PackageMetadata> = PackageMetadata::read(content.as_bytes())
+            .collect::<Result<_, _>>()
+            .unwrap();
+        let index = MaintainerIndex::new(&packages, []);
+
+        let names = |email: &str| -> Vec<&str> {
+            index
+                .packages(email)
+                .iter()
+                .map(|p| p.package.as_str())
+                .collect()
+        };
+        assert_eq!(names("sanvila@debian.org"), ["hello", "bash"]);
+        assert_eq!(names("JANE@x.org"), ["bash"]);
+        assert!(names("nobody@x.org").is_empty());
+        assert!(index.sources("sanvila@debian.org").is_empty());
+        assert_eq!(
+            index.emails(),
+            [
+                "doko@debian.org",
+                "jane@x.org",
+                "sanvila@debian.org",
+                "team+curl@tracker.debian.org"
+            ]
+        );
+    }
+}
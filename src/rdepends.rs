@@ -0,0 +1,306 @@
+use crate::package::{PackageMetadata, PackageRelation, RelationField};
+use crate::repository::RepositoryData;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+/// Relation of a package to another one, found by a reverse dependency query.
+#[derive(Debug, Clone)]
+pub struct ReverseDependency<'a> {
+    /// Package declaring the relation.
+    pub package: &'a PackageMetadata,
+    pub field: RelationField,
+    /// The relation, with all alternatives.
+    pub relation: &'a PackageRelation,
+    /// Package named by the relation, directly or by its Provides.
+    pub target: &'a PackageMetadata,
+    /// Distance to the queried package, 1 for direct reverse dependencies.
+    pub depth: usize,
+}
+
+impl fmt::Display for ReverseDependency<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} {} {}: {}",
+            self.package.package, self.package.version, self.field, self.relation
+        )
+    }
+}
+
+/// Index of the relations by the named packages, to find the reverse dependencies.
+pub struct ReverseIndex<'a> {
+    /// Declaring package, field and relation by the names of the relation alternatives.
+    relations: HashMap<&'a str, Vec<(&'a PackageMetadata, RelationField, &'a PackageRelation)>>,
+}
+
+impl<'a> ReverseIndex<'a> {
+    pub fn new<I>(packages: I) -> ReverseIndex<'a>
+    where
+        I: IntoIterator<Item = &'a PackageMetadata>,
+    {
+        let mut index = ReverseIndex {
+            relations: HashMap::new(),
+        };
+
+        for package in packages {
+            for field in RelationField::ALL {
+                for relation in field.relations(package) {
+                    let mut names: Vec<&str> = relation
+                        .alternatives()
+                        .map(|r| r.package.as_str())
+                        .collect();
+                    names.sort();
+                    names.dedup();
+                    for name in names {
+                        index
+                            .relations
+                            .entry(name)
+                            .or_default()
+                            .push((package, field, relation));
+                    }
+                }
+            }
+        }
+
+        index
+    }
+
+    /// Index of all packages loaded from the repository.
+    pub fn from_repository(data: &'a RepositoryData) -> ReverseIndex<'a> {
+        ReverseIndex::new(data.packages.values().flatten().map(|p| p.as_ref()))
+    }
+
+    /// Relations of the given fields which are satisfied by the package, directly or
+    /// by one of its Provides.
+    pub fn direct(
+        &self,
+        package: &'a PackageMetadata,
+        fields: &[RelationField],
+    ) -> Vec<ReverseDependency<'a>> {
+        let mut names = vec![package.package.as_str()];
+        names.extend(
+            package
+                .provides
+                .iter()
+                .flatten()
+                .map(|p| p.package.as_str()),
+        );
+        names.sort();
+        names.dedup();
+
+        let mut found: Vec<ReverseDependency<'a>> = Vec::new();
+        for name in names {
+            for (declaring, field, relation) in self.relations.get(name).into_iter().flatten() {
+                if !fields.contains(field)
+                    || found.iter().any(|d| std::ptr::eq(d.relation, *relation))
+                {
+                    continue;
+                }
+                if relation
+                    .alternatives()
+                    .any(|r| r.package == name && r.matches(package))
+                {
+                    found.push(ReverseDependency {
+                        package: declaring,
+                        field: *field,
+                        relation,
+                        target: package,
+                        depth: 1,
+                    });
+                }
+            }
+        }
+
+        found.sort_by(|a, b| {
+            a.package
+                .package
+                .cmp(&b.package.package)
+                .then_with(|| b.package.version.cmp(&a.package.version))
+                .then_with(|| a.field.name().cmp(b.field.name()))
+        });
+        found
+    }
+
+    /// Reverse dependencies of the package, and recursively of the found packages.
+    ///
+    /// The result is ordered by depth, a package is only followed at the lowest
+    /// depth it was found. `max_depth` limits the recursion, e.g. 1 for the direct
+    /// reverse dependencies only, None for no limit.
+    pub fn recursive(
+        &self,
+        package: &'a PackageMetadata,
+        fields: &[RelationField],
+        max_depth: Option<usize>,
+    ) -> Vec<ReverseDependency<'a>> {
+        let mut visited: HashSet<*const PackageMetadata> = HashSet::new();
+        visited.insert(package);
+
+        let mut result = Vec::new();
+        let mut level = vec![package];
+        let mut depth = 1;
+
+        while !level.is_empty() && max_depth.is_none_or(|max| depth <= max) {
+            let mut found = Vec::new();
+            for target in level {
+                for mut dependency in self.direct(target, fields) {
+                    if !visited.contains(&(dependency.package as *const PackageMetadata)) {
+                        dependency.depth = depth;
+                        found.push(dependency);
+                    }
+                }
+            }
+
+            level = Vec::new();
+            for dependency in &found {
+                if visited.insert(dependency.package) {
+                    level.push(dependency.package);
+                }
+            }
+
+            result.extend(found);
+            depth += 1;
+        }
+
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ReverseIndex;
+    use crate::package::{PackageMetadata, RelationField};
+
+    const PACKAGES: &str = "\
+Package: libc6
+Version: 2.36-9
+Architecture: amd64
+Breaks: old-app (<< 2.0)
+
+Package: libssl3
+Version: 3.0.11-1
+Architecture: amd64
+Depends: libc6 (>= 2.34)
+
+Package: curl
+Version: 7.88.1-10
+Architecture: amd64
+Depends: libc6 (>= 2.17), libssl3 (>= 3.0.0)
+Recommends: ca-certificates
+
+Package: old-app
+Version: 1.0
+Architecture: amd64
+Depends: libc6 (<< 2.30) | libc6.1
+
+Package: postfix
+Version: 3.7.9-0
+Architecture: amd64
+Depends: libssl3
+Provides: mail-transport-agent, default-mta (= 3.7.9-0)
+
+Package: mailutils
+Version: 1:3.15-4
+Architecture: amd64
+Depends: default-mta | mail-transport-agent
+Suggests: mailutils-doc
+
+Package: reportbug
+Version: 12.0.0
+Architecture: all
+Suggests: mailutils, curl
+Conflicts: default-mta (<< 3.0)
+";
+
+    fn packages() -> Vec<PackageMetadata> {
+        PackageMetadata::read(PACKAGES.as_bytes())
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap()
+    }
+
+    fn find<'a>(packages: &'a [PackageMetadata], name: &str) -> &'a PackageMetadata {
+        packages.iter().find(|p| p.package == name).unwrap()
+    }
+
+    #[test]
+    fn direct_reverse_dependencies() {
+        let packages = packages();
+        let index = ReverseIndex::new(&packages);
+
+        let libc6 = find(&packages, "libc6");
+        let rdepends: Vec<String> = index
+            .direct(libc6, &RelationField::ALL)
+            .iter()
+            .map(|d| d.to_string())
+            .collect();
+        // old-app requires an older version of libc6
+        assert_eq!(
+            rdepends,
+            [
+                "curl 7.88.1-10 Depends: libc6 (>= 2.17)",
+                "libssl3 3.0.11-1 Depends: libc6 (>= 2.34)",
+            ]
+        );
+
+        // relations on provided virtual packages, once per relation
+        let postfix = find(&packages, "postfix");
+        let rdepends: Vec<String> = index
+            .direct(postfix, &RelationField::ALL)
+            .iter()
+            .map(|d| d.to_string())
+            .collect();
+        assert_eq!(
+            rdepends,
+            ["mailutils 1:3.15-4 Depends: default-mta | mail-transport-agent"]
+        );
+
+        let mailutils = find(&packages, "mailutils");
+        assert_eq!(
+            index.direct(mailutils, &RelationField::ALL)[0].to_string(),
+            "reportbug 12.0.0 Suggests: mailutils"
+        );
+        assert!(index
+            .direct(mailutils, &[RelationField::Depends])
+            .is_empty());
+    }
+
+    #[test]
+    fn recursive_reverse_dependencies() {
+        let packages = packages();
+        let index = ReverseIndex::new(&packages);
+        let libc6 = find(&packages, "libc6");
+
+        let rdepends: Vec<(usize, &str, &str)> = index
+            .recursive(libc6, &RelationField::ALL, None)
+            .iter()
+            .map(|d| (d.depth, d.package.package.as_str(), d.field.name()))
+            .collect();
+        assert_eq!(
+            rdepends,
+            [
+                (1, "curl", "Depends"),
+                (1, "libssl3", "Depends"),
+                (2, "reportbug", "Suggests"),
+                (2, "postfix", "Depends"),
+                (3, "mailutils", "Depends"),
+            ]
+        );
+
+        let depends = [RelationField::PreDepends, RelationField::Depends];
+        let rdepends: Vec<String> = index
+            .recursive(libc6, &depends, Some(2))
+            .iter()
+            .map(|d| format!("{} {} -> {}", d.depth, d.package.package, d.target.package))
+            .collect();
+        assert_eq!(
+            rdepends,
+            [
+                "1 curl -> libc6",
+                "1 libssl3 -> libc6",
+                "2 postfix -> libssl3"
+            ]
+        );
+
+        assert_eq!(index.recursive(libc6, &depends, Some(1)).len(), 2);
+        assert!(index.recursive(libc6, &depends, Some(0)).is_empty());
+    }
+}
@@ -1,13 +1,20 @@
 use std::collections::HashMap;
+use std::io::{self, BufReader, Read};
 
-use crate::error::RaptoboError;
-use crate::package::PackageMetadata;
-use crate::utils::{
-    download, parse_metadata, stanza_files, stanza_list, stanza_opt_value, stanza_text,
-    stanza_value, File,
-};
+use crate::deb822::{from_stanza, to_stanza, Stanza, RELEASE_FIELD_ORDER};
+use crate::error::{ErrorKind, RaptoboError};
+use crate::index::{Compression, IndexKind, IndexTarget};
+use crate::package::{PackageMetadata, ProviderIndex, VersionRange};
+use crate::signature::{CleartextMessage, Keyring};
+use crate::source::SourcePackage;
+use crate::transport::{transport_for, Transport};
+use crate::utils::{decompress, decompress_reader, parse_metadata, split_lines, File};
 use chrono::NaiveDateTime;
 use clap::Parser;
+use md5::Md5;
+use serde::{Deserialize, Serialize};
+use sha1::Sha1;
+use sha2::{Digest, Sha256, Sha512};
 
 #[derive(Debug, Parser)]
 pub struct RepositorySpec {
@@ -26,67 +33,144 @@ pub struct RepositorySpec {
     /// Components to use
     #[arg(short, long)]
     pub components: Option<Vec<String>>,
+    /// Keyring files used to verify the repository signature, like the Signed-By option of APT
+    #[arg(short = 'k', long = "signed-by")]
+    pub signed_by: Option<Vec<String>>,
+    /// Preference order of the index compressions, best first
+    #[arg(long, value_enum, value_delimiter = ',')]
+    pub compression: Option<Vec<Compression>>,
 }
 
 impl RepositorySpec {
-    pub fn to_repo(self) -> Repository {
-        Repository {
+    /// Create the repository, with the transport selected by the URI scheme.
+    pub fn to_repo(self) -> Result<Repository, RaptoboError> {
+        Ok(Repository {
+            transport: transport_for(&self.uri)?,
             spec: self,
             metadata: None,
             data: RepositoryData::new(),
-        }
+        })
     }
 }
 
-#[derive(Debug)]
+/// Release file the repository metadata was loaded from.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum ReleaseFile {
+    /// Inline signed InRelease file.
+    #[default]
+    InRelease,
+    /// Release file, signed by a detached Release.gpg signature.
+    Release,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
 pub struct RepositoryMetadata {
+    /// Release file used to load this metadata.
+    #[serde(skip)]
+    pub release_file: ReleaseFile,
+    /// Detached signature, only available for Release files.
+    #[serde(skip)]
+    pub signature: Option<Vec<u8>>,
+    /// Architectures, empty for flat repositories.
+    #[serde(rename = "Architectures", default)]
     pub architectures: Vec<String>,
+    /// Components, empty for flat repositories.
+    #[serde(rename = "Components", default)]
     pub components: Vec<String>,
-    pub description: String,
+    #[serde(rename = "Description")]
+    pub description: Option<String>,
+    #[serde(rename = "Origin")]
     pub origin: Option<String>,
+    #[serde(rename = "Label")]
     pub label: Option<String>,
-    pub version: String,
+    #[serde(rename = "Version")]
+    pub version: Option<String>,
+    #[serde(rename = "Suite")]
     pub suite: Option<String>,
-    pub codename: String,
+    #[serde(rename = "Codename")]
+    pub codename: Option<String>,
+    #[serde(rename = "Date", with = "release_date")]
     pub date: NaiveDateTime,
+    #[serde(rename = "MD5Sum", default)]
     pub md5sum: Vec<File>,
+    #[serde(rename = "SHA1", default)]
     pub sha1: Vec<File>,
+    #[serde(rename = "SHA256", default)]
     pub sha256: Vec<File>,
+    #[serde(rename = "SHA512", default)]
+    pub sha512: Vec<File>,
 }
 
 impl RepositoryMetadata {
-    pub fn new(content: Vec<String>) -> Result<RepositoryMetadata, RaptoboError> {
+    pub fn new(
+        content: Vec<String>,
+        release_file: ReleaseFile,
+    ) -> Result<RepositoryMetadata, RaptoboError> {
         let data = parse_metadata(content)?;
 
-        // search right stanza
+        // search right stanza, flat repositories may have no Codename
         let stanza = data
             .into_iter()
-            .find(|d| d.contains_key("Codename"))
+            .find(|d| {
+                ["MD5Sum", "SHA1", "SHA256", "SHA512"]
+                    .iter()
+                    .any(|k| d.contains_key(k))
+            })
             .ok_or(RaptoboError::new(
-                "[RepositoryMetadata] Codename not found!",
+                "[RepositoryMetadata] no file hash list found!",
             ))?;
 
-        let date = stanza_value("Date", &stanza)?;
-        let date = NaiveDateTime::parse_from_str(&date, "%a, %d %b %Y %H:%M:%S %Z")
-            .map_err(|e| RaptoboError::new(&e.to_string()))?;
-
-        let metadata = RepositoryMetadata {
-            architectures: stanza_list("Architectures", &stanza)?,
-            components: stanza_list("Components", &stanza)?,
-            description: stanza_text("Description", &stanza)?,
-            origin: stanza_opt_value("Origin", &stanza),
-            label: stanza_opt_value("Label", &stanza),
-            version: stanza_value("Version", &stanza)?,
-            suite: stanza_opt_value("Suite", &stanza),
-            codename: stanza_value("Codename", &stanza)?,
-            date,
-            md5sum: stanza_files("MD5Sum", &stanza)?,
-            sha1: stanza_files("SHA1", &stanza)?,
-            sha256: stanza_files("SHA256", &stanza)?,
-        };
+        let mut metadata: RepositoryMetadata = from_stanza(&stanza)?;
+        metadata.release_file = release_file;
 
         Ok(metadata)
     }
+
+    /// Convert the metadata back to a Release file stanza.
+    pub fn to_stanza(&self) -> Result<Stanza, RaptoboError> {
+        let mut stanza = to_stanza(self)?;
+
+        // hash lists are written with aligned sizes, like apt-ftparchive does
+        for (name, files) in [
+            ("MD5Sum", &self.md5sum),
+            ("SHA1", &self.sha1),
+            ("SHA256", &self.sha256),
+            ("SHA512", &self.sha512),
+        ] {
+            if !files.is_empty() {
+                stanza.set_lines(
+                    name,
+                    files
+                        .iter()
+                        .map(|f| format!("{} {:>16} {}", f.hash, f.size, f.path)),
+                );
+            }
+        }
+
+        stanza.sort_fields(RELEASE_FIELD_ORDER);
+        Ok(stanza)
+    }
+}
+
+/// Serde helper for the Date of Release files, e.g. "Sat, 13 Jan 2024 12:00:00 UTC".
+mod release_date {
+    use chrono::NaiveDateTime;
+    use serde::{de, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(
+        date: &NaiveDateTime,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(&date.format("%a, %d %b %Y %H:%M:%S UTC"))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<NaiveDateTime, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        NaiveDateTime::parse_from_str(&text, "%a, %d %b %Y %H:%M:%S %Z")
+            .map_err(|e| de::Error::custom(format!("invalid date {}: {}", text, e)))
+    }
 }
 
 #[derive(Debug)]
@@ -94,6 +178,82 @@ pub enum FileHash {
     MD5(String), SHA1(String), SHA256(String), SHA512(String)
 }
 
+impl FileHash {
+    /// Name of the hash, as used in the Release file.
+    pub fn name(&self) -> &str {
+        match self {
+            FileHash::MD5(_) => "MD5Sum",
+            FileHash::SHA1(_) => "SHA1",
+            FileHash::SHA256(_) => "SHA256",
+            FileHash::SHA512(_) => "SHA512",
+        }
+    }
+
+    /// The expected hash value.
+    pub fn value(&self) -> &str {
+        match self {
+            FileHash::MD5(v) | FileHash::SHA1(v) | FileHash::SHA256(v) | FileHash::SHA512(v) => v,
+        }
+    }
+
+    /// Strength of the hash algorithm, a higher value is stronger.
+    pub fn strength(&self) -> u8 {
+        match self {
+            FileHash::MD5(_) => 1,
+            FileHash::SHA1(_) => 2,
+            FileHash::SHA256(_) => 3,
+            FileHash::SHA512(_) => 4,
+        }
+    }
+
+    /// Calculate the hash of the given data with the same algorithm, as lower case hex.
+    pub fn digest(&self, data: &[u8]) -> String {
+        let mut hasher = self.hasher();
+        hasher.update(data);
+        hasher.finish()
+    }
+
+    /// Hasher of the same algorithm, to calculate the hash incrementally.
+    pub fn hasher(&self) -> Hasher {
+        match self {
+            FileHash::MD5(_) => Hasher::MD5(Md5::new()),
+            FileHash::SHA1(_) => Hasher::SHA1(Sha1::new()),
+            FileHash::SHA256(_) => Hasher::SHA256(Sha256::new()),
+            FileHash::SHA512(_) => Hasher::SHA512(Sha512::new()),
+        }
+    }
+}
+
+/// Incremental hash calculation of one of the Release file hash algorithms.
+pub enum Hasher {
+    MD5(Md5),
+    SHA1(Sha1),
+    SHA256(Sha256),
+    SHA512(Sha512),
+}
+
+impl Hasher {
+    pub fn update(&mut self, data: &[u8]) {
+        match self {
+            Hasher::MD5(h) => h.update(data),
+            Hasher::SHA1(h) => h.update(data),
+            Hasher::SHA256(h) => h.update(data),
+            Hasher::SHA512(h) => h.update(data),
+        }
+    }
+
+    /// The hash of all data, as lower case hex.
+    pub fn finish(self) -> String {
+        let digest = match self {
+            Hasher::MD5(h) => h.finalize().to_vec(),
+            Hasher::SHA1(h) => h.finalize().to_vec(),
+            Hasher::SHA256(h) => h.finalize().to_vec(),
+            Hasher::SHA512(h) => h.finalize().to_vec(),
+        };
+        digest.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+}
+
 #[derive(Debug)]
 pub struct FileMetadata {
     pub path: String,
@@ -101,21 +261,153 @@ pub struct FileMetadata {
     pub hashes: Vec<FileHash>,
 }
 
+impl FileMetadata {
+    /// The strongest hash available for this file.
+    pub fn strongest_hash(&self) -> Option<&FileHash> {
+        self.hashes.iter().max_by_key(|h| h.strength())
+    }
+
+    /// Verify size and strongest hash of the given file content.
+    pub fn verify(&self, data: &[u8]) -> Result<(), RaptoboError> {
+        self.check_size(data.len() as u64)?;
+        let hash = self.verified_hash()?;
+        self.check_hash(hash, &hash.digest(data))
+    }
+
+    /// Wrap the reader of the file content with a reader verifying size and
+    /// strongest hash incrementally; a mismatch is a read error at the end of the data.
+    pub fn verifying_reader<R: Read>(
+        &self,
+        reader: R,
+    ) -> Result<VerifyingReader<'_, R>, RaptoboError> {
+        Ok(VerifyingReader {
+            meta: self,
+            reader,
+            size: 0,
+            hasher: Some(self.verified_hash()?.hasher()),
+        })
+    }
+
+    fn verified_hash(&self) -> Result<&FileHash, RaptoboError> {
+        self.strongest_hash().ok_or(RaptoboError::with_kind(
+            ErrorKind::HashMismatch,
+            &format!("[FileMetadata::verify] {}: no hash available!", self.path),
+        ))
+    }
+
+    fn check_size(&self, size: u64) -> Result<(), RaptoboError> {
+        if size != self.size {
+            return Err(RaptoboError::with_kind(
+                ErrorKind::SizeMismatch,
+                &format!(
+                    "[FileMetadata::verify] {}: size mismatch, expected {}, got {}",
+                    self.path, self.size, size
+                ),
+            ));
+        }
+        Ok(())
+    }
+
+    fn check_hash(&self, hash: &FileHash, digest: &str) -> Result<(), RaptoboError> {
+        if !digest.eq_ignore_ascii_case(hash.value()) {
+            return Err(RaptoboError::with_kind(
+                ErrorKind::HashMismatch,
+                &format!(
+                    "[FileMetadata::verify] {}: {} mismatch, expected {}, got {}",
+                    self.path,
+                    hash.name(),
+                    hash.value(),
+                    digest
+                ),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Reader verifying size and hash of the data read, see `FileMetadata::verifying_reader`.
+pub struct VerifyingReader<'a, R> {
+    meta: &'a FileMetadata,
+    reader: R,
+    size: u64,
+    /// None after the verification at the end of the data.
+    hasher: Option<Hasher>,
+}
+
+impl<R: Read> Read for VerifyingReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.reader.read(buf)?;
+        self.size += n as u64;
+        if self.size > self.meta.size {
+            return Err(io::Error::other(
+                self.meta.check_size(self.size).unwrap_err(),
+            ));
+        }
+
+        match self.hasher.take() {
+            Some(mut hasher) if n > 0 => {
+                hasher.update(&buf[..n]);
+                self.hasher = Some(hasher);
+            }
+            Some(hasher) if !buf.is_empty() => {
+                let digest = hasher.finish();
+                self.meta
+                    .check_size(self.size)
+                    .and_then(|_| self.meta.check_hash(self.meta.verified_hash()?, &digest))
+                    .map_err(io::Error::other)?;
+            }
+            hasher => self.hasher = hasher,
+        }
+
+        Ok(n)
+    }
+}
+
+impl<R: Read> VerifyingReader<'_, R> {
+    /// Read the rest of the data and verify size and hash of the complete file.
+    ///
+    /// Decompressors stop at the end of their stream and may never read to the
+    /// end of the file, i.e. never trigger the verification at the end of the data.
+    pub fn finish(mut self) -> Result<(), RaptoboError> {
+        io::copy(&mut self, &mut io::sink())
+            .map_err(|e| RaptoboError::from_io(e, "[VerifyingReader::finish]"))?;
+        Ok(())
+    }
+}
+
 #[derive(Debug)]
 pub struct RepositoryData {
     pub files: HashMap<String, FileMetadata>,
+    /// Index files of the Release file, sorted by path.
+    pub indices: Vec<IndexTarget>,
+    /// Paths of the Packages indices, by component and architecture.
     pub package_indices: HashMap<String, HashMap<String, Vec<String>>>,
-    pub packages: HashMap<String, Vec<Box<PackageMetadata>>>
+    pub packages: HashMap<String, Vec<Box<PackageMetadata>>>,
+    /// Source packages by name, loaded for source repositories.
+    pub sources: HashMap<String, Vec<Box<SourcePackage>>>,
 }
 
 impl RepositoryData {
     pub fn new() -> RepositoryData {
         RepositoryData {
             files: HashMap::new(),
+            indices: Vec::new(),
             package_indices: HashMap::new(),
             packages: HashMap::new(),
+            sources: HashMap::new(),
         }
     }
+
+    /// Index of the virtual packages provided by the loaded packages.
+    pub fn provider_index(&self) -> ProviderIndex<'_> {
+        ProviderIndex::new(self.packages.values().flatten().map(|p| p.as_ref()))
+    }
+
+    /// The package with the given name and the highest version contained in the range.
+    pub fn highest_version(&self, package: &str, range: &VersionRange) -> Option<&PackageMetadata> {
+        let packages = self.packages.get(package)?;
+        range.highest(packages.iter().map(|p| p.as_ref()))
+    }
 }
 
 #[derive(Debug)]
@@ -123,6 +415,8 @@ pub struct Repository {
     pub spec: RepositorySpec,
     pub metadata: Option<RepositoryMetadata>,
     pub data: RepositoryData,
+    /// Transport used to fetch the repository files, selected by the URI scheme.
+    pub transport: Box<dyn Transport>,
 }
 
 impl Repository {
@@ -132,95 +426,968 @@ impl Repository {
         components: Option<Vec<&str>>,
         source: bool,
         flat: bool,
-    ) -> Repository {
+    ) -> Result<Repository, RaptoboError> {
         let c: Option<Vec<String>> = match components {
             None => None,
             Some(comps) => Some(comps.into_iter().map(|comp| comp.to_string()).collect()),
         };
 
-        Repository {
+        Ok(Repository {
             spec: RepositorySpec {
                 flat,
                 source,
                 uri: uri.to_string(),
                 distribution: distribution.to_string(),
                 components: c,
+                signed_by: None,
+                compression: None,
             },
             metadata: None,
             data: RepositoryData::new(),
-        }
+            transport: transport_for(uri)?,
+        })
+    }
+
+    /// Use the given transport instead of the one selected by the URI scheme.
+    pub fn with_transport(mut self, transport: Box<dyn Transport>) -> Repository {
+        self.transport = transport;
+        self
     }
 
-    fn inrelease_url(&self) -> String {
+    fn metadata_url(&self, file: &str) -> String {
         if self.spec.flat {
-            format!("{}/{}/InRelease", self.spec.uri, self.spec.distribution)
+            format!("{}/{}/{}", self.spec.uri, self.spec.distribution, file)
         } else {
             format!(
-                "{}/dists/{}/InRelease",
-                self.spec.uri, self.spec.distribution
+                "{}/dists/{}/{}",
+                self.spec.uri, self.spec.distribution, file
             )
         }
     }
 
+    /// Load the keyrings given as signed_by.
+    fn keyring(&self) -> Result<Option<Keyring>, RaptoboError> {
+        let paths = match &self.spec.signed_by {
+            Some(paths) if !paths.is_empty() => paths,
+            _ => return Ok(None),
+        };
+
+        let mut keyring = Keyring::new();
+        for path in paths {
+            keyring.extend(Keyring::load(path)?);
+        }
+
+        Ok(Some(keyring))
+    }
+
+    /// Load the repository metadata.
+    ///
+    /// The InRelease file is preferred, if it is not available the Release file
+    /// and its detached signature Release.gpg are used. If keyrings are given as
+    /// signed_by, the signature is verified and a missing or invalid signature
+    /// is an error.
     pub fn load_metadata(&mut self) -> Result<(), RaptoboError> {
-        let url = self.inrelease_url();
+        let keyring = self.keyring()?;
+        if keyring.is_none() {
+            log::warn!("[load_metadata] no keyring given, signature is not verified!");
+        }
+
+        let url = self.metadata_url("InRelease");
 
         log::debug!("[load_metadata] url: {}", url);
 
-        let content = download(&url)?;
+        let metadata = match self.transport.fetch(&url) {
+            Ok(content) => {
+                let content =
+                    String::from_utf8(content).map_err(|e| RaptoboError::new(&e.to_string()))?;
+                let message = CleartextMessage::parse(&content)?;
+
+                if let Some(keyring) = &keyring {
+                    keyring.verify_cleartext(&message)?;
+                }
+
+                RepositoryMetadata::new(split_lines(&message.text), ReleaseFile::InRelease)?
+            }
+            Err(e) => {
+                log::info!("[load_metadata] InRelease not available: {}", e);
+
+                let url = self.metadata_url("Release");
+                log::debug!("[load_metadata] url: {}", url);
+                let content = self.transport.fetch(&url)?;
+
+                let url = self.metadata_url("Release.gpg");
+                log::debug!("[load_metadata] url: {}", url);
+                let signature = match self.transport.fetch(&url) {
+                    Ok(signature) => Some(signature),
+                    Err(e) => {
+                        log::warn!("[load_metadata] Release.gpg not available: {}", e);
+                        None
+                    }
+                };
+
+                if let Some(keyring) = &keyring {
+                    match &signature {
+                        Some(signature) => keyring.verify_detached(&content, signature)?,
+                        None => {
+                            return Err(RaptoboError::with_kind(
+                                ErrorKind::SignatureMissing,
+                                &format!("[load_metadata] {} not available!", url),
+                            ))
+                        }
+                    }
+                }
+
+                let content =
+                    String::from_utf8(content).map_err(|e| RaptoboError::new(&e.to_string()))?;
+                let mut metadata =
+                    RepositoryMetadata::new(split_lines(&content), ReleaseFile::Release)?;
+                metadata.signature = signature;
+
+                metadata
+            }
+        };
 
-        let metadata = RepositoryMetadata::new(content)?;
         self.metadata = Some(metadata);
 
         Ok(())
     }
 
+    /// Download a file listed in the Release file, and verify its size and hash.
+    ///
+    /// The path is relative to the Release file, e.g. "main/binary-amd64/Packages.xz".
+    pub fn download_file(&self, path: &str) -> Result<Vec<u8>, RaptoboError> {
+        let meta = self.data.files.get(path).ok_or(RaptoboError::new(&format!(
+            "[Repository::download_file] {} is not listed in the Release file!",
+            path
+        )))?;
+
+        let url = self.metadata_url(path);
+        log::debug!("[Repository::download_file] url: {}", url);
+
+        let content = self.transport.fetch(&url)?;
+        meta.verify(&content)?;
+
+        Ok(content)
+    }
+
+    /// Open a file listed in the Release file as a stream, verifying its size and
+    /// hash while reading.
+    ///
+    /// The path is relative to the Release file, e.g. "main/binary-amd64/Packages.xz".
+    pub fn open_file(
+        &self,
+        path: &str,
+    ) -> Result<VerifyingReader<'_, Box<dyn Read + Send>>, RaptoboError> {
+        let meta = self.data.files.get(path).ok_or(RaptoboError::new(&format!(
+            "[Repository::open_file] {} is not listed in the Release file!",
+            path
+        )))?;
+
+        let url = self.metadata_url(path);
+        log::debug!("[Repository::open_file] url: {}", url);
+
+        meta.verifying_reader(self.transport.open(&url)?)
+    }
+
     pub fn process_files(&mut self) -> Result<(), RaptoboError> {
         let meta = match &self.metadata {
             Some(m) => m,
-            None => return Err(RaptoboError::new("[Repository::process_files] no metadata!")),
+            None => {
+                return Err(RaptoboError::new(
+                    "[Repository::process_files] no metadata!",
+                ))
+            }
         };
-        
-        for file in &meta.md5sum {
-            let hash = FileHash::MD5(file.hash.to_string());
-            let meta = FileMetadata {
-                path: file.path.to_string(),
-                size: file.size,
-                hashes: vec![hash],
-            };
-            self.data.files.insert(file.path.to_string(), meta);
+
+        let lists = [
+            (&meta.md5sum, FileHash::MD5 as fn(String) -> FileHash),
+            (&meta.sha1, FileHash::SHA1),
+            (&meta.sha256, FileHash::SHA256),
+            (&meta.sha512, FileHash::SHA512),
+        ];
+
+        for (files, hash) in lists {
+            for file in files {
+                let meta = self
+                    .data
+                    .files
+                    .entry(file.path.to_string())
+                    .or_insert(FileMetadata {
+                        path: file.path.to_string(),
+                        size: file.size,
+                        hashes: Vec::new(),
+                    });
+                if meta.size != file.size {
+                    return Err(RaptoboError::new(&format!(
+                        "[Repository::process_files] {}: inconsistent sizes {} and {}",
+                        file.path, meta.size, file.size
+                    )));
+                }
+                meta.hashes.push(hash(file.hash.to_string()));
+            }
+        }
+
+        let mut indices: Vec<IndexTarget> = self
+            .data
+            .files
+            .keys()
+            .map(|path| IndexTarget::parse(path, &meta.components))
+            .collect();
+        indices.sort_by(|a, b| a.path.cmp(&b.path));
+
+        for c_name in &meta.components {
+            let component = self
+                .data
+                .package_indices
+                .entry(c_name.to_string())
+                .or_default();
+            for a_name in &meta.architectures {
+                let arch = component.entry(a_name.to_string()).or_default();
+                for index in &indices {
+                    if index.kind == IndexKind::Packages
+                        && !index.installer
+                        && index.component.as_ref() == Some(c_name)
+                        && index.architecture.as_ref() == Some(a_name)
+                    {
+                        arch.push(index.path.to_string());
+                    }
+                }
+            }
         }
 
-        for file in &meta.sha1 {
-            let hash = FileHash::SHA1(file.hash.to_string());
-            self.data.files.get_mut(&file.path).unwrap().hashes.push(hash);
+        self.data.indices = indices;
+
+        Ok(())
+    }
+
+    /// Components selected by the spec, or all components of the repository.
+    pub fn selected_components(&self) -> Result<Vec<String>, RaptoboError> {
+        match (&self.spec.components, &self.metadata) {
+            (Some(components), _) => Ok(components.clone()),
+            (None, Some(meta)) => Ok(meta.components.clone()),
+            (None, None) => Err(RaptoboError::new(
+                "[Repository::selected_components] no metadata!",
+            )),
         }
+    }
+
+    /// Find all index files of the given kind, component and architecture.
+    ///
+    /// Indices of the debian-installer are excluded.
+    pub fn find_indices(
+        &self,
+        kind: IndexKind,
+        component: Option<&str>,
+        architecture: Option<&str>,
+    ) -> Vec<&IndexTarget> {
+        self.data
+            .indices
+            .iter()
+            .filter(|i| {
+                i.kind == kind
+                    && !i.installer
+                    && i.component.as_deref() == component
+                    && i.architecture.as_deref() == architecture
+            })
+            .collect()
+    }
 
-        for file in &meta.sha256 {
-            let hash = FileHash::SHA256(file.hash.to_string());
-            self.data.files.get_mut(&file.path).unwrap().hashes.push(hash);
+    /// Compression preference order of the spec, or the default order.
+    pub fn compression_preference(&self) -> Vec<Compression> {
+        match &self.spec.compression {
+            Some(compression) => compression.clone(),
+            None => Compression::default_preference(),
         }
+    }
 
-        for c_name in &meta.components {
-            if !self.data.package_indices.contains_key(c_name) {
-                self.data.package_indices.insert(c_name.to_string(), HashMap::new());
+    /// Index files of the given kind, component and architecture, ordered by the
+    /// compression preference. Compressions not in the preference list are skipped.
+    pub fn index_candidates(
+        &self,
+        kind: IndexKind,
+        component: Option<&str>,
+        architecture: Option<&str>,
+    ) -> Vec<&IndexTarget> {
+        let indices = self.find_indices(kind, component, architecture);
+        self.compression_preference()
+            .into_iter()
+            .filter_map(|c| indices.iter().find(|i| i.compression == c).copied())
+            .collect()
+    }
+
+    /// Choose the best index file of the given kind, component and architecture.
+    pub fn best_index(
+        &self,
+        kind: IndexKind,
+        component: Option<&str>,
+        architecture: Option<&str>,
+    ) -> Option<&IndexTarget> {
+        self.index_candidates(kind, component, architecture)
+            .into_iter()
+            .next()
+    }
+
+    /// Download and decompress the best index file of the given kind, component
+    /// and architecture.
+    ///
+    /// If the download, verification or decompression fails, the next compression
+    /// variant is tried.
+    pub fn fetch_index(
+        &self,
+        kind: IndexKind,
+        component: Option<&str>,
+        architecture: Option<&str>,
+    ) -> Result<(&IndexTarget, Vec<u8>), RaptoboError> {
+        let mut error = None;
+
+        for index in self.index_candidates(kind.clone(), component, architecture) {
+            log::debug!("[Repository::fetch_index] loading {}", index.path);
+
+            match self
+                .download_file(&index.path)
+                .and_then(|content| decompress(&content))
+            {
+                Ok(content) => return Ok((index, content)),
+                Err(e) => {
+                    log::warn!("[Repository::fetch_index] {}: {}", index.path, e);
+                    error = Some(e);
+                }
             }
-            let component = self.data.package_indices.get_mut(c_name).unwrap();
-            for a_name in &meta.architectures {
-                if !component.contains_key(a_name) {
-                    component.insert(a_name.to_string(), Vec::new());
+        }
+
+        Err(error.unwrap_or(RaptoboError::new(&format!(
+            "[Repository::fetch_index] no {:?} index for {:?} {:?}!",
+            kind, component, architecture
+        ))))
+    }
+
+    /// Stream the best index file of the given kind, component and architecture
+    /// through the decompressor into the parser.
+    ///
+    /// The index is verified while reading, and the rest of the file after the
+    /// end of the compressed stream is verified after parsing. If the download, verification,
+    /// decompression or parsing fails, the next compression variant is tried.
+    pub fn read_index<T, F>(
+        &self,
+        kind: IndexKind,
+        component: Option<&str>,
+        architecture: Option<&str>,
+        parse: F,
+    ) -> Result<(&IndexTarget, Vec<T>), RaptoboError>
+    where
+        F: Fn(Box<dyn io::BufRead + '_>) -> Result<Vec<T>, RaptoboError>,
+    {
+        let mut error = None;
+
+        for index in self.index_candidates(kind.clone(), component, architecture) {
+            log::debug!("[Repository::read_index] loading {}", index.path);
+
+            match self.open_file(&index.path).and_then(|mut verifier| {
+                let reader = decompress_reader(BufReader::new(&mut verifier))?;
+                let items = parse(reader).map_err(|e| {
+                    RaptoboError::with_kind(e.kind().clone(), &format!("{}: {}", index.path, e))
+                })?;
+                verifier.finish()?;
+                Ok(items)
+            }) {
+                Ok(items) => return Ok((index, items)),
+                Err(e) => {
+                    log::warn!("[Repository::read_index] {}: {}", index.path, e);
+                    error = Some(e);
                 }
-                let arch = component.get_mut(a_name).unwrap();
-                for (path, _meta) in &self.data.files {
-                    if path.starts_with(c_name) {
-                        if path.contains(&format!("binary-{}", a_name)) {
-                            arch.push(path.to_string());
-                        }
+            }
+        }
+
+        Err(error.unwrap_or(RaptoboError::new(&format!(
+            "[Repository::read_index] no {:?} index for {:?} {:?}!",
+            kind, component, architecture
+        ))))
+    }
+
+    /// Load the packages of all selected components and architectures,
+    /// or the source packages of all selected components for source repositories.
+    ///
+    /// Requires loaded metadata and processed files.
+    pub fn load_packages(&mut self) -> Result<(), RaptoboError> {
+        if self.spec.source {
+            return self.load_sources();
+        }
+
+        let architectures = match &self.metadata {
+            Some(m) => m.architectures.clone(),
+            None => {
+                return Err(RaptoboError::new(
+                    "[Repository::load_packages] no metadata!",
+                ))
+            }
+        };
+
+        // flat repositories have a single Packages index without component and architecture
+        let mut targets = Vec::new();
+        if self.spec.flat {
+            targets.push((None, None));
+        } else {
+            for component in self.selected_components()? {
+                for architecture in &architectures {
+                    let available = self
+                        .data
+                        .package_indices
+                        .get(&component)
+                        .and_then(|a| a.get(architecture))
+                        .is_some_and(|paths| !paths.is_empty());
+                    if available {
+                        targets.push((Some(component.to_string()), Some(architecture.to_string())));
+                    } else {
+                        log::warn!(
+                            "[Repository::load_packages] no Packages index for {} {}",
+                            component,
+                            architecture
+                        );
                     }
                 }
             }
         }
 
+        if targets.is_empty() {
+            return Err(RaptoboError::new(
+                "[Repository::load_packages] no Packages index for the selected components and architectures!",
+            ));
+        }
+
+        for (component, architecture) in targets {
+            let (index, packages) = self.read_index(
+                IndexKind::Packages,
+                component.as_deref(),
+                architecture.as_deref(),
+                |reader| PackageMetadata::read(reader).collect(),
+            )?;
+            let path = index.path.to_string();
+            let mut count = 0;
+            for mut package in packages {
+                package.component = component.clone();
+                package.index_architecture = architecture.clone();
+                self.data
+                    .packages
+                    .entry(package.package.to_string())
+                    .or_default()
+                    .push(Box::new(package));
+                count += 1;
+            }
+            log::info!("[Repository::load_packages] {} packages in {}", count, path);
+        }
+
+        Ok(())
+    }
+
+    /// Load the source packages of all selected components from the Sources indices.
+    ///
+    /// Requires loaded metadata and processed files.
+    pub fn load_sources(&mut self) -> Result<(), RaptoboError> {
+        if self.metadata.is_none() {
+            return Err(RaptoboError::new("[Repository::load_sources] no metadata!"));
+        }
+
+        for component in self.selected_components()? {
+            if self
+                .best_index(IndexKind::Sources, Some(&component), None)
+                .is_none()
+            {
+                log::warn!(
+                    "[Repository::load_sources] no Sources index for {}",
+                    component
+                );
+                continue;
+            }
+
+            let (index, packages) =
+                self.read_index(IndexKind::Sources, Some(&component), None, |reader| {
+                    SourcePackage::read(reader).collect()
+                })?;
+            let path = index.path.to_string();
+            let mut count = 0;
+            for mut package in packages {
+                package.component = Some(component.to_string());
+                self.data
+                    .sources
+                    .entry(package.package.to_string())
+                    .or_default()
+                    .push(Box::new(package));
+                count += 1;
+            }
+            log::info!(
+                "[Repository::load_sources] {} source packages in {}",
+                count,
+                path
+            );
+        }
+
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{FileHash, FileMetadata, ReleaseFile, Repository, RepositoryMetadata};
+    use crate::error::{ErrorKind, RaptoboError};
+    use crate::index::{Compression, IndexKind};
+    use crate::package::{PackageMetadata, PackageRelation, VersionRange};
+    use crate::resolver::Resolver;
+    use crate::transport::MemoryTransport;
+    use crate::utils::split_lines;
+    use std::fs;
+    use std::io::Read;
+
+    fn fixture_repo(key: &str) -> Repository {
+        let data = format!("{}/tests/data", env!("CARGO_MANIFEST_DIR"));
+        let mut repo = Repository::new(
+            &format!("file://{}/repo", data),
+            "stable",
+            None,
+            false,
+            false,
+        )
+        .unwrap();
+        repo.spec.signed_by = Some(vec![format!("{}/keys/{}", data, key)]);
+        repo
+    }
+
+    #[test]
+    fn metadata_url_layouts() {
+        let repo =
+            Repository::new("http://example.org/debian", "bookworm", None, false, false).unwrap();
+        assert_eq!(
+            repo.metadata_url("InRelease"),
+            "http://example.org/debian/dists/bookworm/InRelease"
+        );
+        assert_eq!(
+            repo.metadata_url("Release.gpg"),
+            "http://example.org/debian/dists/bookworm/Release.gpg"
+        );
+
+        let repo = Repository::new("http://example.org/flat", "repo", None, false, true).unwrap();
+        assert_eq!(
+            repo.metadata_url("InRelease"),
+            "http://example.org/flat/repo/InRelease"
+        );
+        assert_eq!(
+            repo.metadata_url("Release"),
+            "http://example.org/flat/repo/Release"
+        );
+    }
+
+    #[test]
+    fn write_release() {
+        let path = format!(
+            "{}/tests/data/repo/dists/stable/Release",
+            env!("CARGO_MANIFEST_DIR")
+        );
+        let content = fs::read_to_string(path).unwrap();
+        let metadata =
+            RepositoryMetadata::new(split_lines(&content), ReleaseFile::Release).unwrap();
+
+        assert_eq!(metadata.to_stanza().unwrap().to_string(), content);
+    }
+
+    #[test]
+    fn load_signed_metadata() {
+        let mut repo = fixture_repo("rsa.gpg");
+        repo.load_metadata().unwrap();
+
+        let metadata = repo.metadata.unwrap();
+        assert_eq!(metadata.release_file, ReleaseFile::InRelease);
+        assert_eq!(metadata.codename.as_deref(), Some("stable"));
+        assert_eq!(metadata.components, vec!["main"]);
+        assert_eq!(metadata.sha256.len(), 5);
+    }
+
+    #[test]
+    fn load_flat_metadata() {
+        let data = format!("{}/tests/data", env!("CARGO_MANIFEST_DIR"));
+        let mut repo =
+            Repository::new(&format!("file://{}", data), "flat", None, false, true).unwrap();
+        repo.load_metadata().unwrap();
+
+        let metadata = repo.metadata.as_ref().unwrap();
+        assert_eq!(metadata.release_file, ReleaseFile::Release);
+        assert_eq!(metadata.codename, None);
+        assert_eq!(metadata.version, None);
+        assert!(metadata.components.is_empty());
+        assert!(metadata.architectures.is_empty());
+        assert_eq!(metadata.sha512.len(), 2);
+
+        let content = fs::read_to_string(format!("{}/flat/Release", data)).unwrap();
+        assert_eq!(metadata.to_stanza().unwrap().to_string(), content);
+    }
+
+    #[test]
+    fn load_flat_packages() {
+        let data = format!("{}/tests/data/flat", env!("CARGO_MANIFEST_DIR"));
+        let mut transport = MemoryTransport::new();
+        for file in ["Release", "Packages", "Packages.gz"] {
+            let content = fs::read(format!("{}/{}", data, file)).unwrap();
+            transport.insert(&format!("http://mem.test/flat/{}", file), content);
+        }
+
+        let mut repo = Repository::new("http://mem.test", "flat", None, false, true)
+            .unwrap()
+            .with_transport(Box::new(transport));
+        repo.load_metadata().unwrap();
+        repo.process_files().unwrap();
+        repo.load_packages().unwrap();
+
+        assert_eq!(repo.data.packages.len(), 3);
+        let acl = &repo.data.packages["acl"][0];
+        assert_eq!(acl.component, None);
+        assert_eq!(acl.index_architecture, None);
+    }
+
+    #[test]
+    fn load_packages_without_index() {
+        let mut repo = fixture_repo("rsa.gpg");
+        repo.spec.components = Some(vec![String::from("contrib")]);
+        repo.load_metadata().unwrap();
+        repo.process_files().unwrap();
+        assert!(repo.load_packages().is_err());
+        assert!(repo.data.packages.is_empty());
+    }
+
+    #[test]
+    fn load_metadata_unknown_key() {
+        let mut repo = fixture_repo("other.asc");
+        let err = repo.load_metadata().unwrap_err();
+        assert_eq!(err.kind(), &ErrorKind::UnknownKey);
+    }
+
+    #[test]
+    fn download_verified_index() {
+        let mut repo = fixture_repo("rsa.gpg");
+        repo.load_metadata().unwrap();
+        repo.process_files().unwrap();
+
+        let meta = &repo.data.files["main/binary-amd64/Packages.xz"];
+        assert_eq!(meta.hashes.len(), 3);
+        assert_eq!(meta.strongest_hash().unwrap().name(), "SHA256");
+
+        let content = repo.download_file("main/binary-amd64/Packages.xz").unwrap();
+        assert_eq!(content.len() as u64, meta.size);
+
+        assert!(repo
+            .download_file("main/binary-amd64/Packages.bz2")
+            .is_err());
+    }
+
+    #[test]
+    fn verify_file_metadata() {
+        let data = b"Package: test\n";
+        let meta = FileMetadata {
+            path: String::from("main/binary-amd64/Packages"),
+            size: data.len() as u64,
+            hashes: vec![
+                FileHash::MD5(String::from("00000000000000000000000000000000")),
+                FileHash::SHA256(String::from(
+                    "1c9a3a8e0d4ba4b5fba0b2ac2c1bfd8b9ff05b5fd8f19b76ac9fc2fc70d1f4a1",
+                )),
+            ],
+        };
+
+        let err = meta.verify(data).unwrap_err();
+        assert_eq!(err.kind(), &ErrorKind::HashMismatch);
+        let msg = err.to_string();
+        assert!(msg.contains("main/binary-amd64/Packages"));
+        assert!(msg.contains("SHA256"));
+        assert!(msg.contains("1c9a3a8e0d4ba4b5fba0b2ac2c1bfd8b9ff05b5fd8f19b76ac9fc2fc70d1f4a1"));
+        assert!(msg.contains(&FileHash::SHA256(String::new()).digest(data)));
+
+        let err = meta.verify(b"Package: other\n").unwrap_err();
+        assert_eq!(err.kind(), &ErrorKind::SizeMismatch);
+
+        let meta = FileMetadata {
+            path: String::from("main/binary-amd64/Packages"),
+            size: data.len() as u64,
+            hashes: vec![FileHash::SHA512(
+                FileHash::SHA512(String::new()).digest(data),
+            )],
+        };
+        meta.verify(data).unwrap();
+
+        let mut content = Vec::new();
+        meta.verifying_reader(&data[..])
+            .unwrap()
+            .read_to_end(&mut content)
+            .unwrap();
+        assert_eq!(content, data);
+
+        let mut reader = meta.verifying_reader(&b"Package: tests\n"[..]).unwrap();
+        let err = reader.read_to_end(&mut Vec::new()).unwrap_err();
+        let err = RaptoboError::from_io(err, "[test]");
+        assert_eq!(err.kind(), &ErrorKind::SizeMismatch);
+
+        let mut reader = meta.verifying_reader(&b"Package: tesT\n"[..]).unwrap();
+        let err = reader.read_to_end(&mut Vec::new()).unwrap_err();
+        let err = RaptoboError::from_io(err, "[test]");
+        assert_eq!(err.kind(), &ErrorKind::HashMismatch);
+    }
+
+    #[test]
+    fn load_release_with_detached_signature() {
+        let data = format!("{}/tests/data", env!("CARGO_MANIFEST_DIR"));
+        let mut transport = MemoryTransport::new();
+        for file in ["Release", "Release.gpg"] {
+            let content = fs::read(format!("{}/repo/dists/stable/{}", data, file)).unwrap();
+            transport.insert(&format!("http://mem.test/repo/{}", file), content);
+        }
+
+        let mut repo = Repository::new("http://mem.test", "repo", None, false, true)
+            .unwrap()
+            .with_transport(Box::new(transport.clone()));
+        repo.spec.signed_by = Some(vec![format!("{}/keys/ed25519.asc", data)]);
+        repo.load_metadata().unwrap();
+
+        let metadata = repo.metadata.unwrap();
+        assert_eq!(metadata.release_file, ReleaseFile::Release);
+        assert!(metadata.signature.is_some());
+        assert_eq!(metadata.codename.as_deref(), Some("stable"));
+
+        // a missing signature is an error, if a keyring is given
+        transport.files.remove("http://mem.test/repo/Release.gpg");
+        let mut repo = Repository::new("http://mem.test", "repo", None, false, true)
+            .unwrap()
+            .with_transport(Box::new(transport));
+        repo.spec.signed_by = Some(vec![format!("{}/keys/ed25519.asc", data)]);
+        let err = repo.load_metadata().unwrap_err();
+        assert_eq!(err.kind(), &ErrorKind::SignatureMissing);
+    }
+
+    #[test]
+    fn load_packages() {
+        let mut repo = fixture_repo("rsa.gpg");
+        repo.load_metadata().unwrap();
+        repo.process_files().unwrap();
+
+        assert_eq!(repo.data.indices.len(), 5);
+        assert_eq!(repo.data.package_indices["main"]["amd64"].len(), 3);
+        assert_eq!(
+            repo.find_indices(IndexKind::Packages, Some("main"), Some("amd64"))
+                .len(),
+            3
+        );
+
+        let index = repo
+            .best_index(IndexKind::Packages, Some("main"), Some("amd64"))
+            .unwrap();
+        assert_eq!(index.path, "main/binary-amd64/Packages.xz");
+        assert_eq!(index.compression, Compression::Xz);
+        assert!(repo
+            .best_index(IndexKind::Packages, Some("main"), Some("i386"))
+            .is_none());
+        assert_eq!(
+            repo.best_index(IndexKind::Sources, Some("main"), None)
+                .unwrap()
+                .path,
+            "main/source/Sources.xz"
+        );
+
+        repo.load_packages().unwrap();
+
+        assert_eq!(repo.data.packages.len(), 8);
+        let acl = &repo.data.packages["acl"];
+        assert_eq!(acl.len(), 1);
+        assert_eq!(acl[0].version, *"2.3.1-1");
+        assert_eq!(acl[0].component.as_deref(), Some("main"));
+        assert_eq!(acl[0].index_architecture.as_deref(), Some("amd64"));
+        assert!(repo.data.sources.is_empty());
+
+        let range = PackageRelation::new("acl (>= 2.3)")
+            .unwrap()
+            .version_range();
+        let highest = repo.data.highest_version("acl", &range).unwrap();
+        assert_eq!(highest.version, *"2.3.1-1");
+        let range = range.intersection(
+            &PackageRelation::new("acl (<< 2.3.1)")
+                .unwrap()
+                .version_range(),
+        );
+        assert!(repo.data.highest_version("acl", &range).is_none());
+        assert!(repo
+            .data
+            .highest_version("missing", &VersionRange::any())
+            .is_none());
+
+        let requested = PackageRelation::parse("acl").unwrap();
+        let error = Resolver::from_repository(&repo.data)
+            .resolve(&requested)
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "[Resolver] acl -> acl 2.3.1-1 Depends: libacl1 (= 2.3.1-1): missing package"
+        );
+    }
+
+    #[test]
+    fn load_sources() {
+        let mut repo = fixture_repo("rsa.gpg");
+        repo.spec.source = true;
+        repo.load_metadata().unwrap();
+        repo.process_files().unwrap();
+        repo.load_packages().unwrap();
+
+        assert!(repo.data.packages.is_empty());
+        assert_eq!(repo.data.sources.len(), 2);
+        let acl = &repo.data.sources["acl"];
+        assert_eq!(acl.len(), 1);
+        assert_eq!(acl[0].version, *"2.3.1-1");
+        assert_eq!(acl[0].directory, "pool/main/a/acl");
+        assert_eq!(acl[0].component.as_deref(), Some("main"));
+    }
+
+    #[test]
+    fn fetch_index_fallback() {
+        let data = format!(
+            "{}/tests/data/repo/dists/stable",
+            env!("CARGO_MANIFEST_DIR")
+        );
+        let mut transport = MemoryTransport::new();
+        for file in [
+            "Release",
+            "main/binary-amd64/Packages",
+            "main/binary-amd64/Packages.gz",
+        ] {
+            let content = fs::read(format!("{}/{}", data, file)).unwrap();
+            transport.insert(&format!("http://mem.test/dists/stable/{}", file), content);
+        }
+        // corrupted download of the preferred index
+        transport.insert(
+            "http://mem.test/dists/stable/main/binary-amd64/Packages.xz",
+            b"broken".to_vec(),
+        );
+
+        let mut repo = Repository::new("http://mem.test", "stable", None, false, false)
+            .unwrap()
+            .with_transport(Box::new(transport));
+        repo.load_metadata().unwrap();
+        repo.process_files().unwrap();
+
+        let (index, content) = repo
+            .fetch_index(IndexKind::Packages, Some("main"), Some("amd64"))
+            .unwrap();
+        assert_eq!(index.compression, Compression::Gzip);
+        assert!(content.starts_with(b"Package: accountsservice"));
+
+        repo.spec.compression = Some(vec![Compression::None, Compression::Xz]);
+        let candidates: Vec<&str> = repo
+            .index_candidates(IndexKind::Packages, Some("main"), Some("amd64"))
+            .iter()
+            .map(|i| i.path.as_str())
+            .collect();
+        assert_eq!(
+            candidates,
+            vec![
+                "main/binary-amd64/Packages",
+                "main/binary-amd64/Packages.xz"
+            ]
+        );
+
+        repo.spec.compression = Some(vec![Compression::Xz]);
+        let err = repo
+            .fetch_index(IndexKind::Packages, Some("main"), Some("amd64"))
+            .unwrap_err();
+        assert_eq!(err.kind(), &ErrorKind::SizeMismatch);
+    }
+
+    #[test]
+    fn read_index_fallback() {
+        let data = format!(
+            "{}/tests/data/repo/dists/stable",
+            env!("CARGO_MANIFEST_DIR")
+        );
+        let mut transport = MemoryTransport::new();
+        for file in ["Release", "main/binary-amd64/Packages.gz"] {
+            let content = fs::read(format!("{}/{}", data, file)).unwrap();
+            transport.insert(&format!("http://mem.test/dists/stable/{}", file), content);
+        }
+        // corrupted download of the same size, detected at the end of the stream
+        let mut content = fs::read(format!("{}/main/binary-amd64/Packages", data)).unwrap();
+        let last = content.len() - 2;
+        content[last] ^= 0x20;
+        transport.insert(
+            "http://mem.test/dists/stable/main/binary-amd64/Packages",
+            content,
+        );
+
+        let mut repo = Repository::new("http://mem.test", "stable", None, false, false)
+            .unwrap()
+            .with_transport(Box::new(transport));
+        repo.load_metadata().unwrap();
+        repo.process_files().unwrap();
+        repo.spec.compression = Some(vec![Compression::None, Compression::Gzip]);
+
+        let (index, packages) = repo
+            .read_index(IndexKind::Packages, Some("main"), Some("amd64"), |reader| {
+                PackageMetadata::read(reader).collect()
+            })
+            .unwrap();
+        assert_eq!(index.compression, Compression::Gzip);
+        assert_eq!(packages[0].package, "accountsservice");
+
+        repo.spec.compression = Some(vec![Compression::None]);
+        let err = repo
+            .read_index(IndexKind::Packages, Some("main"), Some("amd64"), |reader| {
+                PackageMetadata::read(reader).collect()
+            })
+            .unwrap_err();
+        assert_eq!(err.kind(), &ErrorKind::HashMismatch);
+        assert!(err.to_string().contains("main/binary-amd64/Packages"));
+    }
+
+    #[test]
+    fn read_index_verifies_data_after_stream() {
+        let data = format!(
+            "{}/tests/data/repo/dists/stable",
+            env!("CARGO_MANIFEST_DIR")
+        );
+        let mut transport = MemoryTransport::new();
+        let release = fs::read(format!("{}/Release", data)).unwrap();
+        transport.insert("http://mem.test/dists/stable/Release", release);
+        // valid xz stream padded with junk, the decompressor never reads the junk
+        let mut content = fs::read(format!("{}/main/binary-amd64/Packages.xz", data)).unwrap();
+        content.extend(b"garbage");
+        let size = content.len() as u64;
+        transport.insert(
+            "http://mem.test/dists/stable/main/binary-amd64/Packages.xz",
+            content.clone(),
+        );
+
+        let mut repo = Repository::new("http://mem.test", "stable", None, false, false)
+            .unwrap()
+            .with_transport(Box::new(transport));
+        repo.load_metadata().unwrap();
+        repo.process_files().unwrap();
+        repo.spec.compression = Some(vec![Compression::Xz]);
+        let read = |repo: &Repository| {
+            repo.read_index(IndexKind::Packages, Some("main"), Some("amd64"), |reader| {
+                PackageMetadata::read(reader).collect()
+            })
+            .map(|(_, packages)| packages.len())
+        };
+
+        let meta = repo
+            .data
+            .files
+            .get_mut("main/binary-amd64/Packages.xz")
+            .unwrap();
+        meta.size = size;
+        meta.hashes = vec![FileHash::SHA256("0".repeat(64))];
+        let err = read(&repo).unwrap_err();
+        assert_eq!(err.kind(), &ErrorKind::HashMismatch);
+
+        let meta = repo
+            .data
+            .files
+            .get_mut("main/binary-amd64/Packages.xz")
+            .unwrap();
+        meta.hashes = vec![FileHash::SHA256(
+            FileHash::SHA256(String::new()).digest(&content),
+        )];
+        assert_eq!(read(&repo).unwrap(), 8);
+    }
+}
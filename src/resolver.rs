@@ -0,0 +1,828 @@
+use crate::error::{ErrorKind, RaptoboError};
+use crate::package::{
+    MultiArch, PackageMetadata, PackageRelation, PackageVersion, PackageVersionRelation,
+    ProviderIndex, RelationField,
+};
+use crate::repository::RepositoryData;
+use std::collections::HashMap;
+use std::fmt;
+
+/// Maximum number of package selections of one resolution, to limit backtracking.
+const MAX_STEPS: usize = 100_000;
+
+/// Dependency resolver, computes the packages needed to install the requested packages.
+///
+/// The install set contains one version of each package, satisfies all Depends and
+/// Pre-Depends of the selected packages and contains no package which is named in
+/// Conflicts or Breaks of another selected package. Alternatives and candidate versions
+/// are tried in order, highest version first, and the search backtracks on failures.
+///
+/// The architectures are ignored unless a native architecture is set, see
+/// `with_architecture`.
+pub struct Resolver<'a> {
+    /// Packages by name, highest version first.
+    packages: HashMap<&'a str, Vec<&'a PackageMetadata>>,
+    providers: ProviderIndex<'a>,
+    /// Native architecture, for Multi-Arch aware resolution.
+    native: Option<String>,
+}
+
+impl<'a> Resolver<'a> {
+    pub fn new<I>(packages: I) -> Resolver<'a>
+    where
+        I: IntoIterator<Item = &'a PackageMetadata>,
+    {
+        let mut by_name: HashMap<&str, Vec<&PackageMetadata>> = HashMap::new();
+        let mut unique = Vec::new();
+        for package in packages {
+            let versions = by_name.entry(package.package.as_str()).or_default();
+            // Architecture all packages are listed in the indices of all architectures
+            if !versions
+                .iter()
+                .any(|p| p.version == package.version && p.architecture == package.architecture)
+            {
+                versions.push(package);
+                unique.push(package);
+            }
+        }
+        for packages in by_name.values_mut() {
+            packages.sort_by(|a, b| b.version.cmp(&a.version));
+        }
+
+        Resolver {
+            packages: by_name,
+            providers: ProviderIndex::new(unique),
+            native: None,
+        }
+    }
+
+    /// Resolve for the given native architecture, following the multiarch specification.
+    ///
+    /// Packages of other architectures only satisfy relations according to their
+    /// Multi-Arch value, see `PackageRelation::matches_arch`, and several architectures
+    /// of a package can only be installed if it is Multi-Arch same, in the same version.
+    pub fn with_architecture(mut self, native: &str) -> Resolver<'a> {
+        self.native = Some(native.to_string());
+        self
+    }
+
+    /// Resolver for all packages loaded from the repository.
+    pub fn from_repository(data: &'a RepositoryData) -> Resolver<'a> {
+        Resolver::new(data.packages.values().flatten().map(|p| p.as_ref()))
+    }
+
+    /// Compute an install set for the requested relations, e.g. parsed from "foo, bar (>= 1.0)".
+    ///
+    /// The packages are returned in the order of selection, the requested packages first.
+    pub fn resolve(
+        &self,
+        requested: &'a [PackageRelation],
+    ) -> Result<Vec<&'a PackageMetadata>, Unsatisfiable> {
+        let goals = requested
+            .iter()
+            .map(|relation| Goal {
+                relation,
+                field: "",
+                parent: None,
+            })
+            .collect();
+        self.search(goals, None)
+    }
+
+    /// Compute an install set for exactly the given package, e.g. to check if it is installable.
+    ///
+    /// Unlike requesting "foo (= 1.0)", a package providing "foo (= 1.0)" is not
+    /// an install set of foo 1.0.
+    pub fn resolve_package(
+        &self,
+        package: &'a PackageMetadata,
+    ) -> Result<Vec<&'a PackageMetadata>, Unsatisfiable> {
+        // the requested relation is only used in explanations
+        let requested = PackageRelation {
+            package: package.package.clone(),
+            arch_qualifier: None,
+            relation: PackageVersionRelation::EQ,
+            version: Some(package.version.clone()),
+            architectures: Vec::new(),
+            profiles: Vec::new(),
+            alternative: None,
+        };
+        let goals = vec![Goal {
+            relation: &requested,
+            field: "",
+            parent: None,
+        }];
+        self.search(goals, Some(package))
+    }
+
+    /// Search an install set for the goals, with the seed package selected for the first goal.
+    fn search<'g>(
+        &self,
+        goals: Vec<Goal<'g>>,
+        seed: Option<&'a PackageMetadata>,
+    ) -> Result<Vec<&'a PackageMetadata>, Unsatisfiable>
+    where
+        'a: 'g,
+    {
+        let mut search = Search {
+            resolver: self,
+            selected: Vec::new(),
+            by_name: HashMap::new(),
+            goals,
+            steps: 0,
+            failure: None,
+        };
+
+        let next = match seed {
+            Some(package) => {
+                search.select(package, 0);
+                1
+            }
+            None => 0,
+        };
+
+        if search.satisfy(next) {
+            Ok(search.selected.into_iter().map(|s| s.package).collect())
+        } else {
+            Err(search
+                .failure
+                .expect("[Resolver::resolve] failed search without failure"))
+        }
+    }
+
+    /// Packages which may satisfy the relation by name and version, ignoring its
+    /// alternatives and the architectures.
+    ///
+    /// The real packages come first, then the providers of a virtual package.
+    fn candidates(&self, relation: &PackageRelation) -> Vec<&'a PackageMetadata> {
+        let mut candidates: Vec<&'a PackageMetadata> = self
+            .packages
+            .get(relation.package.as_str())
+            .into_iter()
+            .flatten()
+            .filter(|p| relation.version_range().contains(&p.version))
+            .copied()
+            .collect();
+
+        candidates.extend(self.providers.satisfying(relation));
+        candidates
+    }
+
+    /// Check if any package or provider of the relation exists, ignoring the version.
+    fn is_known(&self, relation: &PackageRelation) -> bool {
+        self.packages.contains_key(relation.package.as_str())
+            || self.providers.contains(&relation.package)
+    }
+
+    /// Check if the package satisfies a single relation of the field of the declaring
+    /// package, None for requested relations which are treated like native ones.
+    fn satisfies(
+        &self,
+        relation: &PackageRelation,
+        package: &PackageMetadata,
+        declaring: Option<&PackageMetadata>,
+        field: RelationField,
+    ) -> bool {
+        match &self.native {
+            None => relation.matches(package),
+            Some(native) => {
+                let arch = declaring.map_or(native.as_str(), |d| d.architecture.as_str());
+                relation.matches_arch(package, arch, native, field)
+            }
+        }
+    }
+
+    /// Check if one of the packages declares a conflict with or breaks the other.
+    ///
+    /// A package may conflict with a virtual package it provides itself.
+    fn conflicts(&self, a: &PackageMetadata, b: &PackageMetadata) -> bool {
+        let declares = |p: &PackageMetadata, other: &PackageMetadata| {
+            [RelationField::Conflicts, RelationField::Breaks]
+                .into_iter()
+                .any(|field| {
+                    field
+                        .relations(p)
+                        .iter()
+                        .flat_map(|r| r.alternatives())
+                        .any(|r| self.satisfies(r, other, Some(p), field))
+                })
+        };
+        a.package != b.package && (declares(a, b) || declares(b, a))
+    }
+
+    /// Check if two packages of the same name can be installed at the same time.
+    fn coinstallable(&self, a: &PackageMetadata, b: &PackageMetadata) -> bool {
+        self.native.is_some()
+            && a.multi_arch == Some(MultiArch::Same)
+            && b.multi_arch == Some(MultiArch::Same)
+            && a.architecture != b.architecture
+            && a.version == b.version
+    }
+}
+
+/// Relation which must be satisfied by the install set.
+struct Goal<'g> {
+    relation: &'g PackageRelation,
+    /// Field declaring the relation, empty for requested relations.
+    field: &'static str,
+    /// Index of the selected package declaring the relation, None if requested.
+    parent: Option<usize>,
+}
+
+struct Selection<'a> {
+    package: &'a PackageMetadata,
+    /// Index of the goal which selected the package.
+    goal: usize,
+}
+
+/// State of a backtracking search for an install set.
+struct Search<'r, 'a, 'g> {
+    resolver: &'r Resolver<'a>,
+    selected: Vec<Selection<'a>>,
+    /// Indices of the selected packages by name.
+    by_name: HashMap<&'a str, Vec<usize>>,
+    goals: Vec<Goal<'g>>,
+    steps: usize,
+    /// First failure of the search, used as explanation.
+    failure: Option<Unsatisfiable>,
+}
+
+impl<'r, 'a: 'g, 'g> Search<'r, 'a, 'g> {
+    /// Satisfy the goals starting at the given index, backtracking over candidates.
+    fn satisfy(&mut self, mut next: usize) -> bool {
+        // skip the goals which are already satisfied by the selection
+        while next < self.goals.len() && self.is_satisfied(&self.goals[next]) {
+            next += 1;
+        }
+        if next == self.goals.len() {
+            return true;
+        }
+
+        let relation = self.goals[next].relation;
+        let declaring = self.declaring(&self.goals[next]);
+        let mut blocker: Option<&'a PackageMetadata> = None;
+        let (mut found, mut found_arch) = (false, false);
+
+        for alternative in relation.alternatives() {
+            for candidate in self.resolver.candidates(alternative) {
+                found = true;
+                if !self.resolver.satisfies(
+                    alternative,
+                    candidate,
+                    declaring,
+                    RelationField::Depends,
+                ) {
+                    continue;
+                }
+                found_arch = true;
+
+                if let Some(b) = self.blocker(candidate) {
+                    blocker.get_or_insert(b);
+                    continue;
+                }
+
+                self.steps += 1;
+                if self.steps > MAX_STEPS {
+                    self.failure = Some(self.unsatisfiable(next, Problem::Aborted));
+                    return false;
+                }
+
+                let (selected, goals) = (self.selected.len(), self.goals.len());
+                self.select(candidate, next);
+                if self.satisfy(next + 1) {
+                    return true;
+                }
+                if self.steps > MAX_STEPS {
+                    return false;
+                }
+                self.unselect(selected, goals);
+            }
+        }
+
+        if self.failure.is_none() {
+            let problem = match blocker {
+                Some(b) => Problem::Conflict {
+                    package: b.package.clone(),
+                    version: b.version.clone(),
+                },
+                None if found_arch => return false,
+                None if found => Problem::Architecture,
+                None if relation.alternatives().any(|r| self.resolver.is_known(r)) => {
+                    Problem::Version
+                }
+                None => Problem::Missing,
+            };
+            self.failure = Some(self.unsatisfiable(next, problem));
+        }
+
+        false
+    }
+
+    /// Package declaring the relation of the goal, None if requested.
+    fn declaring(&self, goal: &Goal<'g>) -> Option<&'a PackageMetadata> {
+        goal.parent.map(|i| self.selected[i].package)
+    }
+
+    fn is_satisfied(&self, goal: &Goal<'g>) -> bool {
+        let declaring = self.declaring(goal);
+        goal.relation.alternatives().any(|r| {
+            self.selected.iter().any(|s| {
+                self.resolver
+                    .satisfies(r, s.package, declaring, RelationField::Depends)
+            })
+        })
+    }
+
+    /// Selected package which prevents the selection of the candidate, if any.
+    fn blocker(&self, candidate: &'a PackageMetadata) -> Option<&'a PackageMetadata> {
+        let same_name = self
+            .by_name
+            .get(candidate.package.as_str())
+            .into_iter()
+            .flatten()
+            .map(|&i| self.selected[i].package)
+            .find(|s| !self.resolver.coinstallable(candidate, s));
+        same_name.or_else(|| {
+            self.selected
+                .iter()
+                .map(|s| s.package)
+                .find(|s| self.resolver.conflicts(candidate, s))
+        })
+    }
+
+    fn select(&mut self, package: &'a PackageMetadata, goal: usize) {
+        let parent = self.selected.len();
+        self.by_name
+            .entry(package.package.as_str())
+            .or_default()
+            .push(parent);
+        self.selected.push(Selection { package, goal });
+
+        for field in [RelationField::PreDepends, RelationField::Depends] {
+            for relation in field.relations(package) {
+                self.goals.push(Goal {
+                    relation,
+                    field: field.name(),
+                    parent: Some(parent),
+                });
+            }
+        }
+    }
+
+    /// Undo the selections and goals after the given lengths.
+    fn unselect(&mut self, selected: usize, goals: usize) {
+        for s in self.selected.drain(selected..).rev() {
+            let name = s.package.package.as_str();
+            if let Some(indices) = self.by_name.get_mut(name) {
+                indices.pop();
+                if indices.is_empty() {
+                    self.by_name.remove(name);
+                }
+            }
+        }
+        self.goals.truncate(goals);
+    }
+
+    /// Explanation for the failing goal, with the chain of relations leading to it.
+    fn unsatisfiable(&self, goal: usize, problem: Problem) -> Unsatisfiable {
+        let mut chain = Vec::new();
+        let mut goal = &self.goals[goal];
+
+        while let Some(parent) = goal.parent {
+            let selection = &self.selected[parent];
+            chain.push(Dependency {
+                package: selection.package.package.clone(),
+                version: selection.package.version.clone(),
+                field: goal.field.to_string(),
+                relation: goal.relation.clone(),
+            });
+            goal = &self.goals[selection.goal];
+        }
+        chain.reverse();
+
+        Unsatisfiable {
+            requested: Box::new(goal.relation.clone()),
+            chain,
+            problem,
+        }
+    }
+}
+
+/// Reason why a relation can't be satisfied.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Problem {
+    /// There is no package or provider of the named packages.
+    Missing,
+    /// Packages of the named packages exist, but no version satisfies the relation.
+    Version,
+    /// Matching versions exist, but not for an architecture satisfying the relation.
+    Architecture,
+    /// All candidates conflict with a package of the install set, e.g. the first blocking one.
+    Conflict {
+        package: String,
+        version: PackageVersion,
+    },
+    /// The search was aborted after too many steps.
+    Aborted,
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Problem::Missing => write!(f, "missing package"),
+            Problem::Version => write!(f, "no matching version"),
+            Problem::Architecture => write!(f, "no matching architecture"),
+            Problem::Conflict { package, version } => {
+                write!(f, "conflicts with {} {}", package, version)
+            }
+            Problem::Aborted => write!(f, "search aborted after {} steps", MAX_STEPS),
+        }
+    }
+}
+
+/// Relation declared by a selected package, e.g. "foo 1.0 Depends: bar (>= 2.0)".
+#[derive(Debug, Clone)]
+pub struct Dependency {
+    pub package: String,
+    pub version: PackageVersion,
+    pub field: String,
+    pub relation: PackageRelation,
+}
+
+impl fmt::Display for Dependency {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} {} {}: {}",
+            self.package, self.version, self.field, self.relation
+        )
+    }
+}
+
+/// Explanation of a failed resolution.
+#[derive(Debug, Clone)]
+pub struct Unsatisfiable {
+    /// The requested relation which can't be installed.
+    pub requested: Box<PackageRelation>,
+    /// The relations from the requested package to the unsatisfiable relation,
+    /// empty if the requested relation itself is unsatisfiable.
+    pub chain: Vec<Dependency>,
+    pub problem: Problem,
+}
+
+impl Unsatisfiable {
+    /// The relation which can't be satisfied.
+    pub fn relation(&self) -> &PackageRelation {
+        self.chain
+            .last()
+            .map(|d| &d.relation)
+            .unwrap_or(self.requested.as_ref())
+    }
+}
+
+impl fmt::Display for Unsatisfiable {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "[Resolver] {}", self.requested)?;
+        for dependency in &self.chain {
+            write!(f, " -> {}", dependency)?;
+        }
+        write!(f, ": {}", self.problem)
+    }
+}
+
+impl From<Unsatisfiable> for RaptoboError {
+    fn from(e: Unsatisfiable) -> RaptoboError {
+        RaptoboError::with_kind(ErrorKind::Unsatisfiable, &e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Problem, Resolver};
+    use crate::error::{ErrorKind, RaptoboError};
+    use crate::package::{PackageMetadata, PackageRelation};
+
+    const PACKAGES: &str = "\
+Package: app
+Version: 2.0
+Architecture: amd64
+Depends: libfoo (>= 1.2), mail-transport-agent | exim4
+Pre-Depends: base
+
+Package: app
+Version: 1.0
+Architecture: amd64
+Depends: libfoo (<< 1.0)
+
+Package: base
+Version: 1
+Architecture: amd64
+
+Package: libfoo
+Version: 1.5-1
+Architecture: amd64
+Depends: base
+Breaks: app (<< 2.0)
+
+Package: libfoo
+Version: 0.9-1
+Architecture: amd64
+
+Package: postfix
+Version: 3.7
+Architecture: amd64
+Provides: mail-transport-agent
+Conflicts: mail-transport-agent
+
+Package: exim4
+Version: 4.96
+Architecture: all
+Provides: mail-transport-agent
+Conflicts: mail-transport-agent
+
+Package: old-app
+Version: 1.0
+Architecture: amd64
+Depends: app (>= 3.0)
+
+Package: broken
+Version: 1.0
+Architecture: amd64
+Depends: app, missing-lib | other-missing
+
+Package: mta-user
+Version: 1.0
+Architecture: amd64
+Depends: postfix, exim4
+
+Package: librust-serde-dev
+Version: 1.0.190-1
+Architecture: amd64
+Provides: librust-serde-1-dev (= 1.0.190-1), librust-serde+std-dev
+
+Package: librust-serde-dev
+Version: 1.0.150-1
+Architecture: amd64
+Provides: librust-serde-1-dev (= 1.0.150-1), librust-serde+std-dev
+
+Package: rust-app
+Version: 1.0
+Architecture: amd64
+Depends: librust-serde-1-dev (>= 1.0.100), librust-serde-1-dev (<< 1.0.160)
+
+Package: rust-tool
+Version: 1.0
+Architecture: amd64
+Depends: librust-serde+std-dev (>= 1.0)
+
+Package: serde-legacy
+Version: 1.0
+Architecture: amd64
+Conflicts: librust-serde-1-dev (>= 1.0.160)
+";
+
+    fn packages() -> Vec<PackageMetadata> {
+        PackageMetadata::read(PACKAGES.as_bytes())
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap()
+    }
+
+    fn names(packages: &[&PackageMetadata]) -> Vec<String> {
+        packages
+            .iter()
+            .map(|p| format!("{} {}", p.package, p.version))
+            .collect()
+    }
+
+    #[test]
+    fn resolve_closure() {
+        let packages = packages();
+        let resolver = Resolver::new(&packages);
+
+        let requested = PackageRelation::parse("app").unwrap();
+        let install = resolver.resolve(&requested).unwrap();
+        assert_eq!(
+            names(&install),
+            ["app 2.0", "base 1", "libfoo 1.5-1", "exim4 4.96"]
+        );
+
+        // the version constraint selects the older app, which libfoo 1.5 breaks
+        let requested = PackageRelation::parse("app (<< 2.0)").unwrap();
+        let install = resolver.resolve(&requested).unwrap();
+        assert_eq!(names(&install), ["app 1.0", "libfoo 0.9-1"]);
+
+        // the second alternative of the request is used if the first one fails
+        let requested = PackageRelation::parse("old-app | app, exim4").unwrap();
+        let install = resolver.resolve(&requested).unwrap();
+        assert_eq!(
+            names(&install),
+            ["app 2.0", "exim4 4.96", "base 1", "libfoo 1.5-1"]
+        );
+
+        assert!(resolver.resolve(&[]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn resolve_versioned_provides() {
+        let packages = packages();
+        let resolver = Resolver::new(&packages);
+
+        // the versioned Provides select the provider version
+        let requested = PackageRelation::parse("rust-app").unwrap();
+        let install = resolver.resolve(&requested).unwrap();
+        assert_eq!(
+            names(&install),
+            ["rust-app 1.0", "librust-serde-dev 1.0.150-1"]
+        );
+
+        let requested = PackageRelation::parse("serde-legacy, librust-serde-1-dev").unwrap();
+        let install = resolver.resolve(&requested).unwrap();
+        assert_eq!(
+            names(&install),
+            ["serde-legacy 1.0", "librust-serde-dev 1.0.150-1"]
+        );
+
+        // an unversioned Provides doesn't satisfy a versioned dependency
+        let requested = PackageRelation::parse("rust-tool").unwrap();
+        let error = resolver.resolve(&requested).unwrap_err();
+        assert_eq!(error.problem, Problem::Version);
+
+        let requested =
+            PackageRelation::parse("serde-legacy, librust-serde-dev (>= 1.0.190)").unwrap();
+        let error = resolver.resolve(&requested).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "[Resolver] librust-serde-dev (>= 1.0.190): conflicts with serde-legacy 1.0"
+        );
+    }
+
+    #[test]
+    fn explain_failures() {
+        let packages = packages();
+        let resolver = Resolver::new(&packages);
+
+        let requested = PackageRelation::parse("old-app").unwrap();
+        let error = resolver.resolve(&requested).unwrap_err();
+        assert_eq!(error.problem, Problem::Version);
+        assert_eq!(error.chain.len(), 1);
+        assert_eq!(error.relation().to_string(), "app (>= 3.0)");
+        assert_eq!(
+            error.to_string(),
+            "[Resolver] old-app -> old-app 1.0 Depends: app (>= 3.0): no matching version"
+        );
+
+        let requested = PackageRelation::parse("broken").unwrap();
+        let error = resolver.resolve(&requested).unwrap_err();
+        assert_eq!(error.problem, Problem::Missing);
+        assert_eq!(
+            error.to_string(),
+            "[Resolver] broken -> broken 1.0 Depends: missing-lib | other-missing: missing package"
+        );
+
+        let requested = PackageRelation::parse("mta-user").unwrap();
+        let error = resolver.resolve(&requested).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "[Resolver] mta-user -> mta-user 1.0 Depends: exim4: conflicts with postfix 3.7"
+        );
+
+        // a requested version broken by a requested dependency
+        let requested = PackageRelation::parse("app (= 1.0), libfoo (>= 1.5)").unwrap();
+        let error = resolver.resolve(&requested).unwrap_err();
+        assert_eq!(error.requested.to_string(), "libfoo (>= 1.5)");
+        assert!(error.chain.is_empty());
+        assert_eq!(error.problem.to_string(), "conflicts with app 1.0");
+
+        let requested = PackageRelation::parse("nothing").unwrap();
+        let error: RaptoboError = resolver.resolve(&requested).unwrap_err().into();
+        assert_eq!(error.kind(), &ErrorKind::Unsatisfiable);
+        assert_eq!(error.to_string(), "[Resolver] nothing: missing package");
+    }
+
+    const MULTIARCH_PACKAGES: &str = "\
+Package: app
+Version: 1.0
+Architecture: i386
+Depends: libc6, python3:any, make, data
+
+Package: libc6
+Version: 2.36-9
+Architecture: amd64
+Multi-Arch: same
+
+Package: libc6
+Version: 2.36-9
+Architecture: i386
+Multi-Arch: same
+
+Package: libc6
+Version: 2.35-1
+Architecture: i386
+Multi-Arch: same
+
+Package: python3
+Version: 3.11.2-1
+Architecture: amd64
+Multi-Arch: allowed
+
+Package: make
+Version: 4.3-4.1
+Architecture: amd64
+Multi-Arch: foreign
+
+Package: data
+Version: 1.0
+Architecture: all
+Multi-Arch: foreign
+
+Package: perl
+Version: 5.36.0-7
+Architecture: amd64
+
+Package: tool
+Version: 1.0
+Architecture: i386
+Depends: perl
+
+Package: legacy
+Version: 1.0
+Architecture: i386
+
+Package: mta
+Version: 1.0
+Architecture: amd64
+Conflicts: legacy
+";
+
+    #[test]
+    fn resolve_multiarch() {
+        let packages: Vec<PackageMetadata> = PackageMetadata::read(MULTIARCH_PACKAGES.as_bytes())
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let resolver = Resolver::new(&packages).with_architecture("amd64");
+        let names = |packages: &[&PackageMetadata]| -> Vec<String> {
+            packages
+                .iter()
+                .map(|p| format!("{}:{} {}", p.package, p.architecture, p.version))
+                .collect()
+        };
+
+        // foreign and allowed packages satisfy relations of other architectures
+        let requested = PackageRelation::parse("app:i386").unwrap();
+        let install = resolver.resolve(&requested).unwrap();
+        assert_eq!(
+            names(&install),
+            [
+                "app:i386 1.0",
+                "libc6:i386 2.36-9",
+                "python3:amd64 3.11.2-1",
+                "make:amd64 4.3-4.1",
+                "data:all 1.0"
+            ]
+        );
+
+        // Multi-Arch same packages are co-installable in the same version
+        let requested = PackageRelation::parse("app:i386, libc6").unwrap();
+        let install = resolver.resolve(&requested).unwrap();
+        let install = names(&install);
+        assert_eq!(
+            install[..3],
+            ["app:i386 1.0", "libc6:amd64 2.36-9", "libc6:i386 2.36-9"]
+        );
+
+        let requested = PackageRelation::parse("libc6:i386 (= 2.35-1), libc6").unwrap();
+        let error = resolver.resolve(&requested).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "[Resolver] libc6: conflicts with libc6 2.35-1"
+        );
+
+        // perl is Multi-Arch no, only packages of the same architecture can use it
+        let requested = PackageRelation::parse("tool:i386").unwrap();
+        let error = resolver.resolve(&requested).unwrap_err();
+        assert_eq!(error.problem, Problem::Architecture);
+        assert_eq!(
+            error.to_string(),
+            "[Resolver] tool:i386 -> tool 1.0 Depends: perl: no matching architecture"
+        );
+        // requested packages are native packages, unless qualified
+        for requested in ["tool", "perl:i386"] {
+            let requested = PackageRelation::parse(requested).unwrap();
+            let error = resolver.resolve(&requested).unwrap_err();
+            assert_eq!(error.problem, Problem::Architecture);
+        }
+
+        // unqualified Conflicts apply to the packages of all architectures
+        let requested = PackageRelation::parse("mta, legacy:i386").unwrap();
+        let error = resolver.resolve(&requested).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "[Resolver] legacy:i386: conflicts with mta 1.0"
+        );
+
+        // without native architecture, the architectures are ignored
+        let requested = PackageRelation::parse("tool").unwrap();
+        let install = Resolver::new(&packages).resolve(&requested).unwrap();
+        assert_eq!(names(&install), ["tool:i386 1.0", "perl:amd64 5.36.0-7"]);
+    }
+}
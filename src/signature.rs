@@ -0,0 +1,1165 @@
+use crate::error::{ErrorKind, RaptoboError};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use chrono::Utc;
+use ed25519_dalek::{Signature as Ed25519Signature, Verifier, VerifyingKey};
+use rsa::traits::PublicKeyParts;
+use rsa::{BigUint, Pkcs1v15Sign, RsaPublicKey};
+use sha1::Sha1;
+use sha2::{Digest, Sha224, Sha256, Sha384, Sha512};
+use std::fs;
+
+// OpenPGP support for verifying InRelease and Release files, see RFC 4880.
+//
+// Only the subset needed for APT repositories is implemented: v4 keys and
+// signatures using RSA or Ed25519. The keys of a keyring are trusted as is,
+// verified self-signatures are only used to find the key expiration time and
+// revocations. SHA-1 signatures are rejected as weak, like APT does.
+
+const TAG_SIGNATURE: u8 = 2;
+const TAG_PUBLIC_KEY: u8 = 6;
+const TAG_USER_ID: u8 = 13;
+const TAG_PUBLIC_SUBKEY: u8 = 14;
+const TAG_USER_ATTRIBUTE: u8 = 17;
+
+const SUBPACKET_CREATION_TIME: u8 = 2;
+const SUBPACKET_EXPIRATION_TIME: u8 = 3;
+const SUBPACKET_KEY_EXPIRATION_TIME: u8 = 9;
+const SUBPACKET_ISSUER: u8 = 16;
+const SUBPACKET_ISSUER_FINGERPRINT: u8 = 33;
+
+const ED25519_OID: [u8; 9] = [0x2B, 0x06, 0x01, 0x04, 0x01, 0xDA, 0x47, 0x0F, 0x01];
+
+/// Public key algorithm, RFC 4880 9.1.
+#[derive(Debug, Clone)]
+pub enum KeyMaterial {
+    Rsa(RsaPublicKey),
+    Ed25519(VerifyingKey),
+    /// Key using an algorithm not supported for verification.
+    Unsupported(u8),
+}
+
+/// An OpenPGP v4 public key or subkey.
+#[derive(Debug, Clone)]
+pub struct PublicKey {
+    /// Key fingerprint, upper case hex.
+    pub fingerprint: String,
+    /// Key ID, the lower 64 bits of the fingerprint, upper case hex.
+    pub key_id: String,
+    /// Creation time, seconds since epoch.
+    pub created: i64,
+    /// Expiration time, seconds since epoch.
+    pub expires: Option<i64>,
+    /// Revoked by a revocation signature of the key or its primary key.
+    pub revoked: bool,
+    pub material: KeyMaterial,
+}
+
+impl PublicKey {
+    fn parse(body: &[u8]) -> Result<PublicKey, RaptoboError> {
+        let mut reader = Reader::new(body);
+
+        let version = reader.u8()?;
+        if version != 4 {
+            return Err(RaptoboError::new(&format!(
+                "[PublicKey::parse] unsupported key version {}",
+                version
+            )));
+        }
+
+        let created = reader.u32()? as i64;
+        let algorithm = reader.u8()?;
+
+        let material = match algorithm {
+            1..=3 => {
+                let n = BigUint::from_bytes_be(reader.mpi()?);
+                let e = BigUint::from_bytes_be(reader.mpi()?);
+                let key = RsaPublicKey::new_with_max_size(n, e, 16384)
+                    .map_err(|e| RaptoboError::new(&e.to_string()))?;
+                KeyMaterial::Rsa(key)
+            }
+            22 => {
+                let len = reader.u8()? as usize;
+                let oid = reader.bytes(len)?;
+                let point = reader.mpi()?;
+                if oid != ED25519_OID || point.len() != 33 || point[0] != 0x40 {
+                    KeyMaterial::Unsupported(algorithm)
+                } else {
+                    KeyMaterial::Ed25519(ed25519_key(&point[1..])?)
+                }
+            }
+            27 => KeyMaterial::Ed25519(ed25519_key(reader.bytes(32)?)?),
+            _ => KeyMaterial::Unsupported(algorithm),
+        };
+
+        let mut hasher = Sha1::new();
+        hasher.update(key_data(body));
+        let fingerprint = to_hex(&hasher.finalize());
+        let key_id = fingerprint[24..].to_string();
+
+        Ok(PublicKey {
+            fingerprint,
+            key_id,
+            created,
+            expires: None,
+            revoked: false,
+            material,
+        })
+    }
+
+    fn matches(&self, signature: &Signature) -> bool {
+        match &signature.issuer_fingerprint {
+            Some(fingerprint) => &self.fingerprint == fingerprint,
+            None => match &signature.issuer {
+                Some(key_id) => &self.key_id == key_id,
+                None => false,
+            },
+        }
+    }
+
+    /// Verify the signature of the data made by this key.
+    fn verify_data(&self, data: &[u8], signature: &Signature) -> Result<(), RaptoboError> {
+        let digest = signature.digest(data)?;
+        if digest[..2] != signature.left {
+            return Err(RaptoboError::with_kind(
+                ErrorKind::SignatureInvalid,
+                &format!("[PublicKey::verify] {}: bad signature", self.fingerprint),
+            ));
+        }
+        self.verify(&digest, signature)
+    }
+
+    fn verify(&self, digest: &[u8], signature: &Signature) -> Result<(), RaptoboError> {
+        let invalid = |msg: &str| {
+            RaptoboError::with_kind(
+                ErrorKind::SignatureInvalid,
+                &format!("[PublicKey::verify] {}: {}", self.fingerprint, msg),
+            )
+        };
+
+        match (&self.material, &signature.algorithm, &signature.values) {
+            (KeyMaterial::Rsa(key), 1..=3, values) if values.len() == 1 => {
+                let scheme = match signature.hash {
+                    8 => Pkcs1v15Sign::new::<Sha256>(),
+                    9 => Pkcs1v15Sign::new::<Sha384>(),
+                    10 => Pkcs1v15Sign::new::<Sha512>(),
+                    11 => Pkcs1v15Sign::new::<Sha224>(),
+                    h => return Err(invalid(&format!("unsupported hash algorithm {}", h))),
+                };
+                let value =
+                    left_pad(&values[0], key.size()).ok_or(invalid("invalid signature size"))?;
+                key.verify(scheme, digest, &value)
+                    .map_err(|_| invalid("bad signature"))
+            }
+            (KeyMaterial::Ed25519(key), 22 | 27, values) if values.len() <= 2 => {
+                let value: Vec<u8> = if values.len() == 2 {
+                    let r = left_pad(&values[0], 32).ok_or(invalid("invalid signature size"))?;
+                    let s = left_pad(&values[1], 32).ok_or(invalid("invalid signature size"))?;
+                    [r, s].concat()
+                } else {
+                    values.concat()
+                };
+                let value: [u8; 64] = value
+                    .try_into()
+                    .map_err(|_| invalid("invalid signature size"))?;
+                key.verify(digest, &Ed25519Signature::from_bytes(&value))
+                    .map_err(|_| invalid("bad signature"))
+            }
+            (KeyMaterial::Unsupported(a), _, _) => {
+                Err(invalid(&format!("unsupported key algorithm {}", a)))
+            }
+            (_, a, _) => Err(invalid(&format!("unsupported signature algorithm {}", a))),
+        }
+    }
+}
+
+/// An OpenPGP v4 signature, RFC 4880 5.2.
+#[derive(Debug, Clone)]
+pub struct Signature {
+    /// Signature type, e.g. 0x00 for binary documents, 0x01 for text documents,
+    /// 0x13 for user ID certifications and 0x20 for key revocations.
+    pub signature_type: u8,
+    /// Public key algorithm.
+    pub algorithm: u8,
+    /// Hash algorithm.
+    pub hash: u8,
+    /// Creation time, seconds since epoch.
+    pub created: Option<i64>,
+    /// Expiration time, seconds since epoch.
+    pub expires: Option<i64>,
+    /// Key expiration time in seconds after key creation, only used in self-signatures.
+    pub key_expiration: Option<i64>,
+    /// Issuer key ID, upper case hex.
+    pub issuer: Option<String>,
+    /// Issuer fingerprint, upper case hex.
+    pub issuer_fingerprint: Option<String>,
+    /// Version, type, algorithms and hashed subpackets, part of the signed data.
+    hashed: Vec<u8>,
+    /// Left 16 bits of the signed hash value.
+    left: [u8; 2],
+    /// Algorithm specific signature values.
+    values: Vec<Vec<u8>>,
+}
+
+impl Signature {
+    fn parse(body: &[u8]) -> Result<Signature, RaptoboError> {
+        let mut reader = Reader::new(body);
+
+        let version = reader.u8()?;
+        if version != 4 {
+            return Err(RaptoboError::with_kind(
+                ErrorKind::SignatureInvalid,
+                &format!(
+                    "[Signature::parse] unsupported signature version {}",
+                    version
+                ),
+            ));
+        }
+
+        let signature_type = reader.u8()?;
+        let algorithm = reader.u8()?;
+        let hash = reader.u8()?;
+
+        let len = reader.u16()? as usize;
+        let hashed_subpackets = reader.bytes(len)?;
+        let hashed = body[..6 + len].to_vec();
+        let len = reader.u16()? as usize;
+        let unhashed_subpackets = reader.bytes(len)?;
+
+        let left = reader.bytes(2)?;
+        let left = [left[0], left[1]];
+
+        let mut values = Vec::new();
+        if algorithm == 27 {
+            values.push(reader.bytes(64)?.to_vec());
+        } else {
+            while !reader.is_empty() {
+                values.push(reader.mpi()?.to_vec());
+            }
+        }
+
+        let mut signature = Signature {
+            signature_type,
+            algorithm,
+            hash,
+            created: None,
+            expires: None,
+            key_expiration: None,
+            issuer: None,
+            issuer_fingerprint: None,
+            hashed,
+            left,
+            values,
+        };
+
+        let mut expiration = None;
+        for (hashed, data) in [(true, hashed_subpackets), (false, unhashed_subpackets)] {
+            let mut reader = Reader::new(data);
+            while !reader.is_empty() {
+                let len = match reader.u8()? as usize {
+                    l if l < 192 => l,
+                    l if l < 255 => ((l - 192) << 8) + reader.u8()? as usize + 192,
+                    _ => reader.u32()? as usize,
+                };
+                if len == 0 {
+                    return Err(RaptoboError::new("[Signature::parse] empty subpacket"));
+                }
+                let subpacket = reader.bytes(len)?;
+                let mut sub = Reader::new(&subpacket[1..]);
+
+                // only the issuer is accepted from the unhashed area, as it is not signed
+                match (hashed, subpacket[0] & 0x7F) {
+                    (true, SUBPACKET_CREATION_TIME) => signature.created = Some(sub.u32()? as i64),
+                    (true, SUBPACKET_EXPIRATION_TIME) => expiration = Some(sub.u32()? as i64),
+                    (true, SUBPACKET_KEY_EXPIRATION_TIME) => {
+                        signature.key_expiration = Some(sub.u32()? as i64)
+                    }
+                    (_, SUBPACKET_ISSUER) if signature.issuer.is_none() => {
+                        signature.issuer = Some(to_hex(sub.bytes(8)?));
+                    }
+                    (_, SUBPACKET_ISSUER_FINGERPRINT) if signature.issuer_fingerprint.is_none() => {
+                        let version = sub.u8()?;
+                        if version == 4 {
+                            signature.issuer_fingerprint = Some(to_hex(sub.bytes(20)?));
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        signature.expires = match (signature.created, expiration) {
+            (Some(created), Some(expiration)) if expiration > 0 => Some(created + expiration),
+            _ => None,
+        };
+
+        Ok(signature)
+    }
+
+    /// Parse all signatures of a binary or ASCII armored signature block.
+    pub fn parse_all(data: &[u8]) -> Result<Vec<Signature>, RaptoboError> {
+        let data = match std::str::from_utf8(data) {
+            Ok(text) if is_armored(text) => {
+                let mut data = Vec::new();
+                for block in dearmor(text)? {
+                    data.extend(block);
+                }
+                data
+            }
+            _ => data.to_vec(),
+        };
+
+        let mut signatures = Vec::new();
+        for (tag, body) in packets(&data)? {
+            if tag == TAG_SIGNATURE {
+                signatures.push(Signature::parse(body)?);
+            }
+        }
+
+        if signatures.is_empty() {
+            return Err(RaptoboError::with_kind(
+                ErrorKind::SignatureMissing,
+                "[Signature::parse_all] no signature found!",
+            ));
+        }
+
+        Ok(signatures)
+    }
+
+    /// Calculate the hash of the given document as signed by this signature.
+    fn digest(&self, data: &[u8]) -> Result<Vec<u8>, RaptoboError> {
+        let mut trailer = self.hashed.clone();
+        trailer.extend([0x04, 0xFF]);
+        trailer.extend((self.hashed.len() as u32).to_be_bytes());
+
+        let digest = match self.hash {
+            2 => {
+                return Err(RaptoboError::with_kind(
+                    ErrorKind::SignatureInvalid,
+                    "[Signature::digest] weak hash algorithm SHA1",
+                ))
+            }
+            8 => hash::<Sha256>(data, &trailer),
+            9 => hash::<Sha384>(data, &trailer),
+            10 => hash::<Sha512>(data, &trailer),
+            11 => hash::<Sha224>(data, &trailer),
+            h => {
+                return Err(RaptoboError::with_kind(
+                    ErrorKind::SignatureInvalid,
+                    &format!("[Signature::digest] unsupported hash algorithm {}", h),
+                ))
+            }
+        };
+
+        Ok(digest)
+    }
+}
+
+/// A cleartext signed message like the InRelease file, RFC 4880 7.
+#[derive(Debug)]
+pub struct CleartextMessage {
+    /// Hash algorithms announced in the armor headers.
+    pub hashes: Vec<String>,
+    /// The message text, dash-escaping removed.
+    pub text: String,
+    /// The signatures of the message.
+    pub signatures: Vec<Signature>,
+}
+
+impl CleartextMessage {
+    pub fn parse(content: &str) -> Result<CleartextMessage, RaptoboError> {
+        let mut lines = content.lines().map(|l| l.trim_end_matches('\r'));
+
+        match lines.find(|l| !l.trim().is_empty()) {
+            Some("-----BEGIN PGP SIGNED MESSAGE-----") => {}
+            _ => {
+                return Err(RaptoboError::with_kind(
+                    ErrorKind::SignatureMissing,
+                    "[CleartextMessage::parse] not a signed message!",
+                ))
+            }
+        }
+
+        let mut hashes = Vec::new();
+        for line in lines.by_ref() {
+            if line.is_empty() {
+                break;
+            }
+            if let Some(("Hash", value)) = line.split_once(':') {
+                hashes.extend(value.split(',').map(|h| h.trim().to_string()));
+            }
+        }
+
+        let mut text: Vec<&str> = Vec::new();
+        let mut signed = false;
+        for line in lines.by_ref() {
+            if line == "-----BEGIN PGP SIGNATURE-----" {
+                signed = true;
+                break;
+            }
+            text.push(line.strip_prefix("- ").unwrap_or(line));
+        }
+
+        if !signed {
+            return Err(RaptoboError::with_kind(
+                ErrorKind::SignatureMissing,
+                "[CleartextMessage::parse] signature block missing!",
+            ));
+        }
+
+        let armor: Vec<&str> = ["-----BEGIN PGP SIGNATURE-----"]
+            .into_iter()
+            .chain(lines)
+            .collect();
+        let signatures = Signature::parse_all(armor.join("\n").as_bytes())?;
+
+        Ok(CleartextMessage {
+            hashes,
+            text: text.join("\n"),
+            signatures,
+        })
+    }
+
+    /// The signed data, trailing whitespace removed and using CRLF line endings.
+    fn signed_data(&self) -> Vec<u8> {
+        let lines: Vec<&str> = self
+            .text
+            .split('\n')
+            .map(|l| l.trim_end_matches([' ', '\t']))
+            .collect();
+        lines.join("\r\n").into_bytes()
+    }
+}
+
+/// A set of trusted public keys, like the keyrings referenced by Signed-By.
+#[derive(Debug, Clone, Default)]
+pub struct Keyring {
+    pub keys: Vec<PublicKey>,
+}
+
+impl Keyring {
+    pub fn new() -> Keyring {
+        Keyring { keys: Vec::new() }
+    }
+
+    /// Load a binary (.gpg) or ASCII armored (.asc) keyring file.
+    pub fn load(path: &str) -> Result<Keyring, RaptoboError> {
+        let data = fs::read(path)
+            .map_err(|e| RaptoboError::new(&format!("[Keyring::load] {}: {}", path, e)))?;
+        let mut keyring = Keyring::new();
+        keyring.add_keys(&data)?;
+        log::debug!("[Keyring::load] {}: {} keys", path, keyring.keys.len());
+        Ok(keyring)
+    }
+
+    /// Add all keys of the given binary or ASCII armored keyring data.
+    pub fn add_keys(&mut self, data: &[u8]) -> Result<(), RaptoboError> {
+        let data = match std::str::from_utf8(data) {
+            Ok(text) if is_armored(text) => dearmor(text)?.concat(),
+            _ => data.to_vec(),
+        };
+
+        // index of the current primary key and the key the next signatures belong to
+        let mut primary: Option<usize> = None;
+        let mut current: Option<usize> = None;
+        // the current primary key is unsupported, its subkeys are skipped too
+        let mut skipped = false;
+        // user ID or attribute packet the next certifications belong to
+        let mut user: Option<(u8, &[u8])> = None;
+        // per added key: key packet, primary key index and creation time of the
+        // self-signature which provided the key expiration
+        let first = self.keys.len();
+        let mut bodies: Vec<&[u8]> = Vec::new();
+        let mut primaries: Vec<usize> = Vec::new();
+        let mut expiration_from: Vec<i64> = Vec::new();
+
+        for (tag, body) in packets(&data)? {
+            match tag {
+                TAG_PUBLIC_KEY | TAG_PUBLIC_SUBKEY => {
+                    user = None;
+                    if tag == TAG_PUBLIC_SUBKEY && primary.is_none() {
+                        if skipped {
+                            log::debug!("[Keyring::add_keys] skipping subkey of skipped key");
+                            current = None;
+                            continue;
+                        }
+                        return Err(RaptoboError::new(
+                            "[Keyring::add_keys] subkey without primary key!",
+                        ));
+                    }
+                    match PublicKey::parse(body) {
+                        Ok(key) => {
+                            self.keys.push(key);
+                            current = Some(self.keys.len() - 1);
+                            if tag == TAG_PUBLIC_KEY {
+                                primary = current;
+                                skipped = false;
+                            }
+                            bodies.push(body);
+                            primaries.extend(primary);
+                            expiration_from.push(i64::MIN);
+                        }
+                        Err(e) => {
+                            log::warn!("[Keyring::add_keys] skipping key: {}", e);
+                            current = None;
+                            if tag == TAG_PUBLIC_KEY {
+                                primary = None;
+                                skipped = true;
+                            }
+                        }
+                    }
+                }
+                TAG_USER_ID | TAG_USER_ATTRIBUTE => user = Some((tag, body)),
+                TAG_SIGNATURE => {
+                    let (Some(p), Some(c)) = (primary, current) else {
+                        continue;
+                    };
+                    let signature = match Signature::parse(body) {
+                        Ok(s) => s,
+                        Err(e) => {
+                            log::debug!("[Keyring::add_keys] skipping signature: {}", e);
+                            continue;
+                        }
+                    };
+                    // only self-signatures may define the key expiration and revocation
+                    if !self.keys[p].matches(&signature) {
+                        continue;
+                    }
+
+                    // the signed data, RFC 4880 5.2.4
+                    let mut signed = key_data(bodies[p - first]);
+                    match (signature.signature_type, p == c, user) {
+                        (0x10..=0x13, true, Some((tag, body))) => {
+                            signed.push(if tag == TAG_USER_ID { 0xB4 } else { 0xD1 });
+                            signed.extend((body.len() as u32).to_be_bytes());
+                            signed.extend(body);
+                        }
+                        (0x1F | 0x20, true, _) => {}
+                        (0x18 | 0x28, false, _) => signed.extend(key_data(bodies[c - first])),
+                        _ => continue,
+                    }
+                    if let Err(e) = self.keys[p].verify_data(&signed, &signature) {
+                        log::debug!("[Keyring::add_keys] skipping self-signature: {}", e);
+                        continue;
+                    }
+
+                    if signature.signature_type == 0x20 || signature.signature_type == 0x28 {
+                        self.keys[c].revoked = true;
+                        continue;
+                    }
+                    let created = signature.created.unwrap_or(0);
+                    if created >= expiration_from[c - first] {
+                        expiration_from[c - first] = created;
+                        let key = &mut self.keys[c];
+                        key.expires = match signature.key_expiration {
+                            Some(e) if e > 0 => Some(key.created + e),
+                            _ => None,
+                        };
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        // the subkeys of a revoked primary key are revoked too
+        for (i, p) in primaries.into_iter().enumerate() {
+            if self.keys[p].revoked {
+                self.keys[first + i].revoked = true;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Add all keys of the given keyring.
+    pub fn extend(&mut self, keyring: Keyring) {
+        self.keys.extend(keyring.keys);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// Verify a cleartext signed message, e.g. an InRelease file.
+    pub fn verify_cleartext(&self, message: &CleartextMessage) -> Result<(), RaptoboError> {
+        self.verify(&message.signed_data(), &message.signatures, true)
+    }
+
+    /// Verify a detached binary or ASCII armored signature, e.g. a Release.gpg file.
+    pub fn verify_detached(&self, data: &[u8], signature: &[u8]) -> Result<(), RaptoboError> {
+        let signatures = Signature::parse_all(signature)?;
+        self.verify(data, &signatures, false)
+    }
+
+    /// Check that at least one of the signatures is a valid signature of a key of this keyring.
+    fn verify(
+        &self,
+        data: &[u8],
+        signatures: &[Signature],
+        cleartext: bool,
+    ) -> Result<(), RaptoboError> {
+        let now = Utc::now().timestamp();
+
+        let mut error: Option<RaptoboError> = None;
+        for signature in signatures {
+            let result = self.verify_signature(data, signature, cleartext, now);
+            match result {
+                Ok(fingerprint) => {
+                    log::debug!("[Keyring::verify] good signature from {}", fingerprint);
+                    return Ok(());
+                }
+                Err(e) => {
+                    log::debug!("[Keyring::verify] {}", e);
+                    // errors of known keys are more specific than unknown keys
+                    let replace = match &error {
+                        None => true,
+                        Some(current) => {
+                            current.kind() == &ErrorKind::UnknownKey
+                                && e.kind() != &ErrorKind::UnknownKey
+                        }
+                    };
+                    if replace {
+                        error = Some(e);
+                    }
+                }
+            }
+        }
+
+        Err(error.unwrap_or(RaptoboError::with_kind(
+            ErrorKind::SignatureMissing,
+            "[Keyring::verify] no signature found!",
+        )))
+    }
+
+    fn verify_signature(
+        &self,
+        data: &[u8],
+        signature: &Signature,
+        cleartext: bool,
+        now: i64,
+    ) -> Result<String, RaptoboError> {
+        let key =
+            self.keys
+                .iter()
+                .find(|k| k.matches(signature))
+                .ok_or(RaptoboError::with_kind(
+                    ErrorKind::UnknownKey,
+                    &format!(
+                        "[Keyring::verify] unknown key {}",
+                        signature
+                            .issuer_fingerprint
+                            .as_ref()
+                            .or(signature.issuer.as_ref())
+                            .map(|i| i.as_str())
+                            .unwrap_or("without issuer")
+                    ),
+                ))?;
+
+        if key.revoked {
+            return Err(RaptoboError::with_kind(
+                ErrorKind::KeyRevoked,
+                &format!("[Keyring::verify] key {} is revoked", key.fingerprint),
+            ));
+        }
+
+        if let Some(expires) = signature.expires {
+            if expires <= now {
+                return Err(RaptoboError::with_kind(
+                    ErrorKind::SignatureExpired,
+                    &format!(
+                        "[Keyring::verify] signature of {} expired at {}",
+                        key.fingerprint,
+                        format_time(expires)
+                    ),
+                ));
+            }
+        }
+
+        if let Some(expires) = key.expires {
+            if expires <= now {
+                return Err(RaptoboError::with_kind(
+                    ErrorKind::KeyExpired,
+                    &format!(
+                        "[Keyring::verify] key {} expired at {}",
+                        key.fingerprint,
+                        format_time(expires)
+                    ),
+                ));
+            }
+        }
+
+        match (signature.signature_type, cleartext) {
+            (0x01, _) => key.verify_data(&canonical_text(data), signature)?,
+            (0x00, false) => key.verify_data(data, signature)?,
+            (t, _) => {
+                return Err(RaptoboError::with_kind(
+                    ErrorKind::SignatureInvalid,
+                    &format!("[Keyring::verify] invalid signature type {:#04x}", t),
+                ))
+            }
+        }
+
+        Ok(key.fingerprint.to_string())
+    }
+}
+
+/// Key packet as hashed by signatures over keys, RFC 4880 5.2.4.
+fn key_data(body: &[u8]) -> Vec<u8> {
+    let mut data = vec![0x99];
+    data.extend((body.len() as u16).to_be_bytes());
+    data.extend(body);
+    data
+}
+
+/// Simple reader for binary OpenPGP data.
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8]) -> Reader<'a> {
+        Reader { data, pos: 0 }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.pos >= self.data.len()
+    }
+
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], RaptoboError> {
+        if self.pos + len > self.data.len() {
+            return Err(RaptoboError::new(&format!(
+                "[Reader::bytes] unexpected end of data at {}",
+                self.pos
+            )));
+        }
+        let bytes = &self.data[self.pos..self.pos + len];
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, RaptoboError> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, RaptoboError> {
+        let b = self.bytes(2)?;
+        Ok(u16::from_be_bytes([b[0], b[1]]))
+    }
+
+    fn u32(&mut self) -> Result<u32, RaptoboError> {
+        let b = self.bytes(4)?;
+        Ok(u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    /// Multiprecision integer, RFC 4880 3.2.
+    fn mpi(&mut self) -> Result<&'a [u8], RaptoboError> {
+        let bits = self.u16()? as usize;
+        self.bytes(bits.div_ceil(8))
+    }
+}
+
+/// Split binary OpenPGP data into (tag, body) packets, RFC 4880 4.2.
+fn packets(data: &[u8]) -> Result<Vec<(u8, &[u8])>, RaptoboError> {
+    let mut reader = Reader::new(data);
+    let mut packets = Vec::new();
+
+    while !reader.is_empty() {
+        let header = reader.u8()?;
+        if header & 0x80 == 0 {
+            return Err(RaptoboError::new(&format!(
+                "[packets] invalid packet header {:#04x}",
+                header
+            )));
+        }
+
+        let (tag, len) = if header & 0x40 != 0 {
+            // new format
+            let len = match reader.u8()? as usize {
+                l if l < 192 => l,
+                l if l < 224 => ((l - 192) << 8) + reader.u8()? as usize + 192,
+                255 => reader.u32()? as usize,
+                _ => {
+                    return Err(RaptoboError::new(
+                        "[packets] partial body lengths are not supported!",
+                    ))
+                }
+            };
+            (header & 0x3F, len)
+        } else {
+            // old format
+            let len = match header & 0x03 {
+                0 => reader.u8()? as usize,
+                1 => reader.u16()? as usize,
+                2 => reader.u32()? as usize,
+                _ => data.len() - reader.pos,
+            };
+            ((header >> 2) & 0x0F, len)
+        };
+
+        packets.push((tag, reader.bytes(len)?));
+    }
+
+    Ok(packets)
+}
+
+fn is_armored(text: &str) -> bool {
+    text.trim_start().starts_with("-----BEGIN PGP ")
+}
+
+/// Decode all ASCII armored blocks of the text, RFC 4880 6.2.
+fn dearmor(text: &str) -> Result<Vec<Vec<u8>>, RaptoboError> {
+    let mut blocks = Vec::new();
+    let mut lines = text.lines().map(|l| l.trim());
+
+    while let Some(line) = lines.next() {
+        let kind = match line
+            .strip_prefix("-----BEGIN ")
+            .and_then(|l| l.strip_suffix("-----"))
+        {
+            Some(kind) => kind,
+            None => continue,
+        };
+        let end = format!("-----END {}-----", kind);
+
+        let mut encoded = String::new();
+        let mut checksum: Option<&str> = None;
+        let mut headers = true;
+        let mut complete = false;
+        for line in lines.by_ref() {
+            if line == end {
+                complete = true;
+                break;
+            }
+            if headers {
+                headers = false;
+                if line.contains(": ") {
+                    // armor header, skip up to the blank line
+                    headers = true;
+                    continue;
+                } else if line.is_empty() {
+                    continue;
+                }
+            }
+            if let Some(crc) = line.strip_prefix('=') {
+                checksum = Some(crc);
+            } else {
+                encoded.push_str(line);
+            }
+        }
+
+        if !complete {
+            return Err(RaptoboError::new(&format!(
+                "[dearmor] {} not terminated!",
+                kind
+            )));
+        }
+
+        let data = STANDARD
+            .decode(&encoded)
+            .map_err(|e| RaptoboError::new(&format!("[dearmor] {}: {}", kind, e)))?;
+
+        if let Some(checksum) = checksum {
+            let expected = STANDARD
+                .decode(checksum)
+                .map_err(|e| RaptoboError::new(&format!("[dearmor] {}: {}", kind, e)))?;
+            let crc = crc24(&data).to_be_bytes();
+            if expected != crc[1..] {
+                return Err(RaptoboError::new(&format!(
+                    "[dearmor] {}: checksum mismatch!",
+                    kind
+                )));
+            }
+        }
+
+        blocks.push(data);
+    }
+
+    if blocks.is_empty() {
+        return Err(RaptoboError::new("[dearmor] no armored data found!"));
+    }
+
+    Ok(blocks)
+}
+
+/// CRC-24 checksum of the armor, RFC 4880 6.1.
+fn crc24(data: &[u8]) -> u32 {
+    let mut crc: u32 = 0xB704CE;
+    for b in data {
+        crc ^= (*b as u32) << 16;
+        for _ in 0..8 {
+            crc <<= 1;
+            if crc & 0x1000000 != 0 {
+                crc ^= 0x1864CFB;
+            }
+        }
+    }
+    crc & 0xFFFFFF
+}
+
+/// Convert line endings to CRLF, as required for text signatures.
+fn canonical_text(data: &[u8]) -> Vec<u8> {
+    let mut text = Vec::with_capacity(data.len());
+    let mut last = 0u8;
+    for b in data {
+        if *b == b'\n' && last != b'\r' {
+            text.push(b'\r');
+        }
+        text.push(*b);
+        last = *b;
+    }
+    text
+}
+
+fn hash<D: Digest>(data: &[u8], trailer: &[u8]) -> Vec<u8> {
+    let mut hasher = D::new();
+    hasher.update(data);
+    hasher.update(trailer);
+    hasher.finalize().to_vec()
+}
+
+fn ed25519_key(point: &[u8]) -> Result<VerifyingKey, RaptoboError> {
+    let point: [u8; 32] = point
+        .try_into()
+        .map_err(|_| RaptoboError::new("[ed25519_key] invalid key size"))?;
+    VerifyingKey::from_bytes(&point).map_err(|e| RaptoboError::new(&e.to_string()))
+}
+
+fn left_pad(value: &[u8], len: usize) -> Option<Vec<u8>> {
+    if value.len() > len {
+        return None;
+    }
+    let mut padded = vec![0; len - value.len()];
+    padded.extend_from_slice(value);
+    Some(padded)
+}
+
+fn to_hex(data: &[u8]) -> String {
+    data.iter().map(|b| format!("{:02X}", b)).collect()
+}
+
+fn format_time(time: i64) -> String {
+    match chrono::DateTime::from_timestamp(time, 0) {
+        Some(t) => t.to_rfc2822(),
+        None => time.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{dearmor, CleartextMessage, Keyring};
+    use crate::error::ErrorKind;
+    use std::fs;
+
+    fn data(path: &str) -> String {
+        format!("{}/tests/data/{}", env!("CARGO_MANIFEST_DIR"), path)
+    }
+
+    fn keyring(name: &str) -> Keyring {
+        Keyring::load(&data(&format!("keys/{}", name))).unwrap()
+    }
+
+    fn release() -> Vec<u8> {
+        fs::read(data("repo/dists/stable/Release")).unwrap()
+    }
+
+    fn inrelease() -> CleartextMessage {
+        let content = fs::read_to_string(data("repo/dists/stable/InRelease")).unwrap();
+        CleartextMessage::parse(&content).unwrap()
+    }
+
+    #[test]
+    fn load_keyrings() {
+        let rsa = keyring("rsa.gpg");
+        assert_eq!(rsa.keys.len(), 1);
+        assert_eq!(
+            rsa.keys[0].fingerprint,
+            "CD45199F5834CBDE9B060BB047FB0CC1B2A43275"
+        );
+        assert_eq!(rsa.keys[0].key_id, "47FB0CC1B2A43275");
+        assert_eq!(rsa.keys[0].expires, None);
+
+        let ed25519 = keyring("ed25519.asc");
+        assert_eq!(ed25519.keys.len(), 1);
+        assert_eq!(
+            ed25519.keys[0].fingerprint,
+            "F13B206CC4919EF750715FD4C1EBF77D6455818B"
+        );
+
+        let old = keyring("old.asc");
+        assert_eq!(old.keys.len(), 2);
+        assert_eq!(old.keys[0].expires, None);
+        assert_eq!(old.keys[1].expires, Some(1609372800));
+    }
+
+    #[test]
+    fn parse_inrelease() {
+        let message = inrelease();
+        let release = String::from_utf8(release()).unwrap();
+
+        assert_eq!(message.hashes, vec!["SHA512"]);
+        assert_eq!(message.text, release.trim_end());
+        assert_eq!(message.signatures.len(), 1);
+        assert_eq!(
+            message.signatures[0].issuer_fingerprint.as_deref(),
+            Some("CD45199F5834CBDE9B060BB047FB0CC1B2A43275")
+        );
+    }
+
+    #[test]
+    fn verify_inrelease() {
+        keyring("rsa.gpg").verify_cleartext(&inrelease()).unwrap();
+    }
+
+    #[test]
+    fn verify_dash_escaped() {
+        let content = fs::read_to_string(data("signature/dash.txt.asc")).unwrap();
+        let message = CleartextMessage::parse(&content).unwrap();
+
+        assert_eq!(
+            message.text,
+            "Dash escaped message\n-----BEGIN not an armor\n- a list item\n\
+             trailing whitespace   \n\tindented\n--\nlast line"
+        );
+        keyring("ed25519.asc").verify_cleartext(&message).unwrap();
+    }
+
+    #[test]
+    fn verify_detached() {
+        let signature = fs::read(data("repo/dists/stable/Release.gpg")).unwrap();
+        keyring("ed25519.asc")
+            .verify_detached(&release(), &signature)
+            .unwrap();
+
+        let signature = fs::read(data("signature/Release.binary.sig")).unwrap();
+        keyring("rsa.gpg")
+            .verify_detached(&release(), &signature)
+            .unwrap();
+    }
+
+    #[test]
+    fn verify_with_multiple_keyrings() {
+        let mut keys = keyring("other.asc");
+        keys.extend(keyring("rsa.gpg"));
+        keys.verify_cleartext(&inrelease()).unwrap();
+    }
+
+    #[test]
+    fn reject_unknown_key() {
+        let err = keyring("other.asc")
+            .verify_cleartext(&inrelease())
+            .unwrap_err();
+        assert_eq!(err.kind(), &ErrorKind::UnknownKey);
+    }
+
+    #[test]
+    fn reject_modified_data() {
+        let signature = fs::read(data("repo/dists/stable/Release.gpg")).unwrap();
+        let mut release = release();
+        release[0] = b'X';
+        let err = keyring("ed25519.asc")
+            .verify_detached(&release, &signature)
+            .unwrap_err();
+        assert_eq!(err.kind(), &ErrorKind::SignatureInvalid);
+
+        let mut message = inrelease();
+        message.text = message.text.replace("Raptobo", "Modified");
+        let err = keyring("rsa.gpg").verify_cleartext(&message).unwrap_err();
+        assert_eq!(err.kind(), &ErrorKind::SignatureInvalid);
+    }
+
+    #[test]
+    fn reject_expired() {
+        let old = keyring("old.asc");
+
+        let signature = fs::read(data("signature/Release.sig-expired.gpg")).unwrap();
+        let err = old.verify_detached(&release(), &signature).unwrap_err();
+        assert_eq!(err.kind(), &ErrorKind::SignatureExpired);
+
+        let signature = fs::read(data("signature/Release.key-expired.gpg")).unwrap();
+        let err = old.verify_detached(&release(), &signature).unwrap_err();
+        assert_eq!(err.kind(), &ErrorKind::KeyExpired);
+    }
+
+    #[test]
+    fn ignore_invalid_self_signatures() {
+        // the expiration of the second key is set by a corrupted self-signature
+        let text = fs::read_to_string(data("keys/old.asc")).unwrap();
+        let mut keys = dearmor(&text).unwrap().concat();
+        let last = keys.len() - 1;
+        keys[last] ^= 0x01;
+
+        let mut old = Keyring::new();
+        old.add_keys(&keys).unwrap();
+        assert_eq!(old.keys.len(), 2);
+        assert_eq!(old.keys[1].expires, None);
+    }
+
+    #[test]
+    fn skip_subkeys_of_unsupported_keys() {
+        // new format packet with the given tag
+        let packet = |tag: u8, body: &[u8]| {
+            let mut packet = vec![0xC0 | tag, body.len() as u8];
+            packet.extend(body);
+            packet
+        };
+        let text = fs::read_to_string(data("keys/ed25519.asc")).unwrap();
+        let ed25519 = dearmor(&text).unwrap().concat();
+
+        // v5 primary key with a subkey and a signature, followed by a supported key
+        let mut keys = packet(6, &[5, 0x5F, 0x00, 0x00, 0x00, 22, 0, 0, 0, 0]);
+        keys.extend(packet(14, &[4, 0x5F, 0x00, 0x00, 0x00, 22, 9, 1]));
+        keys.extend(packet(2, &[4, 0x18, 22, 10, 0, 0, 0, 0]));
+        keys.extend(&ed25519);
+
+        let mut keyring = Keyring::new();
+        keyring.add_keys(&keys).unwrap();
+        assert_eq!(keyring.keys.len(), 1);
+        assert_eq!(
+            keyring.keys[0].fingerprint,
+            "F13B206CC4919EF750715FD4C1EBF77D6455818B"
+        );
+
+        // a subkey before any primary key is an error
+        let mut keys = packet(14, &[4, 0x5F, 0x00, 0x00, 0x00, 22, 9, 1]);
+        keys.extend(&ed25519);
+        assert!(Keyring::new().add_keys(&keys).is_err());
+    }
+
+    #[test]
+    fn reject_revoked() {
+        let revoked = keyring("revoked.asc");
+        assert_eq!(revoked.keys.len(), 3);
+        assert!(revoked.keys[0].revoked);
+        assert!(!revoked.keys[1].revoked);
+        assert!(revoked.keys[2].revoked);
+
+        for file in ["Release.revoked.gpg", "Release.subkey-revoked.gpg"] {
+            let signature = fs::read(data(&format!("signature/{}", file))).unwrap();
+            let err = revoked.verify_detached(&release(), &signature).unwrap_err();
+            assert_eq!(err.kind(), &ErrorKind::KeyRevoked);
+        }
+    }
+
+    #[test]
+    fn reject_sha1() {
+        let signature = fs::read(data("signature/Release.sha1.gpg")).unwrap();
+        let err = keyring("weak.asc")
+            .verify_detached(&release(), &signature)
+            .unwrap_err();
+        assert_eq!(err.kind(), &ErrorKind::SignatureInvalid);
+        assert!(err.to_string().contains("SHA1"));
+    }
+
+    #[test]
+    fn reject_missing_signature() {
+        let release = String::from_utf8(release()).unwrap();
+        let err = CleartextMessage::parse(&release).unwrap_err();
+        assert_eq!(err.kind(), &ErrorKind::SignatureMissing);
+
+        let err = keyring("rsa.gpg")
+            .verify_detached(release.as_bytes(), b"")
+            .unwrap_err();
+        assert_eq!(err.kind(), &ErrorKind::SignatureMissing);
+    }
+}
@@ -0,0 +1,328 @@
+use crate::deb822::{
+    comma_separated, from_stanza_extra, parse_stanzas, to_stanza, Stanza, StanzaReader,
+    SOURCES_FIELD_ORDER,
+};
+use crate::error::RaptoboError;
+use crate::package::{relations, PackageListItem, PackageRelation, PackageVcs, PackageVersion};
+use crate::person::{persons, Person};
+use crate::utils::File;
+use serde::{Deserialize, Serialize};
+use std::io::BufRead;
+
+/// Source package of a Sources index, Debian Policy 5.4 and 5.6.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct SourcePackage {
+    /// Source package name, Debian policy 5.6.1
+    #[serde(rename = "Package")]
+    pub package: String,
+    /// Format of the source package, Debian Policy 5.6.16
+    #[serde(rename = "Format")]
+    pub format: Option<String>,
+    /// list of binary package names built from this source, Debian Policy 5.6.19
+    #[serde(rename = "Binary", default, with = "comma_separated")]
+    pub binary: Option<Vec<String>>,
+    /// Architectures the binary packages can be built for, Debian Policy 5.6.8
+    #[serde(rename = "Architecture", default)]
+    pub architecture: Vec<String>,
+    /// Version, Debian policy 5.6.12
+    #[serde(rename = "Version")]
+    pub version: PackageVersion,
+    /// Section, Debian policy 5.6.5
+    #[serde(rename = "Section")]
+    pub section: Option<String>,
+    /// Priority, Debian policy 5.6.6
+    #[serde(rename = "Priority")]
+    pub priority: Option<String>,
+    /// Maintainers name and email address, RFC822 format, Debian policy 5.6.2
+    #[serde(rename = "Maintainer")]
+    pub maintainer: Option<Person>,
+    /// maintainer of the original package
+    #[serde(rename = "Original-Maintainer")]
+    pub original_maintainer: Option<Person>,
+    /// List of the names and email addresses of co-maintainers of the package, Debian policy 5.6.3
+    #[serde(rename = "Uploaders", default, with = "persons")]
+    pub uploaders: Option<Vec<Person>>,
+    /// Version, Debian policy 5.6.11
+    #[serde(rename = "Standards-Version")]
+    pub standards_version: Option<String>,
+    // Build relationships, Debian Policy 7.7
+    /// needed to build any package of the source
+    #[serde(rename = "Build-Depends", default, with = "relations")]
+    pub build_depends: Option<Vec<PackageRelation>>,
+    /// needed to build the architecture dependent packages
+    #[serde(rename = "Build-Depends-Arch", default, with = "relations")]
+    pub build_depends_arch: Option<Vec<PackageRelation>>,
+    /// needed to build the architecture independent packages
+    #[serde(rename = "Build-Depends-Indep", default, with = "relations")]
+    pub build_depends_indep: Option<Vec<PackageRelation>>,
+    /// must not be installed to build any package of the source
+    #[serde(rename = "Build-Conflicts", default, with = "relations")]
+    pub build_conflicts: Option<Vec<PackageRelation>>,
+    /// must not be installed to build the architecture dependent packages
+    #[serde(rename = "Build-Conflicts-Arch", default, with = "relations")]
+    pub build_conflicts_arch: Option<Vec<PackageRelation>>,
+    /// must not be installed to build the architecture independent packages
+    #[serde(rename = "Build-Conflicts-Indep", default, with = "relations")]
+    pub build_conflicts_indep: Option<Vec<PackageRelation>>,
+    /// list of test names, Debian Policy 5.6.30
+    #[serde(rename = "Testsuite", default, with = "comma_separated")]
+    pub testsuite: Option<Vec<String>>,
+    /// packages triggering the tests
+    #[serde(rename = "Testsuite-Triggers", default, with = "comma_separated")]
+    pub testsuite_triggers: Option<Vec<String>>,
+    /// Homepage of the project this package belongs to, Debian Policy 5.6.23
+    #[serde(rename = "Homepage")]
+    pub homepage: Option<String>,
+    /// web interface for browsing the source repository, Debian Policy 5.6.26
+    #[serde(rename = "Vcs-Browser")]
+    pub vcs_browser: Option<String>,
+    /// source repository, Debian Policy 5.6.26
+    #[serde(skip)]
+    pub vcs: Option<PackageVcs>,
+    /// git hash of package, Debian Policy 5.6.29
+    #[serde(rename = "Dgit")]
+    pub dgit: Option<String>,
+    /// list of packages built by this source package, Debian Policy 5.6.27
+    #[serde(rename = "Package-List", default)]
+    pub package_list: Vec<PackageListItem>,
+    // Sources index values
+    /// path to the source package files, relative to the base of the repository
+    #[serde(rename = "Directory")]
+    pub directory: String,
+    /// list of files of this source package with md5 checksums, Debian Policy 5.6.21
+    #[serde(rename = "Files", default)]
+    pub files: Vec<File>,
+    /// list of files of this source package with sha1 checksums, Debian Policy 5.6.24
+    #[serde(rename = "Checksums-Sha1", default)]
+    pub checksums_sha1: Vec<File>,
+    /// list of files of this source package with sha256 checksums, Debian Policy 5.6.24
+    #[serde(rename = "Checksums-Sha256", default)]
+    pub checksums_sha256: Vec<File>,
+    /// list of files of this source package with sha512 checksums, Debian Policy 5.6.24
+    #[serde(rename = "Checksums-Sha512", default)]
+    pub checksums_sha512: Vec<File>,
+    // Repository values
+    /// component of the Sources index this package was loaded from
+    #[serde(skip)]
+    pub component: Option<String>,
+    /// fields without a typed value, in their original order
+    #[serde(skip)]
+    pub extra_fields: Stanza,
+    /// errors of the fields with invalid values, which are kept as extra fields
+    #[serde(skip)]
+    pub field_errors: Vec<RaptoboError>,
+}
+
+/// Build relation fields, invalid values are errors to not lose build dependencies.
+const BUILD_RELATIONS: &[&str] = &[
+    "Build-Depends",
+    "Build-Depends-Arch",
+    "Build-Depends-Indep",
+    "Build-Conflicts",
+    "Build-Conflicts-Arch",
+    "Build-Conflicts-Indep",
+];
+
+impl SourcePackage {
+    /// Parse a source package from its stanza.
+    ///
+    /// Fields without a typed value, and optional fields with invalid values,
+    /// e.g. a Maintainer without address, are kept as extra fields. The errors
+    /// of the invalid fields are reported in `field_errors`.
+    pub fn new(stanza: Stanza) -> Result<SourcePackage, RaptoboError> {
+        let vcs = PackageVcs::parse(&stanza);
+        let known: Vec<&str> = vcs.iter().map(|v| v.vcs_type.field_name()).collect();
+        let (mut package, extra, errors): (SourcePackage, _, _) =
+            from_stanza_extra(&stanza, BUILD_RELATIONS, &known)?;
+        package.vcs = vcs;
+        package.extra_fields = extra;
+
+        for e in errors {
+            log::warn!(
+                "[SourcePackage] {} {}: {}",
+                package.package,
+                package.version,
+                e
+            );
+            package.field_errors.push(e);
+        }
+
+        Ok(package)
+    }
+
+    /// Convert the source package back to a stanza, ordered like in Sources indices.
+    pub fn to_stanza(&self) -> Result<Stanza, RaptoboError> {
+        let mut stanza = to_stanza(self)?;
+
+        if let Some(vcs) = &self.vcs {
+            stanza.set_value(vcs.vcs_type.field_name(), &vcs.url);
+        }
+        for field in self.extra_fields.fields() {
+            stanza.insert(&field.name, field.value.clone());
+        }
+        stanza.sort_fields(SOURCES_FIELD_ORDER);
+
+        Ok(stanza)
+    }
+
+    /// Path of the given file of this source package, relative to the base of the repository.
+    pub fn file_path(&self, file: &File) -> String {
+        format!("{}/{}", self.directory, file.path)
+    }
+
+    /// Parse the source packages of a Sources index lazily from the reader.
+    pub fn read<R: BufRead>(
+        reader: R,
+    ) -> impl Iterator<Item = Result<SourcePackage, RaptoboError>> {
+        SourcePackage::from_stanzas(StanzaReader::new(reader))
+    }
+
+    /// Parse the source packages of the given stanzas lazily, invalid packages are skipped.
+    pub fn from_stanzas<I>(stanzas: I) -> impl Iterator<Item = Result<SourcePackage, RaptoboError>>
+    where
+        I: Iterator<Item = Result<Stanza, RaptoboError>>,
+    {
+        parse_stanzas(stanzas, SourcePackage::new, "SourcePackage::parse")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SourcePackage;
+    use crate::deb822::{Stanza, StanzaReader};
+    use crate::package::{PackageRelation, VcsType};
+    use std::fs;
+
+    fn sources() -> Vec<SourcePackage> {
+        let path = format!(
+            "{}/tests/data/repo/dists/stable/main/source/Sources",
+            env!("CARGO_MANIFEST_DIR")
+        );
+        let content = fs::read(path).unwrap();
+        SourcePackage::read(content.as_slice())
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap()
+    }
+
+    fn relations(relations: &Option<Vec<PackageRelation>>) -> Vec<String> {
+        relations.iter().flatten().map(|r| r.to_string()).collect()
+    }
+
+    #[test]
+    fn parse_sources() {
+        let sources = sources();
+        assert_eq!(sources.len(), 2);
+
+        let acl = &sources[0];
+        assert_eq!(acl.package, "acl");
+        assert_eq!(acl.version, *"2.3.1-1");
+        assert_eq!(
+            acl.binary.as_deref().unwrap(),
+            ["acl", "libacl1", "libacl1-dev"]
+        );
+        assert_eq!(acl.architecture, ["any"]);
+        assert_eq!(
+            relations(&acl.build_depends),
+            [
+                "debhelper-compat (= 13)",
+                "gettext",
+                "libattr1-dev (>= 1:2.4.44)"
+            ]
+        );
+        assert_eq!(
+            acl.testsuite_triggers.as_deref().unwrap(),
+            ["build-essential"]
+        );
+        assert!(matches!(acl.vcs.as_ref().unwrap().vcs_type, VcsType::Git));
+        assert_eq!(acl.package_list.len(), 3);
+        assert_eq!(acl.package_list[1].name, "libacl1");
+        assert_eq!(acl.package_list[1].section, "libs");
+        assert_eq!(acl.package_list[1].priority, "optional");
+        assert_eq!(acl.package_list[1].option("arch"), Some("any"));
+        assert_eq!(acl.files.len(), 3);
+        assert_eq!(acl.checksums_sha256[1].size, 355676);
+        assert_eq!(
+            acl.file_path(&acl.files[0]),
+            "pool/main/a/acl/acl_2.3.1-1.dsc"
+        );
+
+        let adsys = &sources[1];
+        assert_eq!(adsys.architecture, ["any", "all"]);
+        let uploaders = adsys.uploaders.as_ref().unwrap();
+        assert_eq!(uploaders.len(), 2);
+        assert_eq!(
+            uploaders[1].name.as_deref(),
+            Some("Jean-Baptiste Lallement")
+        );
+        assert_eq!(uploaders[1].email, "jean-baptiste@ubuntu.com");
+        let build_depends = relations(&adsys.build_depends);
+        assert_eq!(build_depends.len(), 7);
+        assert_eq!(build_depends[3], "golang-go (>= 2:1.18~)");
+        assert_eq!(
+            build_depends[6],
+            "samba-dsdb-modules [amd64 arm64] <!nocheck>"
+        );
+        assert_eq!(relations(&adsys.build_conflicts), ["golang-1.17-go"]);
+        assert!(adsys.build_depends_indep.is_none());
+    }
+
+    /// Field value with normalized whitespace, e.g. of a folded relation list.
+    fn normalized(value: &[String]) -> String {
+        let text = value.join(" ");
+        let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+        text.trim_end_matches(',').to_string()
+    }
+
+    #[test]
+    fn write_sources() {
+        let path = format!(
+            "{}/tests/data/repo/dists/stable/main/source/Sources",
+            env!("CARGO_MANIFEST_DIR")
+        );
+        let content = fs::read(path).unwrap();
+        let original: Vec<Stanza> = StanzaReader::new(content.as_slice())
+            .collect::<Result<_, _>>()
+            .unwrap();
+
+        let sources = sources();
+        assert_eq!(sources.len(), original.len());
+        for (package, original) in sources.iter().zip(&original) {
+            let stanza = package.to_stanza().unwrap();
+            assert_eq!(stanza.keys().next(), Some("Package"));
+            assert_eq!(stanza.len(), original.len());
+            for field in original.fields() {
+                let written = stanza.get(&field.name).unwrap();
+                assert_eq!(normalized(written), normalized(&field.value));
+            }
+        }
+    }
+
+    #[test]
+    fn keep_extra_fields() {
+        let content = "Package: hello\n\
+                       Version: 2.10-3\n\
+                       Directory: pool/main/h/hello\n\
+                       X-Custom: value\n\
+                       Extra-Source-Only: yes\n\
+                       Package-List:\n \
+                       hello deb devel optional arch=any profile=!nocheck essential=yes\n";
+        let package = SourcePackage::read(content.as_bytes())
+            .next()
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            package.extra_fields.keys().collect::<Vec<_>>(),
+            ["X-Custom", "Extra-Source-Only"]
+        );
+        assert_eq!(package.package_list[0].option("profile"), Some("!nocheck"));
+        assert_eq!(package.package_list[0].options.len(), 3);
+
+        let stanza = package.to_stanza().unwrap();
+        assert_eq!(stanza["X-Custom"], [" value"]);
+        assert_eq!(stanza["Extra-Source-Only"], [" yes"]);
+        assert_eq!(
+            stanza["Package-List"][1],
+            " hello deb devel optional arch=any profile=!nocheck essential=yes"
+        );
+    }
+}
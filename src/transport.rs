@@ -0,0 +1,486 @@
+use crate::error::RaptoboError;
+use curl::easy::Easy;
+use flate2::read::MultiGzDecoder;
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::fs;
+use std::io::{self, Cursor, Read};
+use std::path::{Component, Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+
+/// A transport fetches the content of repository files.
+pub trait Transport: Debug {
+    /// Fetch the content of the given URL.
+    fn fetch(&self, url: &str) -> Result<Vec<u8>, RaptoboError>;
+
+    /// Open the content of the given URL as a stream, e.g. to parse large indices
+    /// without holding the whole file in memory.
+    ///
+    /// Errors during the transfer are read errors. The default implementation
+    /// fetches the whole content.
+    fn open(&self, url: &str) -> Result<Box<dyn Read + Send>, RaptoboError> {
+        Ok(Box::new(Cursor::new(self.fetch(url)?)))
+    }
+}
+
+/// Select the transport for the given URI by its scheme.
+///
+/// - http, https and ftp: HttpTransport
+/// - file: FileTransport
+/// - tar+file: MemoryTransport with the files of the local tarball, e.g.
+///   "tar+file:///srv/repo.tar.gz", optionally gzip compressed
+/// - no scheme, i.e. a local path: DirectoryTransport rooted at this path
+///
+/// Other schemes are an error.
+pub fn transport_for(uri: &str) -> Result<Box<dyn Transport>, RaptoboError> {
+    let scheme = match uri.split_once("://") {
+        Some((scheme, _)) => scheme.to_ascii_lowercase(),
+        None => return Ok(Box::new(DirectoryTransport::new(uri, uri))),
+    };
+
+    match scheme.as_str() {
+        "http" | "https" | "ftp" => Ok(Box::new(HttpTransport)),
+        "file" => Ok(Box::new(FileTransport)),
+        "tar+file" => {
+            let path = FileTransport::path(uri)?;
+            let mut transport = MemoryTransport::new();
+            transport.load_tar(uri, &path.to_string_lossy())?;
+            Ok(Box::new(transport))
+        }
+        _ => Err(RaptoboError::new(&format!(
+            "[transport_for] {}: unsupported URI scheme {}!",
+            uri, scheme
+        ))),
+    }
+}
+
+/// Transport using curl, for http, https and ftp URLs.
+#[derive(Debug, Clone, Default)]
+pub struct HttpTransport;
+
+impl HttpTransport {
+    /// Transfer the content of the URL, chunk by chunk.
+    ///
+    /// The transfer is aborted if `write` returns false.
+    fn transfer<F: FnMut(&[u8]) -> bool>(url: &str, mut write: F) -> Result<(), RaptoboError> {
+        let mut easy = Easy::new();
+
+        easy.url(url)
+            .map_err(|e| RaptoboError::new(&e.to_string()))?;
+        // report HTTP errors, e.g. a missing InRelease file, instead of returning the error page
+        easy.fail_on_error(true)
+            .map_err(|e| RaptoboError::new(&e.to_string()))?;
+
+        let mut transfer = easy.transfer();
+        transfer
+            .write_function(|data| Ok(if write(data) { data.len() } else { 0 }))
+            .map_err(|e| RaptoboError::new(&e.to_string()))?;
+
+        transfer
+            .perform()
+            .map_err(|e| RaptoboError::new(&format!("{}: {}", url, e)))
+    }
+}
+
+impl Transport for HttpTransport {
+    fn fetch(&self, url: &str) -> Result<Vec<u8>, RaptoboError> {
+        let mut content = Vec::new();
+        HttpTransport::transfer(url, |data| {
+            content.extend_from_slice(data);
+            true
+        })?;
+        Ok(content)
+    }
+
+    /// Stream the content, the transfer runs in a separate thread.
+    fn open(&self, url: &str) -> Result<Box<dyn Read + Send>, RaptoboError> {
+        let (sender, receiver) = mpsc::sync_channel(16);
+        let url = url.to_string();
+
+        thread::spawn(move || {
+            // a dropped reader aborts the transfer
+            let result =
+                HttpTransport::transfer(&url, |data| sender.send(Ok(data.to_vec())).is_ok());
+            if let Err(e) = result {
+                let _ = sender.send(Err(e.to_string()));
+            }
+        });
+
+        Ok(Box::new(ChannelReader {
+            receiver,
+            chunk: Cursor::new(Vec::new()),
+        }))
+    }
+}
+
+/// Reader of the chunks sent by a transfer thread, an error ends the stream.
+struct ChannelReader {
+    receiver: Receiver<Result<Vec<u8>, String>>,
+    chunk: Cursor<Vec<u8>>,
+}
+
+impl Read for ChannelReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            let n = self.chunk.read(buf)?;
+            if n > 0 || buf.is_empty() {
+                return Ok(n);
+            }
+            match self.receiver.recv() {
+                Ok(Ok(chunk)) => self.chunk = Cursor::new(chunk),
+                Ok(Err(e)) => return Err(io::Error::other(e)),
+                // the transfer is complete
+                Err(_) => return Ok(0),
+            }
+        }
+    }
+}
+
+/// Transport for file:// URLs.
+#[derive(Debug, Clone, Default)]
+pub struct FileTransport;
+
+impl FileTransport {
+    /// Local path of a file:// URL, percent-decoded.
+    fn path(url: &str) -> Result<PathBuf, RaptoboError> {
+        let path = match url.split_once("://") {
+            Some((_, path)) => path,
+            None => url,
+        };
+        // file://localhost/path is the same as file:///path
+        let path = path.strip_prefix("localhost").unwrap_or(path);
+        Ok(PathBuf::from(percent_decode(path)?))
+    }
+}
+
+impl Transport for FileTransport {
+    fn fetch(&self, url: &str) -> Result<Vec<u8>, RaptoboError> {
+        read_file(&FileTransport::path(url)?)
+    }
+
+    fn open(&self, url: &str) -> Result<Box<dyn Read + Send>, RaptoboError> {
+        open_file(&FileTransport::path(url)?)
+    }
+}
+
+/// Transport serving all URLs below a base URI from a local directory, e.g. a mirror.
+#[derive(Debug, Clone)]
+pub struct DirectoryTransport {
+    /// URI prefix mapped to the directory.
+    pub base: String,
+    /// Root directory.
+    pub root: PathBuf,
+}
+
+impl DirectoryTransport {
+    pub fn new(base: &str, root: &str) -> DirectoryTransport {
+        DirectoryTransport {
+            base: base.trim_end_matches('/').to_string(),
+            root: PathBuf::from(root),
+        }
+    }
+}
+
+impl DirectoryTransport {
+    /// Local path of the URL, the root itself for the base URI.
+    fn path(&self, url: &str) -> Result<PathBuf, RaptoboError> {
+        let path = relative_path(&self.base, url)?;
+        if path.as_os_str().is_empty() {
+            // Path::join("") would append a slash, i.e. fail for a file
+            Ok(self.root.clone())
+        } else {
+            Ok(self.root.join(path))
+        }
+    }
+}
+
+impl Transport for DirectoryTransport {
+    fn fetch(&self, url: &str) -> Result<Vec<u8>, RaptoboError> {
+        read_file(&self.path(url)?)
+    }
+
+    fn open(&self, url: &str) -> Result<Box<dyn Read + Send>, RaptoboError> {
+        open_file(&self.path(url)?)
+    }
+}
+
+/// Transport serving files from memory, e.g. for tests.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryTransport {
+    /// File content by URL.
+    pub files: HashMap<String, Vec<u8>>,
+}
+
+impl MemoryTransport {
+    pub fn new() -> MemoryTransport {
+        MemoryTransport {
+            files: HashMap::new(),
+        }
+    }
+
+    /// Add a file with the given URL.
+    pub fn insert(&mut self, url: &str, content: Vec<u8>) {
+        self.files.insert(url.to_string(), content);
+    }
+
+    /// Add all files of a tarball, optionally gzip compressed, below the base URI.
+    pub fn load_tar(&mut self, base: &str, path: &str) -> Result<(), RaptoboError> {
+        let content = read_file(Path::new(path))?;
+
+        let reader: Box<dyn Read> = if content.starts_with(&[0x1F, 0x8B]) {
+            Box::new(MultiGzDecoder::new(content.as_slice()))
+        } else {
+            Box::new(content.as_slice())
+        };
+
+        let mut archive = tar::Archive::new(reader);
+        let entries = archive.entries().map_err(|e| {
+            RaptoboError::new(&format!("[MemoryTransport::load_tar] {}: {}", path, e))
+        })?;
+
+        for entry in entries {
+            let mut entry = entry.map_err(|e| {
+                RaptoboError::new(&format!("[MemoryTransport::load_tar] {}: {}", path, e))
+            })?;
+            if !entry.header().entry_type().is_file() {
+                continue;
+            }
+
+            let name = entry
+                .path()
+                .map_err(|e| RaptoboError::new(&e.to_string()))?
+                .to_string_lossy()
+                .trim_start_matches("./")
+                .to_string();
+
+            let mut data = Vec::new();
+            entry
+                .read_to_end(&mut data)
+                .map_err(|e| RaptoboError::new(&e.to_string()))?;
+
+            self.insert(&format!("{}/{}", base.trim_end_matches('/'), name), data);
+        }
+
+        Ok(())
+    }
+}
+
+impl Transport for MemoryTransport {
+    fn fetch(&self, url: &str) -> Result<Vec<u8>, RaptoboError> {
+        self.files
+            .get(url)
+            .cloned()
+            .ok_or(RaptoboError::new(&format!(
+                "[MemoryTransport] {} not found!",
+                url
+            )))
+    }
+}
+
+fn read_file(path: &Path) -> Result<Vec<u8>, RaptoboError> {
+    fs::read(path).map_err(|e| RaptoboError::new(&format!("{}: {}", path.display(), e)))
+}
+
+fn open_file(path: &Path) -> Result<Box<dyn Read + Send>, RaptoboError> {
+    let file = fs::File::open(path)
+        .map_err(|e| RaptoboError::new(&format!("{}: {}", path.display(), e)))?;
+    Ok(Box::new(file))
+}
+
+/// Decode the percent-encoded bytes of a URL path, e.g. "/srv/my%20repo".
+fn percent_decode(path: &str) -> Result<String, RaptoboError> {
+    let bytes = path.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] != b'%' {
+            decoded.push(bytes[i]);
+            i += 1;
+            continue;
+        }
+        let byte = bytes
+            .get(i + 1..i + 3)
+            .filter(|h| h.iter().all(u8::is_ascii_hexdigit))
+            .and_then(|h| u8::from_str_radix(std::str::from_utf8(h).ok()?, 16).ok())
+            .ok_or(RaptoboError::new(&format!(
+                "[percent_decode] {}: invalid escape at {}!",
+                path, i
+            )))?;
+        decoded.push(byte);
+        i += 3;
+    }
+
+    String::from_utf8(decoded)
+        .map_err(|e| RaptoboError::new(&format!("[percent_decode] {}: {}", path, e)))
+}
+
+/// Path of the URL relative to the base URI, without leaving the base.
+fn relative_path<'a>(base: &str, url: &'a str) -> Result<&'a Path, RaptoboError> {
+    let path = url
+        .strip_prefix(base)
+        .filter(|p| p.is_empty() || p.starts_with('/'))
+        .ok_or(RaptoboError::new(&format!(
+            "[DirectoryTransport] {} is not below {}!",
+            url, base
+        )))?;
+    let path = Path::new(path.trim_start_matches('/'));
+
+    if path
+        .components()
+        .any(|c| !matches!(c, Component::Normal(_)))
+    {
+        return Err(RaptoboError::new(&format!(
+            "[DirectoryTransport] invalid path {}!",
+            url
+        )));
+    }
+
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{percent_decode, transport_for, DirectoryTransport, MemoryTransport, Transport};
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use std::fs::{self, File};
+    use tar::Builder;
+
+    fn data() -> String {
+        format!("{}/tests/data", env!("CARGO_MANIFEST_DIR"))
+    }
+
+    #[test]
+    fn select_transport_by_scheme() {
+        let t = |uri: &str| format!("{:?}", transport_for(uri).unwrap());
+        assert_eq!(t("http://archive.ubuntu.com/ubuntu"), "HttpTransport");
+        assert_eq!(t("https://deb.debian.org/debian"), "HttpTransport");
+        assert_eq!(t("FTP://ftp.debian.org/debian"), "HttpTransport");
+        assert_eq!(t("file:///srv/mirror"), "FileTransport");
+        assert!(t("/srv/mirror").starts_with("DirectoryTransport"));
+
+        assert!(transport_for("htp://archive.ubuntu.com/ubuntu").is_err());
+        assert!(transport_for("s3://bucket/debian").is_err());
+    }
+
+    #[test]
+    fn decode_file_paths() {
+        assert_eq!(percent_decode("/srv/my%20repo").unwrap(), "/srv/my repo");
+        assert_eq!(percent_decode("/srv/a%2fb%C3%A4").unwrap(), "/srv/a/bä");
+        assert!(percent_decode("/srv/100%").is_err());
+        assert!(percent_decode("/srv/%+1").is_err());
+        assert!(percent_decode("/srv/%ff").is_err());
+
+        let dir = std::env::temp_dir().join(format!("raptobo {}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("Release"), b"Origin: Space").unwrap();
+        let url = format!(
+            "file://{}/Release",
+            dir.to_str().unwrap().replace(' ', "%20")
+        );
+        let content = transport_for(&url).unwrap().fetch(&url);
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(content.unwrap(), b"Origin: Space");
+    }
+
+    #[test]
+    fn file_transport() {
+        let release = format!("{}/repo/dists/stable/Release", data());
+        let content = transport_for(&format!("file://{}", release))
+            .unwrap()
+            .fetch(&format!("file://{}", release))
+            .unwrap();
+        assert!(content.starts_with(b"Origin: Raptobo"));
+
+        let content = transport_for("file://localhost/")
+            .unwrap()
+            .fetch(&format!("file://localhost{}", release))
+            .unwrap();
+        assert!(content.starts_with(b"Origin: Raptobo"));
+
+        assert!(transport_for("file:///")
+            .unwrap()
+            .fetch(&format!("file://{}/missing", data()))
+            .is_err());
+    }
+
+    #[test]
+    fn directory_transport() {
+        let root = format!("{}/repo", data());
+        let transport = DirectoryTransport::new("http://mirror.example.org/debian/", &root);
+
+        let content = transport
+            .fetch("http://mirror.example.org/debian/dists/stable/Release")
+            .unwrap();
+        assert!(content.starts_with(b"Origin: Raptobo"));
+
+        assert!(transport
+            .fetch("http://mirror.example.org/debian/dists/stable/missing")
+            .is_err());
+        assert!(transport
+            .fetch("http://mirror.example.org/debian/../repo/dists/stable/Release")
+            .is_err());
+        assert!(transport
+            .fetch("http://mirror.example.org/debian-security/dists/stable/Release")
+            .is_err());
+
+        let content = transport_for(&root)
+            .unwrap()
+            .fetch(&format!("{}/dists/stable/Release", root))
+            .unwrap();
+        assert!(content.starts_with(b"Origin: Raptobo"));
+
+        // a local path of a single file
+        let release = format!("{}/dists/stable/Release", root);
+        let content = transport_for(&release).unwrap().fetch(&release).unwrap();
+        assert!(content.starts_with(b"Origin: Raptobo"));
+    }
+
+    #[test]
+    fn memory_transport() {
+        let mut transport = MemoryTransport::new();
+        transport.insert("mem://repo/Release", b"Origin: Memory".to_vec());
+
+        assert_eq!(
+            transport.fetch("mem://repo/Release").unwrap(),
+            b"Origin: Memory"
+        );
+        assert!(transport.fetch("mem://repo/InRelease").is_err());
+    }
+
+    #[test]
+    fn memory_transport_from_tarball() {
+        let path = std::env::temp_dir().join(format!("raptobo-{}.tar.gz", std::process::id()));
+        {
+            let file = File::create(&path).unwrap();
+            let mut builder = Builder::new(GzEncoder::new(file, Compression::default()));
+            builder
+                .append_path_with_name(
+                    format!("{}/repo/dists/stable/Release", data()),
+                    "./dists/stable/Release",
+                )
+                .unwrap();
+            builder.into_inner().unwrap().finish().unwrap();
+        }
+
+        let mut transport = MemoryTransport::new();
+        let result = transport.load_tar("http://example.org/debian", path.to_str().unwrap());
+        let uri = format!("tar+file://{}", path.to_str().unwrap());
+        let selected = transport_for(&uri);
+        fs::remove_file(&path).unwrap();
+        result.unwrap();
+
+        let content = transport
+            .fetch("http://example.org/debian/dists/stable/Release")
+            .unwrap();
+        assert!(content.starts_with(b"Origin: Raptobo"));
+
+        let content = selected
+            .unwrap()
+            .fetch(&format!("{}/dists/stable/Release", uri))
+            .unwrap();
+        assert!(content.starts_with(b"Origin: Raptobo"));
+    }
+}
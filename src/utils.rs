@@ -1,121 +1,111 @@
+use crate::deb822::{Stanza, StanzaReader};
 use crate::error::RaptoboError;
+use crate::index::Compression;
+use crate::transport::transport_for;
 use chrono::{DateTime, FixedOffset};
-use curl::easy::Easy;
-use flate2::read::GzDecoder;
-use std::collections::HashMap;
-use std::io::{Cursor, Read};
+use flate2::read::MultiGzDecoder;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::io::{BufRead, Cursor, Read};
 
 pub fn download_xz(url: &str) -> Result<Vec<String>, RaptoboError> {
-    let mut content = download_raw(url)?;
+    let content = download_raw(url)?;
     log::debug!("[download_xz] len: {}", content.len());
 
-    let decompressed = lzma::decompress(&mut content)
-    .map_err(|e| RaptoboError::new(&e.to_string()))?;
-    let data = String::from_utf8(decompressed)
-    .map_err(|e| RaptoboError::new(&e.to_string()))?;
-
-    let data = data.split("\n").map(|l| l.to_string()).collect();
+    let data = decompress_xz(&content)?;
+    let data = String::from_utf8(data).map_err(|e| RaptoboError::new(&e.to_string()))?;
 
-    Ok(data)
+    Ok(split_lines(&data))
 }
 
 pub fn download_gz(url: &str) -> Result<Vec<String>, RaptoboError> {
     let content = download_raw(url)?;
-    let content = Cursor::new(content);
-    let mut decoder = GzDecoder::new(content);
-    let mut data = String::new();
-    let _len = decoder
-        .read_to_string(&mut data)
-        .map_err(|e| RaptoboError::new(&e.to_string()))?;
-    let data = data.split("\n").map(|l| l.to_string()).collect();
 
-    Ok(data)
+    let data = decompress_gz(&content)?;
+    let data = String::from_utf8(data).map_err(|e| RaptoboError::new(&e.to_string()))?;
+
+    Ok(split_lines(&data))
 }
 
-pub fn download_raw(url: &str) -> Result<Vec<u8>, RaptoboError> {
-    let mut easy = Easy::new();
+/// Decompress data, detecting the compression by its magic bytes.
+///
+/// Data without known magic bytes is returned unchanged.
+pub fn decompress(content: &[u8]) -> Result<Vec<u8>, RaptoboError> {
+    Compression::detect(content).decompress(content)
+}
 
-    easy.url(&url)
+/// Wrap the reader with a decompressing reader, detecting the compression by its magic bytes.
+///
+/// Data without known magic bytes is read unchanged.
+pub fn decompress_reader<'a, R: BufRead + 'a>(
+    mut reader: R,
+) -> Result<Box<dyn BufRead + 'a>, RaptoboError> {
+    let magic = reader
+        .fill_buf()
         .map_err(|e| RaptoboError::new(&e.to_string()))?;
+    Compression::detect(magic).reader(reader)
+}
 
-    let mut content = Vec::new();
-    {
-        let mut transfer = easy.transfer();
-        transfer
-            .write_function(|data| {
-                content.extend_from_slice(data);
-                Ok(data.len())
-            })
-            .map_err(|e| RaptoboError::new(&e.to_string()))?;
-
-        transfer
-            .perform()
-            .map_err(|e| RaptoboError::new(&e.to_string()))?;
-    }
-
-    Ok(content)
+/// Decompress xz or lzma compressed data.
+pub fn decompress_xz(content: &[u8]) -> Result<Vec<u8>, RaptoboError> {
+    lzma::decompress(content).map_err(|e| RaptoboError::new(&e.to_string()))
 }
 
-pub fn download(url: &str) -> Result<Vec<String>, RaptoboError> {
-    let content = download_raw(url)?;
+/// Decompress gzip compressed data, including all concatenated members.
+pub fn decompress_gz(content: &[u8]) -> Result<Vec<u8>, RaptoboError> {
+    read_all(MultiGzDecoder::new(Cursor::new(content)))
+}
 
-    let content = String::from_utf8(content).map_err(|e| RaptoboError::new(&e.to_string()))?;
+/// Decompress bzip2 compressed data.
+#[cfg(feature = "bzip2")]
+pub fn decompress_bz2(content: &[u8]) -> Result<Vec<u8>, RaptoboError> {
+    read_all(bzip2::read::MultiBzDecoder::new(content))
+}
 
-    let content = content.split("\n").map(|l| l.to_string()).collect();
+/// Decompress zstd compressed data.
+#[cfg(feature = "zstd")]
+pub fn decompress_zst(content: &[u8]) -> Result<Vec<u8>, RaptoboError> {
+    zstd::stream::decode_all(content).map_err(|e| RaptoboError::new(&e.to_string()))
+}
 
-    Ok(content)
+/// Decompress lz4 frame compressed data.
+#[cfg(feature = "lz4")]
+pub fn decompress_lz4(content: &[u8]) -> Result<Vec<u8>, RaptoboError> {
+    read_all(lz4_flex::frame::FrameDecoder::new(content))
 }
 
-pub fn parse_metadata(
-    content: Vec<String>,
-) -> Result<Vec<HashMap<String, Vec<String>>>, RaptoboError> {
+fn read_all<R: Read>(mut reader: R) -> Result<Vec<u8>, RaptoboError> {
     let mut data = Vec::new();
-    let mut stanza = HashMap::new();
+    reader
+        .read_to_end(&mut data)
+        .map_err(|e| RaptoboError::new(&e.to_string()))?;
 
-    let mut key: String = String::from("");
-    let mut value: Vec<String> = Vec::new();
+    Ok(data)
+}
 
-    for line in content.into_iter() {
-        if line.trim().is_empty() {
-            // new stanza
-            if !stanza.is_empty() {
-                data.push(stanza);
-            }
+/// Download the given URL, using the transport selected by the URL scheme.
+pub fn download_raw(url: &str) -> Result<Vec<u8>, RaptoboError> {
+    transport_for(url)?.fetch(url)
+}
 
-            stanza = HashMap::new();
+pub fn download(url: &str) -> Result<Vec<String>, RaptoboError> {
+    let content = download_raw(url)?;
 
-            continue;
-        }
+    let content = String::from_utf8(content).map_err(|e| RaptoboError::new(&e.to_string()))?;
 
-        if line.starts_with(" ") {
-            // follow up line
-            value.push(line);
-        } else {
-            if !value.is_empty() {
-                stanza.insert(key, value);
-                value = Vec::new();
-            }
+    Ok(split_lines(&content))
+}
 
-            match line.split_once(":") {
-                None => {
-                    log::debug!("[parse_metadata] invalid line, missing key: {}", line);
-                    key = String::from("")
-                }
-                Some((k, v)) => {
-                    key = String::from(k);
-                    value.push(String::from(v));
-                }
-            }
-        }
-    }
+/// Split a text into lines, as expected by parse_metadata.
+pub fn split_lines(content: &str) -> Vec<String> {
+    content.split('\n').map(|l| l.to_string()).collect()
+}
 
-    Ok(data)
+pub fn parse_metadata(content: Vec<String>) -> Result<Vec<Stanza>, RaptoboError> {
+    StanzaReader::from_lines(content.into_iter().map(Ok)).collect()
 }
 
-pub fn stanza_value(
-    key: &str,
-    stanza: &HashMap<String, Vec<String>>,
-) -> Result<String, RaptoboError> {
+pub fn stanza_value(key: &str, stanza: &Stanza) -> Result<String, RaptoboError> {
     let value = stanza.get(key).ok_or(RaptoboError::new(&format!(
         "[stanza_value] {} not found!",
         &key
@@ -124,17 +114,14 @@ pub fn stanza_value(
     Ok(value.trim().to_string())
 }
 
-pub fn stanza_opt_value(key: &str, stanza: &HashMap<String, Vec<String>>) -> Option<String> {
+pub fn stanza_opt_value(key: &str, stanza: &Stanza) -> Option<String> {
     match stanza_value(key, stanza) {
         Ok(value) => Some(value),
         Err(_) => None,
     }
 }
 
-pub fn stanza_list(
-    key: &str,
-    stanza: &HashMap<String, Vec<String>>,
-) -> Result<Vec<String>, RaptoboError> {
+pub fn stanza_list(key: &str, stanza: &Stanza) -> Result<Vec<String>, RaptoboError> {
     let values = stanza.get(key).ok_or(RaptoboError::new(&format!(
         "[stanza_list] {} not found!",
         &key
@@ -148,7 +135,7 @@ pub fn stanza_list(
     Ok(values)
 }
 
-pub fn stanza_opt_list(key: &str, stanza: &HashMap<String, Vec<String>>) -> Option<Vec<String>> {
+pub fn stanza_opt_list(key: &str, stanza: &Stanza) -> Option<Vec<String>> {
     let list = match stanza_list(key, stanza) {
         Ok(list) => list,
         Err(_e) => return None,
@@ -160,10 +147,7 @@ pub fn stanza_opt_list(key: &str, stanza: &HashMap<String, Vec<String>>) -> Opti
     }
 }
 
-pub fn stanza_text(
-    key: &str,
-    stanza: &HashMap<String, Vec<String>>,
-) -> Result<String, RaptoboError> {
+pub fn stanza_text(key: &str, stanza: &Stanza) -> Result<String, RaptoboError> {
     let values = stanza.get(key).ok_or(RaptoboError::new(&format!(
         "[stanza_text] {} not found!",
         &key
@@ -175,10 +159,7 @@ pub fn stanza_text(
     Ok(text)
 }
 
-pub fn stanza_date(
-    key: &str,
-    stanza: &HashMap<String, Vec<String>>,
-) -> Option<DateTime<FixedOffset>> {
+pub fn stanza_date(key: &str, stanza: &Stanza) -> Option<DateTime<FixedOffset>> {
     let value = stanza_value(key, stanza);
     match value {
         Err(_) => None,
@@ -192,7 +173,7 @@ pub fn stanza_date(
     }
 }
 
-pub fn stanza_opt_text(key: &str, stanza: &HashMap<String, Vec<String>>) -> Option<String> {
+pub fn stanza_opt_text(key: &str, stanza: &Stanza) -> Option<String> {
     let text = stanza_text(key, stanza);
     match text {
         Ok(text) => Some(text),
@@ -202,7 +183,7 @@ pub fn stanza_opt_text(key: &str, stanza: &HashMap<String, Vec<String>>) -> Opti
 
 pub fn stanza_lines(
     key: &str,
-    stanza: &HashMap<String, Vec<String>>,
+    stanza: &Stanza,
     filter_empty: bool,
 ) -> Result<Vec<String>, RaptoboError> {
     let values = stanza.get(key).ok_or(RaptoboError::new(&format!(
@@ -220,17 +201,22 @@ pub fn stanza_lines(
     Ok(values)
 }
 
-#[derive(Debug)]
+/// Entry of a file list, e.g. "hash size path" of a Release or Sources file.
+#[derive(Debug, Deserialize, Serialize)]
 pub struct File {
     pub hash: String,
     pub size: u64,
     pub path: String,
 }
 
-pub fn stanza_files(
-    key: &str,
-    stanza: &HashMap<String, Vec<String>>,
-) -> Result<Vec<File>, RaptoboError> {
+impl fmt::Display for File {
+    /// Format as line of a file list, e.g. Files or Checksums-Sha256 of a source package.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {} {}", self.hash, self.size, self.path)
+    }
+}
+
+pub fn stanza_files(key: &str, stanza: &Stanza) -> Result<Vec<File>, RaptoboError> {
     let lines = stanza_lines(key, stanza, true)?;
 
     let mut files: Vec<File> = Vec::new();
@@ -263,7 +249,7 @@ pub fn stanza_files(
     Ok(files)
 }
 
-pub fn stanza_opt_files(key: &str, stanza: &HashMap<String, Vec<String>>) -> Option<Vec<File>> {
+pub fn stanza_opt_files(key: &str, stanza: &Stanza) -> Option<Vec<File>> {
     let files = stanza_files(key, stanza);
     match files {
         Ok(files) => {